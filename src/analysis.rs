@@ -17,7 +17,7 @@
  * and navigate to version 3 of the GNU General Public License.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
 use std::num::NonZeroU16;
@@ -25,10 +25,13 @@ use std::ops::{Deref, RangeBounds};
 use std::str::FromStr;
 use calamine::{DataType, Range};
 use log::Level;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use crate::common::*;
-use crate::merge::{Column, ColumnLabel, MergeXL, RowData};
+use crate::merge::{Column, ColumnLabel, ConflictPolicy, Institution, MergeXL, RowData};
+use crate::value::{Footnote, Value, ValueOptions};
 
-const UNSUPPORTED_SHEETS: [(&'static str, &'static str); 4] = [
+const UNSUPPORTED_SHEETS: [(&str, &str); 4] = [
     // We can't read the sheets pertaining to government bonds, which use daily timestamps
     ("BD(Govt) Treasury Bond", "Government securities/bonds sheet unsupported"),
     // Neither can we read those detailing interest rates due to horizontal data
@@ -39,16 +42,242 @@ const UNSUPPORTED_SHEETS: [(&'static str, &'static str); 4] = [
     ("PROFIT RATE STRUCTURE OF THE ISLAMIC BANKS", "Islamic banks sheet unsupported")
 ];
 
-const SKIPPED_LABEL_ELEMENTS: [&'static str; 1] = ["Weight"];
+/// Sheets whose rows are institutions rather than periods, and the kind of cross-section each
+/// holds. These are read only in cross-sectional mode, and otherwise rejected as above
+const CROSS_SECTIONAL_SHEETS: [(&str, &str); 2] = [
+    ("BANK WISE ANNOUNCED INTEREST RATE STRUCTURE", "bank-interest-rates"),
+    ("PROFIT RATE STRUCTURE OF THE ISLAMIC BANKS", "islamic-bank-profit-rates")
+];
+
+const SKIPPED_LABEL_ELEMENTS: [&str; 1] = ["Weight"];
 
 const INFLATION_OLD_BASE_MARKER: &str = "(OB)";
 const INFLATION_NEW_BASE_MARKER: &str = "(NB)";
+/// The labels which tell apart the series of each price index base, when bases are split
+const OLD_BASE_LABEL: &str = "old base";
+const NEW_BASE_LABEL: &str = "new base";
 
 #[derive(Debug)]
 pub struct SheetAnalyzer<'p> {
     pub source: &'p str,
     pub name: &'p str,
-    pub sheet: Range<DataType>
+    pub sheet: Range<DataType>,
+    pub options: &'p AnalysisOptions
+}
+
+/// Settings which tune how sheets are analyzed
+#[derive(Clone, Debug)]
+pub struct AnalysisOptions {
+    pub values: ValueOptions,
+    /// Rows with a smaller fraction of their cells filled are skipped as noise
+    pub min_row_fill: f32,
+    /// How many consecutive unlabeled columns are skipped before the table is taken to end.
+    /// Some tables leave blank columns between their periods and their groups of data
+    pub max_empty_columns: usize,
+    pub years: YearWindow,
+    /// Whether sheets whose data starts in the very first row, leaving no room for labels,
+    /// are read with positional column names (col1, col2, ...) instead of being rejected
+    pub positional_labels: bool,
+    /// Whether sheets listing figures per bank are read into cross-sections keyed by
+    /// institution, instead of being rejected for lacking timestamps
+    pub cross_sectional: bool,
+    pub exclusions: SheetExclusions,
+    /// Orientations of particular sheets, by name ignoring case. Other sheets are vertical
+    pub orientations: Vec<(String, Orientation)>,
+    /// The columns holding the periods of particular sheets, by name ignoring case, for
+    /// sheets where a stray year in an earlier column misleads detection. The column is
+    /// found after any transposing. Other sheets have theirs detected
+    pub timestamp_columns: Vec<(String, TimestampColumn)>,
+    pub provisional: ProvisionalPolicy,
+    /// Which value is kept when sheets overlap, giving different values for the same cell
+    pub conflicts: ConflictPolicy,
+    /// If set, columns of price indices such as the CPI are split into old-base and new-base columns
+    /// rather than discarding data of the old base. Rows not marked with their base are of
+    /// the new base if their period starts in or after July of this year, when the fiscal
+    /// year adopting the new base begins. Bangladesh rebased its CPI in 2012-13
+    pub price_base_cutover: Option<Year>
+}
+
+/// How a sheet lays out its data
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Orientation {
+    /// Periods run down the rows, as most sheets have them
+    #[default]
+    Vertical,
+    /// Periods run across the columns. The sheet is transposed before it is read
+    Horizontal
+}
+
+/// Where the timestamps of a sheet are, overriding their detection
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TimestampColumn {
+    /// The column at this index, counting from zero
+    Index(usize),
+    /// The leftmost column with a cell reading this header, ignoring case
+    Header(String)
+}
+
+/// The base year of a price index
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PriceBase {
+    Old,
+    New
+}
+
+/// What becomes of provisional figures, marked with a trailing P as in 2022-23P or JulyP.
+/// Every row from the first provisional period onward is provisional
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProvisionalPolicy {
+    /// Reading stops at the first provisional period, since its figures will be revised
+    #[default]
+    Exclude,
+    /// Provisional rows are read like any other
+    Keep,
+    /// Provisional rows are read and flagged as such in their sheet
+    Flag
+}
+
+/// Sheets skipped without analysis, by name. Naming varies across years, so tables of
+/// contents may need to be excluded by pattern
+#[derive(Clone, Debug)]
+pub struct SheetExclusions {
+    /// Exact names of excluded sheets, ignoring case
+    pub names: Vec<String>,
+    /// Sheets whose names match are excluded as well
+    pub pattern: Option<Regex>,
+    /// Whether sheets whose names start with "Appendix" are excluded
+    pub appendices: bool
+}
+
+/// The years which numeric cells may denote. Numbers outside the window are not years
+#[derive(Clone, Debug)]
+pub struct YearWindow {
+    pub earliest: u16,
+    /// How many years past the current year are accepted, for sheets which already label
+    /// the coming year or computers with skewed clocks
+    pub future_tolerance: u16,
+    /// If set, two-digit years as in compact tables are read, those up to the pivot in the
+    /// 2000s and the rest in the 1900s. Unset by default, since such cells may well be months
+    pub two_digit_pivot: Option<u8>
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            values: ValueOptions::default(),
+            min_row_fill: 0.15,
+            max_empty_columns: 3,
+            years: YearWindow::default(),
+            positional_labels: false,
+            cross_sectional: false,
+            exclusions: SheetExclusions::default(),
+            orientations: Vec::new(),
+            timestamp_columns: Vec::new(),
+            provisional: ProvisionalPolicy::default(),
+            conflicts: ConflictPolicy::default(),
+            price_base_cutover: None
+        }
+    }
+}
+
+impl AnalysisOptions {
+    /// The orientation of the named sheet, vertical unless overridden
+    pub fn orientation_of(&self, sheet_name: &str) -> Orientation {
+        self.orientations
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(sheet_name))
+            .map(|(_, orientation)| *orientation)
+            .unwrap_or_default()
+    }
+
+    /// The timestamp column of the named sheet, if overridden
+    pub fn timestamp_column_of(&self, sheet_name: &str) -> Option<&TimestampColumn> {
+        self.timestamp_columns
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(sheet_name))
+            .map(|(_, column)| column)
+    }
+}
+
+impl Default for SheetExclusions {
+    fn default() -> Self {
+        Self {
+            names: vec![String::from("Cover Page"), String::from("Contents")],
+            pattern: None,
+            appendices: true
+        }
+    }
+}
+
+impl PriceBase {
+    fn label(&self) -> ColumnLabel {
+        let label = match self {
+            Self::Old => OLD_BASE_LABEL,
+            Self::New => NEW_BASE_LABEL
+        };
+        ColumnLabel::create(label).expect("Base labels are valid")
+    }
+}
+
+impl FromStr for ProvisionalPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "exclude" => Ok(Self::Exclude),
+            "keep" => Ok(Self::Keep),
+            "flag" => Ok(Self::Flag),
+            other => Err(eyre::eyre!("Unknown provisional policy {}. Use exclude, keep, or flag", other))
+        }
+    }
+}
+
+impl FromStr for TimestampColumn {
+    type Err = eyre::Report;
+
+    /// Parses a header, or else a column number after #, where the first column is #1 as in
+    /// spreadsheets. Without the #, numbers are headers too, since sheets may be headed by years
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        match value.strip_prefix('#').map(|number| number.trim().parse::<usize>()) {
+            Some(Ok(0)) => Err(eyre::eyre!("Invalid timestamp column #0. Columns are numbered from #1")),
+            Some(Ok(number)) => Ok(Self::Index(number - 1)),
+            Some(Err(_)) => Err(eyre::eyre!("Invalid timestamp column {}. Use #number, such as #2, for a column number", value)),
+            None if value.is_empty() => Err(eyre::eyre!("Timestamp column is empty. Use a header or #number")),
+            None => Ok(Self::Header(value.to_string()))
+        }
+    }
+}
+
+impl SheetExclusions {
+    pub fn excludes(&self, sheet_name: &str) -> bool {
+        self.names.iter().any(|name| name.eq_ignore_ascii_case(sheet_name))
+            || self.pattern.as_ref().is_some_and(|pattern| pattern.is_match(sheet_name))
+            || (self.appendices && sheet_name.starts_with("Appendix"))
+    }
+}
+
+impl Default for YearWindow {
+    fn default() -> Self {
+        const INDEPENDENCE_YEAR: u16 = 1971;
+        Self {
+            earliest: INDEPENDENCE_YEAR,
+            future_tolerance: 1,
+            two_digit_pivot: None
+        }
+    }
+}
+
+impl YearWindow {
+    fn contains(&self, year: u16) -> bool {
+        year >= self.earliest && year <= current_year().saturating_add(self.future_tolerance)
+    }
+
+    /// Expands a two-digit year about the pivot, if two-digit years are read at all
+    fn expand_two_digit(&self, year: u16) -> Option<u16> {
+        let pivot = u16::from(self.two_digit_pivot?);
+        (year < 100).then(|| if year <= pivot { 2000 + year } else { 1900 + year })
+    }
 }
 
 impl Display for SheetAnalyzer<'_> {
@@ -66,19 +295,33 @@ pub enum AnalysisError {
     OtherFailure(ErrorBox)
 }
 
+/// The machine-readable discriminant of an [AnalysisError]. Its serialized names are stable
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum AnalysisErrorKind {
+    Unsupported,
+    NoData,
+    OtherFailure
+}
+
 #[derive(Debug)]
 pub struct ErrorBox(Box<dyn Error + Send + Sync + 'static>);
 
 impl Display for AnalysisError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.kind().describe(&self.reason(), f)
+    }
+}
+
+impl AnalysisErrorKind {
+    /// Every kind, those representing gaps in support first
+    pub const ALL: [Self; 3] = [Self::Unsupported, Self::OtherFailure, Self::NoData];
+
+    /// Writes the human-readable description of an error of this kind
+    pub fn describe(&self, reason: &str, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Unsupported { reason: what } => {
-                write!(f, "Format unsupported: {}", what)
-            },
-            Self::NoData => f.write_str("No non-provisional data"),
-            Self::OtherFailure(error) => {
-                write!(f, "Other: {}", error)
-            }
+            Self::Unsupported => write!(f, "Format unsupported: {}", reason),
+            Self::NoData => f.write_str(reason),
+            Self::OtherFailure => write!(f, "Other: {}", reason)
         }
     }
 }
@@ -102,6 +345,23 @@ impl AnalysisError {
     pub fn unsupported<M>(reason: M) -> Self where M: Into<String> {
         Self::Unsupported { reason: reason.into() }
     }
+
+    pub fn kind(&self) -> AnalysisErrorKind {
+        match self {
+            Self::Unsupported { .. } => AnalysisErrorKind::Unsupported,
+            Self::NoData => AnalysisErrorKind::NoData,
+            Self::OtherFailure(_) => AnalysisErrorKind::OtherFailure
+        }
+    }
+
+    /// The reason for the error, without mentioning its kind
+    pub fn reason(&self) -> String {
+        match self {
+            Self::Unsupported { reason } => reason.clone(),
+            Self::NoData => String::from("No non-provisional data"),
+            Self::OtherFailure(error) => error.to_string()
+        }
+    }
 }
 
 impl From<ErrorBox> for AnalysisError {
@@ -122,11 +382,21 @@ struct FirstYearlyTimestamp {
     cell: (usize, usize)
 }
 
+#[derive(Debug)]
 enum CellAsTimestamp<'d> {
     None,
     MayNeedContext(&'d str),
     YearlyTimestamp(YearlyTimestamp),
-    TimestampIsProvisional
+    /// A true date cell, identifying the month it falls in
+    Date(MonthlyReport),
+    /// A timestamp whose figures are provisional, read without its marker
+    Provisional(ProvisionalTimestamp)
+}
+
+#[derive(Debug)]
+enum ProvisionalTimestamp {
+    Yearly(YearlyTimestamp),
+    Month(Month)
 }
 
 trait CellInspector {
@@ -174,7 +444,7 @@ impl<const M: usize, const N: usize> CellInspector for SheetSupportInspector<'_,
 }
 
 /// Attempts to read a cell as a timestamp. If successful, it is guaranteed the timestamp
-/// is a year.
+/// is a year, or a date cell.
 ///
 /// This function has two purposes. It is used on the initial scan to find the first timestamp
 /// value in the sheet, checking along the way for signs that the sheet is unsupported via
@@ -182,68 +452,62 @@ impl<const M: usize, const N: usize> CellInspector for SheetSupportInspector<'_,
 ///
 /// Later, it is used again to load each timestamp as the data is collected from the rows.
 /// No checks are necessary for signs the sheet is unsupported.
-fn read_cell_as_timestamp<'d, I>(data_type: &'d DataType, inspector: &I) -> AnalysisResult<CellAsTimestamp<'d>>
+fn read_cell_as_timestamp<'d, I>(data_type: &'d DataType, years: &YearWindow,
+                                 inspector: &I) -> AnalysisResult<CellAsTimestamp<'d>>
     where I: CellInspector {
 
-    /// Attempts to read an integer value as a calendar yeear
-    fn try_as_calendar_year(year: u16) -> CellAsTimestamp<'static> {
-        const INDEPENDENCE_YEAR: u16 = 1971;
-
-        if year >= INDEPENDENCE_YEAR && year <= current_year() {
-            let calendar_year = Year(NonZeroU16::new(year).unwrap());
-            CellAsTimestamp::YearlyTimestamp(YearlyTimestamp::Calendar(calendar_year))
-        } else {
-            CellAsTimestamp::None
+    // Attempts to read an integer value as a calendar year within the window
+    let try_as_calendar_year = |year: i64| -> CellAsTimestamp<'static> {
+        let year = u16::try_from(year).ok().map(|year| years.expand_two_digit(year).unwrap_or(year));
+        match year.filter(|year| years.contains(*year)).and_then(NonZeroU16::new) {
+            Some(year) => CellAsTimestamp::YearlyTimestamp(YearlyTimestamp::Calendar(Year(year))),
+            None => CellAsTimestamp::None
         }
-    }
+    };
     Ok(match data_type {
         // Integer types
-        DataType::Int(year) => try_as_calendar_year(*year as u16),
-        DataType::Float(year) => try_as_calendar_year(year.round() as u16),
-        // Date types
-        DataType::DateTime(_) | DataType::Duration(_) => {
-            // Calamine should probably remove these enum variants if the feature is unset
-            log::trace!("Dates feature of calamine is not enabled for {}", data_type);
-            CellAsTimestamp::None
+        DataType::Int(year) => try_as_calendar_year(*year),
+        DataType::Float(year) => try_as_calendar_year(year.round() as i64),
+        // Date types. OpenDocument spreadsheets store their dates in ISO 8601 form
+        DataType::DateTime(_) | DataType::DateTimeIso(_) => {
+            // Dates are attributed to their month. There is no finer granularity, so rows
+            // dated within the same month are combined
+            match data_type.as_date().map(MonthlyReport::try_from) {
+                Some(Ok(report)) => CellAsTimestamp::Date(report),
+                _ => CellAsTimestamp::None
+            }
         },
+        DataType::Duration(_) | DataType::DurationIso(_) => CellAsTimestamp::None,
         // String
-        DataType::String(value) | DataType::DateTimeIso(value) | DataType::DurationIso(value) => {
+        DataType::String(value) => {
             let value = &mut value.as_str();
 
             // Check for unsupported cells
-            inspector.inspect_if_unsupported(&value)?;
+            inspector.inspect_if_unsupported(value)?;
+
+            // Markers may lead the timestamp too, or enclose it
+            *value = strip_timestamp_enclosure(value);
 
             for provisional_marker in ["P", "p", "(P)", "(p)"] {
-                if value.ends_with(provisional_marker) {
-                    let prior = &value[..value.len() - provisional_marker.len()];
+                if let Some(prior) = value.strip_suffix(provisional_marker) {
                     // Identify both provisional years and months
-                    if let Ok(YearlyTimestamp::Fiscal(_)) = YearlyTimestamp::from_str(prior) {
-                        return Ok(CellAsTimestamp::TimestampIsProvisional);
-                    } else if let Ok(_) = Month::from_str(prior) {
-                        return Ok(CellAsTimestamp::TimestampIsProvisional);
+                    if let Ok(timestamp @ YearlyTimestamp::Fiscal(_)) = YearlyTimestamp::from_str(prior) {
+                        return Ok(CellAsTimestamp::Provisional(ProvisionalTimestamp::Yearly(timestamp)));
+                    } else if let Ok(month) = Month::from_str(prior) {
+                        return Ok(CellAsTimestamp::Provisional(ProvisionalTimestamp::Month(month)));
                     }
                 }
             }
             // Make allowances for asterisks and other characters
-            // 'R' superscript means revised, ® can also be removed
-            if let Some(last_char) = value.chars().rev().next() {
-                match last_char {
-                    '*' | 'R' | '®' => {
-                        let byte_count = last_char.len_utf8();
-                        *value = &value[..value.len() - byte_count];
-                    },
-                    _ => {}
-                }
-            }
+            *value = strip_timestamp_footnote(value).0;
             // Inflation sheet uses these values to signify the change of base year
             // The base year identifier is added only for data recorded in both bases
-            // Keep data using the new base, ignore and discard data explicitly of the old base
-            if value.ends_with(INFLATION_OLD_BASE_MARKER) {
-                return Ok(CellAsTimestamp::None);
-            }
-            // Keep data which uses the new base
-            if value.ends_with(INFLATION_NEW_BASE_MARKER) {
-                *value = &value[..value.len() - INFLATION_NEW_BASE_MARKER.len()];
+            // Callers discard data explicitly of the old base, unless the bases are split
+            *value = strip_price_base(value).0;
+            if value.len() == 2 && value.bytes().all(|byte| byte.is_ascii_digit()) {
+                if let Some(year) = value.parse().ok().and_then(|year| years.expand_two_digit(year)) {
+                    return Ok(try_as_calendar_year(i64::from(year)));
+                }
             }
             if let Ok(timestamp) = YearlyTimestamp::from_str(value) {
                 CellAsTimestamp::YearlyTimestamp(timestamp)
@@ -256,6 +520,65 @@ fn read_cell_as_timestamp<'d, I>(data_type: &'d DataType, inspector: &I) -> Anal
     })
 }
 
+/// Splits the footnote marker from a timestamp, unless the marker is part of the timestamp
+/// itself, as the R ending DECEMBER is
+fn strip_timestamp_footnote(value: &str) -> (&str, Option<Footnote>) {
+    if is_period_name(value) {
+        (value, None)
+    } else {
+        Footnote::strip_from(value)
+    }
+}
+
+/// Strips leading asterisks and the brackets enclosing a timestamp, e.g. *2019 or [Jul-Sep].
+/// Only these are stripped, so that a leading sign is left alone
+fn strip_timestamp_enclosure(value: &str) -> &str {
+    let value = value.trim().trim_start_matches('*').trim_start();
+    for (open, close) in [('(', ')'), ('[', ']')] {
+        if let Some(enclosed) = value.strip_prefix(open).and_then(|inner| inner.strip_suffix(close)) {
+            return enclosed.trim();
+        }
+    }
+    value
+}
+
+/// Splits the marker of the price index base from a timestamp, e.g. 2011-12(OB)
+fn strip_price_base(value: &str) -> (&str, Option<PriceBase>) {
+    if let Some(prior) = value.strip_suffix(INFLATION_OLD_BASE_MARKER) {
+        (prior, Some(PriceBase::Old))
+    } else if let Some(prior) = value.strip_suffix(INFLATION_NEW_BASE_MARKER) {
+        (prior, Some(PriceBase::New))
+    } else {
+        (value, None)
+    }
+}
+
+/// The price index base which a timestamp cell is explicitly marked with
+fn price_base_of(data_type: &DataType) -> Option<PriceBase> {
+    match data_type {
+        DataType::String(value) => strip_price_base(strip_timestamp_footnote(strip_timestamp_enclosure(value)).0).1,
+        _ => None
+    }
+}
+
+/// Whether a timestamp cell carries the 'R' superscript of revised data
+fn is_marked_revised(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::String(value) if strip_timestamp_footnote(strip_timestamp_enclosure(value)).1 == Some(Footnote::Revised)
+    )
+}
+
+/// Reads a numeric cell between 1 and 12 as the month of that number
+fn read_cell_as_month_number(data_type: &DataType) -> Option<Month> {
+    let number = match data_type {
+        DataType::Int(number) => *number,
+        DataType::Float(number) if number.fract() == 0.0 => *number as i64,
+        _ => return None
+    };
+    u8::try_from(number).ok().and_then(|number| Month::try_from(number).ok())
+}
+
 impl SheetAnalyzer<'_> {
     /// Determines the first (yearly) timestamp value in the sheet. This value is critical
     /// and tells us whether the sheet is valid at all, or parsable by our algorithm.
@@ -265,35 +588,146 @@ impl SheetAnalyzer<'_> {
     /// plain months such as "July", "August" which refer back to the previous month.
     /// However, this is not guaranteed; biannual and quarterly data is another possibility.
     /// Moreover, oftentimes, yearly data preceeds monthly data.
+    ///
+    /// Some sheets open with months, leaving the year to the title, and others never give the
+    /// year outside the title. Then the data starts from the first month (or quarter, or
+    /// half-year), and the title year is the starting context.
+    ///
+    /// If the sheet's timestamp column is overridden, only that column is checked.
     fn find_first_timestamp<I: CellInspector>(&self, inspector: &I) -> AnalysisResult<FirstYearlyTimestamp> {
 
         let sheet = &self.sheet;
+        let columns = match self.options.timestamp_column_of(self.name) {
+            Some(column) => {
+                let index = self.find_timestamp_column(column)?;
+                index..index + 1
+            }
+            None => 0..sheet.width()
+        };
+
+        // The first month, quarter, or half-year found in a column without any year
+        let mut periods_without_year = None;
 
         // Important: check columns starting from the left, BEFORE rows
-        for cur_col in 0..sheet.width() {
+        for cur_col in columns {
+            // The first month, quarter, or half-year, should it precede any year
+            let mut first_period_row = None;
 
             // Scan the years until we receive a year
             for cur_row in 0..sheet.height() {
-                match read_cell_as_timestamp(&sheet[(cur_row, cur_col)], inspector)? {
-                    CellAsTimestamp::YearlyTimestamp(timestamp) => {
+                let cell = &sheet[(cur_row, cur_col)];
+                if self.options.price_base_cutover.is_none() && price_base_of(cell) == Some(PriceBase::Old) {
+                    continue;
+                }
+                match read_cell_as_timestamp(cell, &self.options.years, inspector)? {
+                    CellAsTimestamp::Provisional(_) if self.options.provisional == ProvisionalPolicy::Exclude => {
+                        // Provisional data encountered. Stop everything. We have nothing.
+                        // Hereafter, everything (all the rows) will be provisional
+                        return Err(AnalysisError::NoData);
+                    },
+                    CellAsTimestamp::YearlyTimestamp(timestamp)
+                    | CellAsTimestamp::Provisional(ProvisionalTimestamp::Yearly(timestamp)) => {
+                        // Periods above the year belong to the year in the title. Without one, start at the year
+                        if let Some(first) = first_period_row.and_then(|period_row| self.start_from_title_year(period_row, cur_col)) {
+                            return Ok(first);
+                        }
                         return Ok(FirstYearlyTimestamp {
                             value: timestamp,
                             cell: (cur_row, cur_col)
                         });
                     },
-                    CellAsTimestamp::TimestampIsProvisional => {
-                        // Provisional data encountered. Stop everything. We have nothing.
-                        // Hereafter, everything (all the rows) will be provisional
-                        return Err(AnalysisError::NoData);
+                    CellAsTimestamp::MayNeedContext(value) if first_period_row.is_none() && is_period_name(value) => {
+                        first_period_row = Some(cur_row);
+                    },
+                    CellAsTimestamp::Provisional(ProvisionalTimestamp::Month(_)) if first_period_row.is_none() => {
+                        first_period_row = Some(cur_row);
                     },
-                    CellAsTimestamp::None | CellAsTimestamp::MayNeedContext(_) => () /* do nothing */
+                    CellAsTimestamp::Date(report) => {
+                        // The year serves as the starting context. The date itself is read
+                        // again with the rest of the rows
+                        return Ok(FirstYearlyTimestamp {
+                            value: YearlyTimestamp::Calendar(report.year),
+                            cell: (cur_row, cur_col)
+                        });
+                    },
+                    CellAsTimestamp::None | CellAsTimestamp::MayNeedContext(_) | CellAsTimestamp::Provisional(_) => () /* do nothing */
                 }
             }
+            if let Some(period_row) = first_period_row {
+                // The column has periods but no years at all. A column further right may yet have one
+                periods_without_year.get_or_insert((period_row, cur_col));
+            }
+        }
+        match periods_without_year {
+            Some((period_row, col)) => self.start_from_title_year(period_row, col).ok_or_else(|| AnalysisError::unsupported(format!(
+                "Found a period in row {} before any year, and no year in the title", period_row
+            ))),
+            None => Err(AnalysisError::unsupported("No timestamp found"))
+        }
+    }
+
+    /// Locates an overridden timestamp column in the sheet
+    fn find_timestamp_column(&self, column: &TimestampColumn) -> AnalysisResult<usize> {
+        match column {
+            TimestampColumn::Index(index) if *index < self.sheet.width() => Ok(*index),
+            TimestampColumn::Index(index) => Err(AnalysisError::unsupported(format!(
+                "Timestamp column {} is beyond the {} columns of the sheet", index + 1, self.sheet.width()
+            ))),
+            TimestampColumn::Header(header) => (0..self.sheet.width())
+                .find(|&col| (0..self.sheet.height()).any(|row| {
+                    matches!(&self.sheet[(row, col)], DataType::String(text) if text.trim().eq_ignore_ascii_case(header))
+                }))
+                .ok_or_else(|| AnalysisError::unsupported(format!("No timestamp column headed {}", header)))
         }
-        Err(AnalysisError::unsupported("No timestamp found"))
+    }
+
+    /// Starts the data from a period which precedes any year, taking the year from the title
+    fn start_from_title_year(&self, period_row: usize, col: usize) -> Option<FirstYearlyTimestamp> {
+        self.title_year(period_row).map(|year| FirstYearlyTimestamp {
+            value: YearlyTimestamp::Calendar(year),
+            cell: (period_row, col)
+        })
+    }
+
+    /// Whether the labels of the column, or the text above it, mention a price index
+    fn is_price_index(&self, column_info: &ColumnInfo, data_start_row: usize) -> bool {
+        let mentions_prices = |text: &str| {
+            let text = text.to_lowercase();
+            ["inflation", "consumer price", "cpi"].iter().any(|term| text.contains(term))
+        };
+        let column = &column_info.column;
+        (0..column.depth()).filter_map(|level| column.label_at(level)).any(mentions_prices) || (0..data_start_row)
+            .any(|row| matches!(&self.sheet[(row, column_info.index_in_sheet)], DataType::String(text) if mentions_prices(text)))
+    }
+
+    /// Finds the first year mentioned in the sheet name, or else in the text above the data
+    fn title_year(&self, data_start_row: usize) -> Option<Year> {
+        let find_year = |text: &str| {
+            text.split(|c: char| !c.is_ascii_digit())
+                .filter(|digits| digits.len() == 4)
+                .filter_map(|digits| digits.parse::<u16>().ok())
+                .find(|year| self.options.years.contains(*year))
+                .and_then(NonZeroU16::new)
+                .map(Year)
+        };
+        find_year(self.name).or_else(|| {
+            (0..data_start_row)
+                .flat_map(|row| (0..self.sheet.width()).map(move |col| (row, col)))
+                .find_map(|cell| match &self.sheet[cell] {
+                    DataType::String(text) => find_year(text),
+                    _ => None
+                })
+        })
     }
 }
 
+/// Whether the text names a month, quarter, or half-year. Numbers are not taken for months
+/// here, because columns are often numbered above the data
+fn is_period_name(value: &str) -> bool {
+    value.contains(char::is_alphabetic)
+        && (Month::from_str(value).is_ok() || Quarter::from_str(value).is_ok() || HalfYear::from_str(value).is_ok())
+}
+
 #[derive(Clone, Debug)]
 struct ColumnInfo {
     column: Column,
@@ -308,12 +742,104 @@ impl Display for ColumnInfo {
     }
 }
 
+/// Swaps the rows and columns of a sheet
+fn transpose(sheet: &Range<DataType>) -> Range<DataType> {
+    let (height, width) = sheet.get_size();
+    if height == 0 || width == 0 {
+        return Range::empty();
+    }
+    let mut transposed = Range::new((0, 0), (width as u32 - 1, height as u32 - 1));
+    for (row, cells) in sheet.rows().enumerate() {
+        for (col, cell) in cells.iter().enumerate() {
+            transposed.set_value((col as u32, row as u32), cell.clone());
+        }
+    }
+    transposed
+}
+
+/// What analysis finds in a sheet: where its data starts, its labels, and how much it holds
+#[derive(Clone, Debug)]
+pub struct SheetPreview {
+    /// The cell, as (row, column), of the first timestamp or institution
+    pub data_start: (usize, usize),
+    /// The yearly timestamp the data starts from. None for cross-sections
+    pub start: Option<Timestamp>,
+    /// The rows labeling the columns. None if the columns are named by position
+    pub label_rows: Option<std::ops::Range<usize>>,
+    pub columns: usize,
+    pub rows: usize
+}
+
+/// Where the rows read from a sheet go
+pub(crate) trait RowDestination {
+    async fn add_row(&self, timestamp: Timestamp, row: RowData, provisional: bool);
+
+    async fn add_cross_section_row(&self, kind: &'static str, institution: Institution, row: RowData);
+}
+
+impl RowDestination for MergeXL {
+    async fn add_row(&self, timestamp: Timestamp, row: RowData, provisional: bool) {
+        let sheet = self.get_or_create_sheet(&timestamp).await;
+        if provisional {
+            sheet.mark_provisional(timestamp);
+        }
+        sheet.add_row(timestamp, row);
+    }
+
+    async fn add_cross_section_row(&self, kind: &'static str, institution: Institution, row: RowData) {
+        self.get_or_create_cross_section(kind).await.add_row(institution, row);
+    }
+}
+
+/// Discards every row, for previews which only count them
+struct DryRun;
+
+impl RowDestination for DryRun {
+    async fn add_row(&self, _: Timestamp, _: RowData, _: bool) {}
+
+    async fn add_cross_section_row(&self, _: &'static str, _: Institution, _: RowData) {}
+}
+
 impl SheetAnalyzer<'_> {
-    /// Accepts and merges more data loaded from another spreadsheet
-    pub async fn merge_data(&self, merge_xl: &MergeXL) -> AnalysisResult<()> {
+    /// Accepts and merges more data loaded from another spreadsheet.
+    /// Yields the number of rows merged
+    pub async fn merge_data(&self, merge_xl: &MergeXL) -> AnalysisResult<usize> {
+        self.merge_data_into(merge_xl).await
+    }
+
+    /// Reads the sheet's rows into any destination. Yields the number of rows read
+    pub(crate) async fn merge_data_into<D: RowDestination>(&self, destination: &D) -> AnalysisResult<usize> {
+        Ok(self.read_data(destination).await?.rows)
+    }
+
+    /// Analyzes the sheet as if merging it, but without keeping its rows. Useful for finding
+    /// out why a sheet yields unexpected output
+    pub async fn preview(&self) -> AnalysisResult<SheetPreview> {
+        self.read_data(&DryRun).await
+    }
+
+    async fn read_data<D: RowDestination>(&self, destination: &D) -> AnalysisResult<SheetPreview> {
+        match self.options.orientation_of(self.name) {
+            Orientation::Vertical => self.read_vertical_data(destination).await,
+            Orientation::Horizontal => {
+                let transposed = SheetAnalyzer {
+                    source: self.source,
+                    name: self.name,
+                    sheet: transpose(&self.sheet),
+                    options: self.options
+                };
+                transposed.read_vertical_data(destination).await
+            }
+        }
+    }
+
+    async fn read_vertical_data<D: RowDestination>(&self, destination: &D) -> AnalysisResult<SheetPreview> {
         if self.sheet.is_empty() {
             Err(AnalysisError::NoData)
 
+        } else if let Some(kind) = self.find_cross_section_kind() {
+            self.read_cross_section(kind, destination).await
+
         } else {
             let inspector = SheetSupportInspector {
                 banned_cell_values_to_reasons: UNSUPPORTED_SHEETS,
@@ -324,13 +850,17 @@ impl SheetAnalyzer<'_> {
             } = self.find_first_timestamp(&inspector)?;
 
             let supported_sheet = SupportedSheet {
-                analyzer: &self,
+                analyzer: self,
                 data_start_row,
                 timestamp_col
             };
-            let columns = supported_sheet.load_columns(
-                supported_sheet.find_label_range(&inspector)?
-            )?;
+            let (columns, label_rows) = if data_start_row == 0 && self.options.positional_labels {
+                log::warn!("Data starts in the first row of {}. Naming its columns by position", self);
+                (supported_sheet.positional_columns()?, None)
+            } else {
+                let label_range = supported_sheet.find_label_range(&inspector)?;
+                (supported_sheet.load_columns(label_range.clone())?, Some(label_range))
+            };
             if log::log_enabled!(Level::Debug) {
                 let mut column_display = String::new();
                 for column in columns.clone()    {
@@ -338,8 +868,103 @@ impl SheetAnalyzer<'_> {
                 }
                 log::debug!("Loaded columns [{}]", column_display)
             }
-            supported_sheet.read_rows_into(start_year, columns, merge_xl).await
+            let column_count = columns.len();
+            let rows = supported_sheet.read_rows_into(start_year, columns, destination).await?;
+            Ok(SheetPreview {
+                data_start: (data_start_row, timestamp_col),
+                start: Some(Timestamp::from(start_year)),
+                label_rows,
+                columns: column_count,
+                rows
+            })
+        }
+    }
+}
+
+/// Finds the kind of cross-section by its name, as used for its output sheet
+pub(crate) fn cross_section_kind(name: &str) -> Option<&'static str> {
+    CROSS_SECTIONAL_SHEETS.iter().map(|(_, kind)| *kind).find(|kind| *kind == name)
+}
+
+impl SheetAnalyzer<'_> {
+    /// Identifies the kind of cross-section this sheet holds, if cross-sectional mode is enabled
+    fn find_cross_section_kind(&self) -> Option<&'static str> {
+        if !self.options.cross_sectional {
+            return None;
+        }
+        self.sheet.used_cells().find_map(|(_, _, cell)| match cell {
+            DataType::String(value) => CROSS_SECTIONAL_SHEETS
+                .iter()
+                .find(|(marker, _)| value.contains(*marker))
+                .map(|(_, kind)| *kind),
+            _ => None
+        })
+    }
+
+    /// Finds the heading of the column naming the institutions, such as "Name of the Banks".
+    /// Like [Self::find_first_timestamp], checks columns starting from the left
+    fn find_institution_heading(&self) -> AnalysisResult<(usize, usize)> {
+        for cur_col in 0..self.sheet.width() {
+            for cur_row in 0..self.sheet.height() {
+                if let DataType::String(value) = &self.sheet[(cur_row, cur_col)] {
+                    // The sheet title mentions banks too, but in capitals
+                    if value.contains("Bank") || value.contains("bank") {
+                        return Ok((cur_row, cur_col));
+                    }
+                }
+            }
         }
+        Err(AnalysisError::unsupported("No column of institutions found"))
+    }
+
+    /// Reads a sheet whose rows are institutions. The data starts at the first named
+    /// institution below the heading, and the rows between are the column labels
+    async fn read_cross_section<D>(&self, kind: &'static str, destination: &D) -> AnalysisResult<SheetPreview>
+        where D: RowDestination {
+
+        let (heading_row, key_col) = self.find_institution_heading()?;
+        let has_data = |row: usize| {
+            (key_col + 1..self.sheet.width()).any(|col| !matches!(self.sheet[(row, col)], DataType::Empty))
+        };
+        let data_start_row = (heading_row + 1..self.sheet.height())
+            .find(|&row| matches!(&self.sheet[(row, key_col)], DataType::String(_)) && has_data(row))
+            .ok_or(AnalysisError::NoData)?;
+
+        let supported_sheet = SupportedSheet {
+            analyzer: self,
+            data_start_row,
+            timestamp_col: key_col
+        };
+        let columns = supported_sheet.load_columns(heading_row..data_start_row)?;
+        let mut row_count = 0;
+
+        for row_cursor in data_start_row..self.sheet.height() {
+            let institution = match supported_sheet.cell(row_cursor, key_col) {
+                DataType::String(name) if name.contains("Source") || name.contains("Note") => {
+                    // End of the table
+                    break;
+                }
+                DataType::String(name) => match Institution::create(name) {
+                    Some(institution) => institution,
+                    None => continue
+                },
+                // Blank rows may separate groups of banks
+                _ => continue
+            };
+            let row_data = supported_sheet.read_row(row_cursor, &columns);
+            // Headings for groups of banks have no data of their own
+            if !row_data.is_empty() {
+                destination.add_cross_section_row(kind, institution, row_data).await;
+                row_count += 1;
+            }
+        }
+        Ok(SheetPreview {
+            data_start: (data_start_row, key_col),
+            start: None,
+            label_rows: Some(heading_row..data_start_row),
+            columns: columns.len(),
+            rows: row_count
+        })
     }
 }
 
@@ -475,95 +1100,706 @@ impl SupportedSheet<'_, '_> {
         where R: IntoIterator<Item=usize> + Clone + Debug + RangeBounds<usize> {
 
         let mut columns = HashMap::new();
+        let mut empty_run = 0;
 
         for col_index in (self.timestamp_col + 1)..self.analyzer.sheet.width() {
             let column_info = self.generate_column_info(label_range.clone(), col_index, &columns)?;
             if let Some(column_info) = column_info {
+                empty_run = 0;
                 columns.insert(col_index, column_info);
             } else {
-                // No more columns; we can stop
-                break;
+                empty_run += 1;
+                if empty_run > self.analyzer.options.max_empty_columns {
+                    // No more columns; we can stop
+                    break;
+                }
+                // Labels are never borrowed across the gap, since the column to the left is absent
+                log::debug!("Skipped empty column {} of {}", col_index, self.analyzer);
             };
         }
-        Ok(columns.into_iter().map(|(_, col)| col).collect())
+        Ok(columns.into_values().collect())
     }
 
-    async fn read_rows_into(&self, start_year: YearlyTimestamp,
-                            columns: Vec<ColumnInfo>, output: &MergeXL) -> AnalysisResult<()> {
+    /// Names columns by their position after the timestamp column, for sheets without
+    /// labels. Stops at the first column lacking data
+    fn positional_columns(&self) -> AnalysisResult<Vec<ColumnInfo>> {
+        let mut columns = Vec::new();
+        for col_index in (self.timestamp_col + 1)..self.analyzer.sheet.width() {
+            let has_data = (self.data_start_row..self.analyzer.sheet.height())
+                .any(|row| !matches!(self.cell(row, col_index), DataType::Empty));
+            if !has_data {
+                break;
+            }
+            let label = ColumnLabel::create(&format!("col{}", col_index - self.timestamp_col))
+                .expect("Positional labels are not numbers");
+            columns.push(ColumnInfo {
+                column: Column::new([label.clone()])?,
+                indexed_labels: HashMap::from([(self.data_start_row, label)]),
+                index_in_sheet: col_index
+            });
+        }
+        Ok(columns)
+    }
+
+    /// Reads the values of a row under the given columns
+    fn read_row(&self, row_cursor: usize, columns: &[ColumnInfo]) -> RowData {
+        let mut row_data = RowData::default();
+        for column_info in columns.iter() {
+            let value = self.cell(row_cursor, column_info.index_in_sheet);
+            if let DataType::Empty = value {
+                // It's empty. Skip it. If all the cells are empty, that's fine.
+            } else if let DataType::Error(error) = value {
+                // Formula errors such as #DIV/0! are no data either
+                log::debug!("Skipped error {:?} in row {} of {}", error, row_cursor, self.analyzer);
+            } else {
+                let parsed = Value::from_cell(value, &self.analyzer.options.values);
+                if let Some(unit) = parsed.unit {
+                    log::debug!(
+                        "Converted {} in row {} of {} from {:?} by factor {}",
+                        value, row_cursor, self.analyzer, unit, unit.factor()
+                    );
+                    row_data.mark_unit(&column_info.column, unit);
+                }
+                if parsed.footnote == Some(Footnote::Revised) {
+                    row_data.mark_revised();
+                }
+                if parsed.percent {
+                    row_data.mark_percentage(&column_info.column);
+                }
+                row_data.populate(&column_info.column, parsed.value);
+            }
+        }
+        row_data
+    }
+
+    async fn read_rows_into<D>(&self, start_year: YearlyTimestamp,
+                               columns: Vec<ColumnInfo>, destination: &D) -> AnalysisResult<usize>
+        where D: RowDestination {
+
         // Monthly and quarterly data relies on identifying the last-seen year from prior rows
         let mut current_year = match start_year {
             YearlyTimestamp::Fiscal(fy) => fy,
             YearlyTimestamp::Calendar(cy) => cy
         };
+        let policy = self.analyzer.options.provisional;
+        let price_columns = columns.iter()
+            .filter(|column_info| self.analyzer.is_price_index(column_info, self.data_start_row))
+            .map(|column_info| column_info.column.clone())
+            .collect::<HashSet<_>>();
+        // Once a provisional period is reached, all the rows after it are provisional
+        let mut provisional = false;
+        let mut row_count = 0;
 
         for row_cursor in self.data_start_row..self.analyzer.sheet.height() {
 
             // First, figure out the timestamp of this row
             let timestamp_cell = self.cell(row_cursor, self.timestamp_col);
-            let timestamp = match read_cell_as_timestamp(timestamp_cell, &NoOpInspector {})? {
+            let price_base = price_base_of(timestamp_cell);
+            if price_base == Some(PriceBase::Old) && self.analyzer.options.price_base_cutover.is_none() {
+                // Discard data of the old base
+                continue;
+            }
+            let years = &self.analyzer.options.years;
+            let cell = read_cell_as_timestamp(timestamp_cell, years, &NoOpInspector {})?;
+            provisional |= matches!(cell, CellAsTimestamp::Provisional(_));
+            let timestamp = match cell {
+                CellAsTimestamp::Provisional(_) if policy == ProvisionalPolicy::Exclude => {
+                    // We're done, stop reading
+                    break;
+                }
                 CellAsTimestamp::MayNeedContext(timestamp_str) => {
 
                     // Try to parse as month, quarter, or halfyear
-                    if let Ok(month) = Month::from_str(timestamp_str) {
-                        Timestamp::Monthly(MonthlyReport {
-                            year: current_year,
-                            month,
-                        })
-                    } else if let Ok(quarter) = Quarter::from_str(timestamp_str) {
-                        Timestamp::Quarterly(current_year, quarter)
-                    } else if let Ok(halfyear) = HalfYear::from_str(timestamp_str) {
-                        Timestamp::BiAnnually(current_year, halfyear)
-
-                    // Otherwise, we've either hit the end of document or an error
-                    } else if timestamp_str.contains("Source") || timestamp_str.contains("Note") {
-                        // Hooray, we've reached the end of the document!
-                        // The central bank typically leaves these mentions at the very end of the column
-                        break;
-                    } else {
-                        return Err(AnalysisError::unsupported(format!(
-                            "Found invalid timestamp (non-parsable) {} in row {}", timestamp_cell, row_cursor
-                        )));
+                    match Timestamp::try_from((current_year, timestamp_str)) {
+                        Ok(timestamp) => timestamp,
+                        // Otherwise, we've either hit the end of document or an error
+                        Err(_) if timestamp_str.contains("Source") || timestamp_str.contains("Note") => {
+                            // Hooray, we've reached the end of the document!
+                            // The central bank typically leaves these mentions at the very end of the column
+                            break;
+                        }
+                        Err(error) => {
+                            return Err(AnalysisError::unsupported(format!(
+                                "Found invalid timestamp (non-parsable) {} in row {}: {}", timestamp_cell, row_cursor, error
+                            )));
+                        }
                     }
                 }
                 CellAsTimestamp::None => {
                     if let DataType::Empty = timestamp_cell {
                         // Yes! We're done
                         break;
+                    } else if let Some(month) = read_cell_as_month_number(timestamp_cell) {
+                        // Some sheets number their months instead of naming them
+                        Timestamp::Monthly(MonthlyReport {
+                            year: current_year,
+                            month
+                        })
                     } else {
                         return Err(AnalysisError::unsupported(format!(
                             "Found invalid timestamp (cell type) {} in row {}", timestamp_cell, row_cursor
                         )));
                     }
                 }
-                CellAsTimestamp::YearlyTimestamp(yearly_timestamp) => {
+                CellAsTimestamp::YearlyTimestamp(yearly_timestamp)
+                | CellAsTimestamp::Provisional(ProvisionalTimestamp::Yearly(yearly_timestamp)) => {
                     current_year = Year::from(yearly_timestamp);
                     Timestamp::from(yearly_timestamp)
                 }
-                CellAsTimestamp::TimestampIsProvisional => {
-                    // We're done, stop reading
-                    break;
+                CellAsTimestamp::Date(report) => {
+                    current_year = report.year;
+                    Timestamp::Monthly(report)
+                }
+                CellAsTimestamp::Provisional(ProvisionalTimestamp::Month(month)) => {
+                    Timestamp::Monthly(MonthlyReport {
+                        year: current_year,
+                        month
+                    })
                 }
             };
-            let mut row_data = RowData::default();
-            for column_info in columns.iter() {
-                let value = self.cell(row_cursor, column_info.index_in_sheet);
-                if let DataType::Empty = value {
-                    // It's empty. Skip it. If all the cells are empty, that's fine.
-                } else {
-                    let value = value.to_string();
-                    row_data.populate(&column_info.column, value);
+            let mut row_data = self.read_row(row_cursor, &columns);
+            if is_marked_revised(timestamp_cell) {
+                row_data.mark_revised();
+            }
+            if let Some(cutover) = self.analyzer.options.price_base_cutover {
+                let base = price_base.unwrap_or_else(|| {
+                    if timestamp.date_range().0 < MonthlyReport::new(cutover, Month::July) { PriceBase::Old } else { PriceBase::New }
+                });
+                if !price_columns.is_empty() {
+                    row_data = row_data.map_columns(|column| {
+                        if price_columns.contains(column) { column.with_sublabel(base.label()) } else { column.clone() }
+                    });
                 }
             }
             if columns.len() != row_data.len() {
                 let percent_full = row_data.len() as f32 / columns.len() as f32;
-                if percent_full < 0.15 {
+                if percent_full < self.analyzer.options.min_row_fill {
                     // Probably a useless row worth skipping
+                    log::debug!(
+                        "Skipped row {} ({}) of {} which is only {:.0}% filled",
+                        row_cursor, timestamp, self.analyzer, percent_full * 100.0
+                    );
                     continue;
                 }
             }
-            let sheet = output.get_or_create_sheet(&timestamp).await;
-            sheet.add_row(timestamp, row_data);
+            destination.add_row(timestamp, row_data, provisional && policy == ProvisionalPolicy::Flag).await;
+            row_count += 1;
         }
-        Ok(())
+        Ok(row_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use calamine::CellErrorType;
+    use super::*;
+
+    fn text(value: &str) -> DataType {
+        DataType::String(String::from(value))
+    }
+
+    /// Merges the sheet under the given name, yielding the outcome along with the rows merged
+    fn analyze(name: &str, sheet: &Range<DataType>, options: &AnalysisOptions) -> (AnalysisResult<usize>, MergeXL) {
+        let analyzer = SheetAnalyzer { source: "test.xlsx", name, sheet: sheet.clone(), options };
+        let merge_xl = MergeXL::default();
+        let result = async_std::task::block_on(analyzer.merge_data(&merge_xl));
+        (result, merge_xl)
+    }
+
+    #[test]
+    fn read_date_cells() {
+        // Excel serial 43876 is 2020-02-14
+        let cell = DataType::DateTime(43876.0);
+        let report = assert_matches!(
+            read_cell_as_timestamp(&cell, &YearWindow::default(), &NoOpInspector {}),
+            Ok(CellAsTimestamp::Date(report)) => report
+        );
+        assert_eq!(MonthlyReport::new(Year(NonZeroU16::new(2020).unwrap()), Month::February), report);
+        // As found in OpenDocument spreadsheets
+        let iso_cell = DataType::DateTimeIso(String::from("2020-02-14"));
+        assert_matches!(
+            read_cell_as_timestamp(&iso_cell, &YearWindow::default(), &NoOpInspector {}),
+            Ok(CellAsTimestamp::Date(iso_report)) if iso_report == report
+        );
+        assert_matches!(
+            read_cell_as_timestamp(&DataType::Duration(1.5), &YearWindow::default(), &NoOpInspector {}),
+            Ok(CellAsTimestamp::None)
+        );
+    }
+
+    #[test]
+    fn strip_leading_markers() {
+        let read = |value: &str| read_cell_as_timestamp(
+            &DataType::String(value.to_string()), &YearWindow::default(), &NoOpInspector {}
+        ).map(|cell| match cell {
+            CellAsTimestamp::YearlyTimestamp(timestamp) => Timestamp::from(timestamp).to_string(),
+            CellAsTimestamp::MayNeedContext(value) => value.to_string(),
+            other => format!("{:?}", other)
+        }).unwrap();
+        assert_eq!("2019", read("*2019"));
+        assert_eq!("2019", read("(2019)"));
+        assert_eq!("2019", read("[2019]"));
+        assert_eq!("2019-20", read("* 2019-20*"));
+        assert_eq!("Jul-Sep", read("[Jul-Sep]"));
+        // Unbalanced brackets and signs are left alone
+        assert_eq!("(2019", read("(2019"));
+        assert_eq!("-2019", read("-2019"));
+        assert_matches!(strip_price_base(strip_timestamp_enclosure("(2011-12(OB))")), ("2011-12", Some(PriceBase::Old)));
+    }
+
+    #[test]
+    fn positional_labels() {
+        let mut sheet = Range::new((0, 0), (1, 2));
+        sheet.set_value((0, 0), DataType::Float(2020.0));
+        sheet.set_value((0, 1), DataType::Float(1.5));
+        sheet.set_value((0, 2), DataType::Float(2.5));
+        sheet.set_value((1, 0), DataType::Float(2021.0));
+        sheet.set_value((1, 1), DataType::Float(3.5));
+
+        let merge = |positional_labels| {
+            analyze("Compact", &sheet, &AnalysisOptions { positional_labels, ..Default::default() })
+        };
+        assert_matches!(merge(false).0, Err(AnalysisError::Unsupported { .. }));
+
+        let (result, merge_xl) = merge(true);
+        result.unwrap();
+        let year = |y| Timestamp::CalendarYear(Year(NonZeroU16::new(y).unwrap()));
+        let sheet = async_std::task::block_on(merge_xl.get_or_create_sheet(&year(2020)));
+        let col = |name| Column::new([ColumnLabel::create(name).unwrap()]).unwrap();
+        let row_2020 = sheet.rows.get(&year(2020)).unwrap();
+        assert_eq!(Some(&Value::Number(1.5)), row_2020.get(&col("col1")));
+        assert_eq!(Some(&Value::Number(2.5)), row_2020.get(&col("col2")));
+        let row_2021 = sheet.rows.get(&year(2021)).unwrap();
+        assert_eq!(Some(&Value::Number(3.5)), row_2021.get(&col("col1")));
+        assert_eq!(None, row_2021.get(&col("col2")));
+    }
+
+    #[test]
+    fn cross_sectional_banks() {
+        let mut sheet = Range::new((0, 0), (7, 2));
+        sheet.set_value((0, 0), text("BANK WISE ANNOUNCED INTEREST RATE STRUCTURE"));
+        sheet.set_value((1, 0), text("Name of the Banks"));
+        sheet.set_value((1, 1), text("Deposit rate"));
+        sheet.set_value((2, 1), text("Savings"));
+        sheet.set_value((2, 2), text("Fixed"));
+        sheet.set_value((3, 0), text("State-owned Banks"));
+        sheet.set_value((4, 0), text("Sonali Bank"));
+        sheet.set_value((4, 1), DataType::Float(4.0));
+        sheet.set_value((4, 2), DataType::Float(6.5));
+        sheet.set_value((5, 0), text("Agrani Bank "));
+        sheet.set_value((5, 1), DataType::Float(3.5));
+        sheet.set_value((6, 0), text("Source: Banks"));
+        sheet.set_value((6, 1), DataType::Float(99.0));
+
+        let merge = |cross_sectional| analyze("Rates", &sheet, &AnalysisOptions { cross_sectional, ..Default::default() });
+        assert_matches!(merge(false).0, Err(AnalysisError::Unsupported { .. }));
+
+        let (result, merge_xl) = merge(true);
+        result.unwrap();
+        let sheet = async_std::task::block_on(merge_xl.get_or_create_cross_section("bank-interest-rates"));
+        let col = |labels: [&str; 2]| Column::new(labels.map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let bank = |name| Institution::create(name).unwrap();
+        assert_eq!(vec![bank("Agrani Bank"), bank("Sonali Bank")], sheet.keys_in_order());
+        let sonali = sheet.rows.get(&bank("Sonali Bank")).unwrap();
+        assert_eq!(Some(&Value::Number(4.0)), sonali.get(&col(["Deposit rate", "Savings"])));
+        assert_eq!(Some(&Value::Number(6.5)), sonali.get(&col(["Deposit rate", "Fixed"])));
+        let agrani = sheet.rows.get(&bank("Agrani Bank")).unwrap();
+        assert_eq!(1, agrani.len());
+    }
+
+    #[test]
+    fn exclude_sheets() {
+        let default = SheetExclusions::default();
+        assert!(default.excludes("Cover Page"));
+        assert!(default.excludes("contents"));
+        assert!(default.excludes("Appendix A"));
+        assert!(!default.excludes("Table of Contents"));
+        assert!(!default.excludes("Monetary Survey"));
+
+        let configured = SheetExclusions {
+            pattern: Some(Regex::new("^(Index|Cover|Table of Contents)$").unwrap()),
+            appendices: false,
+            ..Default::default()
+        };
+        assert!(configured.excludes("Table of Contents"));
+        assert!(configured.excludes("Cover"));
+        assert!(configured.excludes("Cover Page"));
+        assert!(!configured.excludes("Cover Notes"));
+        assert!(!configured.excludes("Appendix A"));
+    }
+
+    #[test]
+    fn start_mid_year() {
+        let mut sheet = Range::new((0, 0), (6, 1));
+        sheet.set_value((0, 0), text("Exports during 2022"));
+        sheet.set_value((1, 0), text("Period"));
+        sheet.set_value((1, 1), text("Exports"));
+        for (row, (period, value)) in (2..).zip([
+            (text("June"), 1.0), (text("July"), 2.0), (DataType::Float(2023.0), 30.0), (text("January"), 4.0)
+        ]) {
+            sheet.set_value((row, 0), period);
+            sheet.set_value((row, 1), DataType::Float(value));
+        }
+        let (result, merge_xl) = analyze("Exports", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+
+        let year = |y| Year(NonZeroU16::new(y).unwrap());
+        let month = |y, month| Timestamp::Monthly(MonthlyReport::new(year(y), month));
+        let exports = Column::new([ColumnLabel::create("Exports").unwrap()]).unwrap();
+        let monthly = async_std::task::block_on(merge_xl.sheet(Granularity::Monthly)).unwrap();
+        assert_eq!(vec![month(2022, Month::June), month(2022, Month::July), month(2023, Month::January)], monthly.keys_in_order());
+        assert_eq!(Some(&Value::Number(2.0)), monthly.get(&month(2022, Month::July)).unwrap().get(&exports));
+        let yearly = async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear)).unwrap();
+        assert_eq!(vec![Timestamp::CalendarYear(year(2023))], yearly.keys_in_order());
+
+        // Without a year in the title, the months before the first year are left out
+        sheet.set_value((0, 0), text("Exports"));
+        let (result, merge_xl) = analyze("Exports", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+        let monthly = async_std::task::block_on(merge_xl.sheet(Granularity::Monthly)).unwrap();
+        assert_eq!(vec![month(2023, Month::January)], monthly.keys_in_order());
+    }
+
+    #[test]
+    fn provisional_months() {
+        let mut sheet = Range::new((0, 0), (5, 1));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((0, 1), text("Exports"));
+        for (row, (period, value)) in (1..).zip([
+            (DataType::Float(2022.0), 10.0), (text("May"), 1.0), (text("JuneP"), 2.0), (text("July"), 3.0)
+        ]) {
+            sheet.set_value((row, 0), period);
+            sheet.set_value((row, 1), DataType::Float(value));
+        }
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        let month = |month| Timestamp::Monthly(MonthlyReport::new(year, month));
+        let read = |provisional| {
+            let (result, merge_xl) = analyze("Exports", &sheet, &AnalysisOptions { provisional, ..Default::default() });
+            result.unwrap();
+            async_std::task::block_on(merge_xl.sheet(Granularity::Monthly)).unwrap()
+        };
+
+        // Reading stops at the first provisional month by default
+        let excluded = read(ProvisionalPolicy::Exclude);
+        assert_eq!(vec![month(Month::May)], excluded.keys_in_order());
+        let kept = read(ProvisionalPolicy::Keep);
+        assert_eq!(vec![month(Month::May), month(Month::June), month(Month::July)], kept.keys_in_order());
+        assert!(!kept.is_provisional(&month(Month::June)));
+        // Every month after the first provisional one is provisional too
+        let flagged = read(ProvisionalPolicy::Flag);
+        assert_eq!(kept.keys_in_order(), flagged.keys_in_order());
+        assert!(!flagged.is_provisional(&month(Month::May)));
+        assert!(flagged.is_provisional(&month(Month::June)));
+        assert!(flagged.is_provisional(&month(Month::July)));
+        assert!(ProvisionalPolicy::from_str("discard").is_err());
+    }
+
+    #[test]
+    fn revised_rows() {
+        let mut sheet = Range::new((0, 0), (4, 1));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((0, 1), text("Exports"));
+        for (row, (period, value)) in (1..).zip([
+            (text("2021R"), text("10")), (text("OCTOBER"), text("1")), (text("NOVEMBER"), text("2R")), (text("DECEMBER"), text("3"))
+        ]) {
+            sheet.set_value((row, 0), period);
+            sheet.set_value((row, 1), value);
+        }
+        let (result, merge_xl) = analyze("Exports", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+
+        let year = Year(NonZeroU16::new(2021).unwrap());
+        let yearly = async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear)).unwrap();
+        assert!(yearly.get(&Timestamp::CalendarYear(year)).unwrap().is_revised());
+        // The R ending a month's name is no revision marker
+        let monthly = async_std::task::block_on(merge_xl.sheet(Granularity::Monthly)).unwrap();
+        let revised = |month| monthly.get(&Timestamp::Monthly(MonthlyReport::new(year, month))).unwrap().is_revised();
+        assert!(!revised(Month::October));
+        assert!(revised(Month::November));
+        assert!(!revised(Month::December));
+    }
+
+    #[test]
+    fn preview_without_merging() {
+        let mut sheet = Range::new((0, 0), (4, 2));
+        sheet.set_value((0, 0), text("Table II: Remittances"));
+        sheet.set_value((1, 0), text("Period"));
+        sheet.set_value((1, 1), text("Inflows"));
+        sheet.set_value((1, 2), text("Outflows"));
+        for (row, period) in (2..).zip([text("2021"), text("July"), text("August")]) {
+            sheet.set_value((row, 0), period);
+            sheet.set_value((row, 1), DataType::Float(1.0));
+            sheet.set_value((row, 2), DataType::Float(2.0));
+        }
+        let options = AnalysisOptions::default();
+        let analyzer = SheetAnalyzer { source: "test.xlsx", name: "Remittances", sheet: sheet.clone(), options: &options };
+        let preview = async_std::task::block_on(analyzer.preview()).unwrap();
+        assert_eq!((2, 0), preview.data_start);
+        assert_eq!(Some(Timestamp::CalendarYear(Year(NonZeroU16::new(2021).unwrap()))), preview.start);
+        assert_eq!(Some(1..2), preview.label_rows);
+        assert_eq!(2, preview.columns);
+        assert_eq!(3, preview.rows);
+        // Previews agree with merging
+        assert_eq!(Ok(3), analyze("Remittances", &sheet, &options).0.map_err(|error| error.to_string()));
+    }
+
+    #[test]
+    fn skip_error_cells() {
+        let mut sheet = Range::new((0, 0), (3, 1));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((0, 1), text("Reserves"));
+        for (row, (year, value)) in (1..).zip([
+            (2020.0, DataType::Float(1.5)), (2021.0, DataType::Error(CellErrorType::Div0)), (2022.0, DataType::Float(2.5))
+        ]) {
+            sheet.set_value((row, 0), DataType::Float(year));
+            sheet.set_value((row, 1), value);
+        }
+        let (result, merge_xl) = analyze("Reserves", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+
+        let yearly = async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear)).unwrap();
+        let reserves = yearly.columns().next().unwrap();
+        let value = |year| yearly.get(&Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap())))
+            .and_then(|row| row.get(&reserves).cloned());
+        assert_eq!(Some(Value::Number(1.5)), value(2020));
+        assert_eq!(None, value(2021));
+        assert_eq!(Some(Value::Number(2.5)), value(2022));
+    }
+
+    #[test]
+    fn skip_interior_empty_columns() {
+        // Empty columns between the periods and the data. Those further right borrow the
+        // label of their neighbour, so only these leave a column without any label
+        let labels = |empty_columns: u32, max_empty_columns| {
+            let mut sheet = Range::new((0, 0), (2, empty_columns + 2));
+            sheet.set_value((0, 0), text("Period"));
+            for (col, label) in [(empty_columns + 1, "Exports"), (empty_columns + 2, "Imports")] {
+                sheet.set_value((0, col), text(label));
+                sheet.set_value((1, col), DataType::Float(1.0));
+                sheet.set_value((2, col), DataType::Float(2.0));
+            }
+            sheet.set_value((1, 0), DataType::Float(2021.0));
+            sheet.set_value((2, 0), DataType::Float(2022.0));
+            let (_, merge_xl) = analyze("Trade", &sheet, &AnalysisOptions { max_empty_columns, ..AnalysisOptions::default() });
+            let mut labels = async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear))
+                .map(|yearly| yearly.columns().map(|column| column.display_full_labeling()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            labels.sort();
+            labels
+        };
+        assert!(labels(1, 0).is_empty());
+        assert_eq!(vec!["Exports", "Imports"], labels(1, 1));
+        assert!(labels(2, 1).is_empty());
+        assert_eq!(vec!["Exports", "Imports"], labels(2, 2));
+    }
+
+    #[test]
+    fn split_price_bases() {
+        let mut sheet = Range::new((0, 0), (4, 2));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((0, 1), text("Consumer Price Index"));
+        sheet.set_value((0, 2), text("Wages"));
+        for (row, (period, value)) in (1..).zip([("2010-11", 100.0), ("2011-12(OB)", 110.0), ("2011-12(NB)", 200.0), ("2012-13", 210.0)]) {
+            sheet.set_value((row, 0), text(period));
+            sheet.set_value((row, 1), DataType::Float(value));
+        }
+        // Wages are no price index, and have no old base
+        for (row, value) in [(1, 50.0), (3, 60.0), (4, 70.0)] {
+            sheet.set_value((row, 2), DataType::Float(value));
+        }
+        let fiscal = |year| Timestamp::FiscalYear(Year(NonZeroU16::new(year).unwrap()));
+        let read = |price_base_cutover| {
+            let (result, merge_xl) = analyze("Inflation", &sheet, &AnalysisOptions { price_base_cutover, ..Default::default() });
+            result.unwrap();
+            async_std::task::block_on(merge_xl.sheet(Granularity::FiscalYear)).unwrap()
+        };
+        let value = |sheet: &crate::merge::Sheet, year, column: &Column| sheet.get(&fiscal(year)).and_then(|row| row.get(column).cloned());
+
+        // Data of the old base is discarded by default
+        let general = Column::new([ColumnLabel::create("Consumer Price Index").unwrap()]).unwrap();
+        let wages = Column::new([ColumnLabel::create("Wages").unwrap()]).unwrap();
+        let discarded = read(None);
+        assert_eq!(2, discarded.columns().count());
+        assert_eq!(Some(Value::Number(200.0)), value(&discarded, 2011, &general));
+
+        let split = read(Some(Year(NonZeroU16::new(2012).unwrap())));
+        let old_base = general.with_sublabel(PriceBase::Old.label());
+        let new_base = general.with_sublabel(PriceBase::New.label());
+        assert_eq!(3, split.columns().count());
+        assert_eq!(
+            [Some(Value::Number(50.0)), Some(Value::Number(60.0)), Some(Value::Number(70.0))],
+            [2010, 2011, 2012].map(|year| value(&split, year, &wages))
+        );
+        assert_eq!(
+            [Some(Value::Number(100.0)), Some(Value::Number(110.0)), None],
+            [2010, 2011, 2012].map(|year| value(&split, year, &old_base))
+        );
+        assert_eq!(
+            [None, Some(Value::Number(200.0)), Some(Value::Number(210.0))],
+            [2010, 2011, 2012].map(|year| value(&split, year, &new_base))
+        );
+    }
+
+    #[test]
+    fn override_orientation() {
+        let mut sheet = Range::new((0, 0), (1, 2));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((1, 0), text("Exports"));
+        for (col, (year, value)) in (1..).zip([(2021.0, 10.0), (2022.0, 20.0)]) {
+            sheet.set_value((0, col), DataType::Float(year));
+            sheet.set_value((1, col), DataType::Float(value));
+        }
+        // Read as it is, the sheet makes no sense
+        assert!(analyze("Exports by Year", &sheet, &AnalysisOptions::default()).0.is_err());
+
+        let mut options = AnalysisOptions::default();
+        options.orientations.push((String::from("exports by year"), Orientation::Horizontal));
+        assert_eq!(Orientation::Vertical, options.orientation_of("Imports by year"));
+        let (result, merge_xl) = analyze("Exports by Year", &sheet, &options);
+        result.unwrap();
+
+        let year = |y| Timestamp::CalendarYear(Year(NonZeroU16::new(y).unwrap()));
+        let exports = Column::new([ColumnLabel::create("Exports").unwrap()]).unwrap();
+        let yearly = async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear)).unwrap();
+        assert_eq!(vec![year(2021), year(2022)], yearly.keys_in_order());
+        assert_eq!(Some(&Value::Number(20.0)), yearly.get(&year(2022)).unwrap().get(&exports));
+    }
+
+    #[test]
+    fn override_timestamp_column() {
+        let mut sheet = Range::new((0, 0), (3, 2));
+        for (col, header) in ["Code", "Period", "Exports"].into_iter().enumerate() {
+            sheet.set_value((0, col as u32), text(header));
+        }
+        let rows = [(text("X1"), 2020.0, 10.0), (DataType::Float(2015.0), 2021.0, 20.0), (text("X3"), 2022.0, 30.0)];
+        for (row, (code, year, value)) in (1..).zip(rows) {
+            sheet.set_value((row, 0), code);
+            sheet.set_value((row, 1), DataType::Float(year));
+            sheet.set_value((row, 2), DataType::Float(value));
+        }
+        let year = |y| Timestamp::CalendarYear(Year(NonZeroU16::new(y).unwrap()));
+        let exports = Column::new([ColumnLabel::create("Exports").unwrap()]).unwrap();
+        let read = |options: &AnalysisOptions| {
+            let (result, merge_xl) = analyze("Exports", &sheet, options);
+            result.ok()?;
+            async_std::task::block_on(merge_xl.sheet(Granularity::CalendarYear))
+        };
+        // The stray year in the codes is taken for the start of the data
+        let misread = read(&AnalysisOptions::default());
+        assert!(misread.is_none_or(|yearly| yearly.keys_in_order() != vec![year(2020), year(2021), year(2022)]));
+
+        for column in ["period", "#2"] {
+            let mut options = AnalysisOptions::default();
+            options.timestamp_columns.push((String::from("exports"), column.parse().unwrap()));
+            let yearly = read(&options).unwrap();
+            assert_eq!(vec![year(2020), year(2021), year(2022)], yearly.keys_in_order());
+            assert_eq!(Some(&Value::Number(20.0)), yearly.get(&year(2021)).unwrap().get(&exports));
+        }
+        assert_eq!(TimestampColumn::Index(1), "#2".parse().unwrap());
+        assert_eq!(TimestampColumn::Header(String::from("2021")), "2021".parse().unwrap());
+        assert!("#0".parse::<TimestampColumn>().is_err());
+        assert!("#B".parse::<TimestampColumn>().is_err());
+
+        let mut options = AnalysisOptions::default();
+        options.timestamp_columns.push((String::from("exports"), TimestampColumn::Index(3)));
+        assert!(analyze("Exports", &sheet, &options).0.is_err());
+    }
+
+    #[test]
+    fn start_without_years() {
+        let mut sheet = Range::new((0, 0), (3, 1));
+        sheet.set_value((0, 0), text("Period"));
+        sheet.set_value((0, 1), text("Remittances"));
+        for (row, (quarter, value)) in (1..).zip([("Jan-Mar", 1.0), ("Apr-Jun", 2.0), ("Jul-Sep", 3.0)]) {
+            sheet.set_value((row, 0), text(quarter));
+            sheet.set_value((row, 1), DataType::Float(value));
+        }
+
+        let (result, merge_xl) = analyze("Remittances 2021", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+        let year = Year(NonZeroU16::new(2021).unwrap());
+        let quarterly = async_std::task::block_on(merge_xl.sheet(Granularity::Quarterly)).unwrap();
+        assert_eq!(
+            vec![Quarter::JanFebMar, Quarter::AprMayJun, Quarter::JulAugSep]
+                .into_iter()
+                .map(|quarter| Timestamp::Quarterly(year, quarter))
+                .collect::<Vec<_>>(),
+            quarterly.keys_in_order()
+        );
+        assert_eq!(1, async_std::task::block_on(merge_xl.sheet_count()));
+
+        assert_matches!(analyze("Remittances", &sheet, &AnalysisOptions::default()).0, Err(AnalysisError::Unsupported { .. }));
+    }
+
+    #[test]
+    fn year_right_of_periods() {
+        let mut sheet = Range::new((0, 0), (3, 2));
+        sheet.set_value((0, 0), text("Notes"));
+        sheet.set_value((0, 1), text("Period"));
+        sheet.set_value((0, 2), text("Exports"));
+        sheet.set_value((1, 0), text("March"));
+        for (row, (period, value)) in (1..).zip([(DataType::Float(2022.0), 10.0), (text("May"), 1.0), (text("June"), 2.0)]) {
+            sheet.set_value((row, 1), period);
+            sheet.set_value((row, 2), DataType::Float(value));
+        }
+        let (result, merge_xl) = analyze("Exports", &sheet, &AnalysisOptions::default());
+        result.unwrap();
+
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        let month = |month| Timestamp::Monthly(MonthlyReport::new(year, month));
+        let monthly = async_std::task::block_on(merge_xl.sheet(Granularity::Monthly)).unwrap();
+        assert_eq!(vec![month(Month::May), month(Month::June)], monthly.keys_in_order());
+    }
+
+    #[test]
+    fn year_window_boundaries() {
+        fn is_year(year: i64, window: &YearWindow) -> bool {
+            matches!(
+                read_cell_as_timestamp(&DataType::Int(year), window, &NoOpInspector {}),
+                Ok(CellAsTimestamp::YearlyTimestamp(YearlyTimestamp::Calendar(_)))
+            )
+        }
+        let now = i64::from(current_year());
+        let default = YearWindow::default();
+        assert!(!is_year(1970, &default));
+        assert!(is_year(1971, &default));
+        assert!(is_year(now, &default));
+        assert!(is_year(now + 1, &default));
+        assert!(!is_year(now + 2, &default));
+        assert!(!is_year(-2000, &default));
+        assert!(!is_year(i64::from(u16::MAX) + 2000, &default));
+
+        let widened = YearWindow { earliest: 1947, future_tolerance: 0, two_digit_pivot: None };
+        assert!(is_year(1947, &widened));
+        assert!(!is_year(1946, &widened));
+        assert!(!is_year(now + 1, &widened));
+        // Floats are rounded
+        assert_matches!(
+            read_cell_as_timestamp(&DataType::Float(1947.0), &widened, &NoOpInspector {}),
+            Ok(CellAsTimestamp::YearlyTimestamp(_))
+        );
+    }
+
+    #[test]
+    fn two_digit_years() {
+        let read = |cell: DataType, window: &YearWindow| {
+            match read_cell_as_timestamp(&cell, window, &NoOpInspector {}).unwrap() {
+                CellAsTimestamp::YearlyTimestamp(timestamp) => Some(Timestamp::from(timestamp).to_string()),
+                _ => None
+            }
+        };
+        let pivot = YearWindow { two_digit_pivot: Some(30), ..YearWindow::default() };
+        assert_eq!(Some("2009"), read(DataType::String("09".to_string()), &pivot).as_deref());
+        assert_eq!(Some("1999"), read(DataType::String("99".to_string()), &pivot).as_deref());
+        assert_eq!(Some("2022"), read(DataType::Int(22), &pivot).as_deref());
+        assert_eq!(Some("1975"), read(DataType::Float(75.0), &pivot).as_deref());
+        // Expanded years must still fall in the window
+        assert_eq!(None, read(DataType::Int(45), &pivot));
+        // Four-digit years are unaffected
+        assert_eq!(Some("2015"), read(DataType::String("2015".to_string()), &pivot).as_deref());
+        // Two-digit years are not read unless enabled
+        assert_eq!(None, read(DataType::String("09".to_string()), &YearWindow::default()));
+        assert_eq!(None, read(DataType::Int(99), &YearWindow::default()));
     }
 }
@@ -0,0 +1,360 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Keeps the rows extracted from each data file, so that unchanged files need not be parsed
+// again on the next merge
+
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU16;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::UNIX_EPOCH;
+use async_std::fs;
+use async_std::path::{Path, PathBuf};
+use async_std::stream::StreamExt;
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use crate::analysis;
+use crate::analysis::{AnalysisOptions, RowDestination};
+use crate::common::*;
+use crate::merge::{Column, ColumnLabel, Institution, MergeXL, RowData, SheetError, SheetRows};
+use crate::value::{Unit, Value};
+
+/// The rows each data file yielded when last merged. A state file lists the files by name,
+/// with their modification time, length, and content hash, and the rows of each file are
+/// stored beside it. A file is parsed again only if its contents changed.
+///
+/// Rows depend on the analysis options as well as on the files, so the whole cache is
+/// discarded when the options or the program version change. Changes to the parsing code
+/// within a version go unnoticed; delete the cache directory after making any
+pub struct MergeCache {
+    directory: PathBuf,
+    fingerprint: u64,
+    /// The files as of the last merge
+    previous: HashMap<String, FileEntry>,
+    /// The files as of this merge, whether reused or parsed again
+    current: Mutex<HashMap<String, FileEntry>>,
+    reused: AtomicUsize
+}
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    fingerprint: u64,
+    files: HashMap<String, FileEntry>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct FileEntry {
+    /// Nanoseconds since the Unix epoch
+    modified: Option<u128>,
+    length: u64,
+    hash: u64,
+    merged: Vec<SheetRows>,
+    errors: Vec<SheetError>
+}
+
+/// A row in serializable form, along with where it goes
+#[derive(Serialize, Deserialize)]
+struct CachedRow {
+    key: CachedKey,
+    provisional: bool,
+    revised: bool,
+    cells: Vec<CachedCell>
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum CachedKey {
+    CalendarYear { year: u16 },
+    FiscalYear { year: u16 },
+    BiAnnual { year: u16, half: u8 },
+    Quarterly { year: u16, quarter: u8 },
+    Monthly { year: u16, month: u8 },
+    CrossSection { sheet: String, institution: String }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedCell {
+    labels: Vec<String>,
+    value: Value,
+    percentage: bool,
+    #[serde(default)]
+    unit: Option<Unit>,
+    filled: bool
+}
+
+const STATE_FILE: &str = "state.json";
+
+impl MergeCache {
+    /// Opens the cache in the given directory, creating the directory if needed. A cache
+    /// which cannot be read is started afresh
+    pub async fn open(directory: &Path, options: &AnalysisOptions) -> Result<Self> {
+        fs::create_dir_all(directory).await
+            .wrap_err_with(|| format!("While creating merge cache directory {}", directory.to_string_lossy()))?;
+        let fingerprint = fnv_hash(format!("{} {:?}", env!("CARGO_PKG_VERSION"), options).as_bytes());
+        let previous = match fs::read(directory.join(STATE_FILE)).await {
+            Ok(state) => match serde_json::from_slice::<State>(&state) {
+                Ok(state) if state.fingerprint == fingerprint => state.files,
+                Ok(_) => {
+                    log::info!("Analysis options or version changed since the last merge. Parsing every file again");
+                    HashMap::new()
+                }
+                Err(error) => {
+                    log::warn!("Discarding unreadable merge cache: {}", error);
+                    HashMap::new()
+                }
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error).wrap_err("While reading merge cache")
+        };
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            fingerprint,
+            previous,
+            current: Mutex::default(),
+            reused: AtomicUsize::new(0)
+        })
+    }
+
+    /// How many files were reused rather than parsed
+    pub fn reused(&self) -> usize {
+        self.reused.load(Ordering::Relaxed)
+    }
+
+    /// Adds the rows the file yielded last time to the merge, if the file is unchanged.
+    /// Yields the sheets merged and those which failed, as they were then
+    pub(crate) async fn replay(&self, file: &Path, merge_xl: &MergeXL) -> Option<(Vec<SheetRows>, Vec<SheetError>)> {
+        let name = file.file_name()?.to_string_lossy().into_owned();
+        let entry = self.previous.get(&name)?;
+        let (modified, length) = file_times(file).await.ok()?;
+        // Files may be touched without changing, as when downloaded again
+        if modified.is_none() || modified != entry.modified || length != entry.length {
+            let contents = fs::read(file).await.ok()?;
+            if fnv_hash(&contents) != entry.hash {
+                return None;
+            }
+        }
+        let rows = match self.read_rows(entry.hash).await {
+            Ok(rows) => rows,
+            Err(error) => {
+                log::warn!("Parsing {} again, since its cached rows are unreadable: {}", name, error);
+                return None;
+            }
+        };
+        for (key, row, provisional) in rows {
+            match key {
+                RowKey::Timestamp(timestamp) => merge_xl.add_row(timestamp, row, provisional).await,
+                RowKey::CrossSection(kind, institution) => merge_xl.add_cross_section_row(kind, institution, row).await
+            }
+        }
+        log::debug!("Reused the cached rows of unchanged file {}", name);
+        let entry = FileEntry { modified, length, ..entry.clone() };
+        let report = (entry.merged.clone(), entry.errors.clone());
+        self.current.lock().unwrap().insert(name, entry);
+        self.reused.fetch_add(1, Ordering::Relaxed);
+        Some(report)
+    }
+
+    /// Reads the cached rows of the file with the given hash. Fails if any of them cannot be
+    /// restored, so that a file is never partially reused
+    async fn read_rows(&self, hash: u64) -> Result<Vec<(RowKey, RowData, bool)>> {
+        let rows = fs::read(self.directory.join(rows_file(hash))).await?;
+        serde_json::from_slice::<Vec<CachedRow>>(&rows)?
+            .into_iter()
+            .map(CachedRow::restore)
+            .collect()
+    }
+
+    /// Stores the rows recorded from the file, along with its sheets merged and failed
+    pub(crate) async fn record(&self, file: &Path, recorder: Recorder<'_>,
+                               merged: &[SheetRows], errors: &[SheetError]) -> Result<()> {
+        let Some(name) = file.file_name().map(|name| name.to_string_lossy().into_owned()) else {
+            return Ok(());
+        };
+        let (modified, length) = file_times(file).await?;
+        let hash = fnv_hash(&fs::read(file).await?);
+        let rows = serde_json::to_vec(&recorder.rows.into_inner().unwrap())?;
+        fs::write(self.directory.join(rows_file(hash)), rows).await
+            .wrap_err_with(|| format!("While caching the rows of {}", name))?;
+        let entry = FileEntry { modified, length, hash, merged: merged.to_vec(), errors: errors.to_vec() };
+        self.current.lock().unwrap().insert(name, entry);
+        Ok(())
+    }
+
+    /// Writes the state of this merge, and removes the rows of files no longer present
+    pub async fn save(self) -> Result<()> {
+        let state = State {
+            fingerprint: self.fingerprint,
+            files: self.current.into_inner().unwrap()
+        };
+        let kept = state.files.values().map(|entry| rows_file(entry.hash)).collect::<HashSet<_>>();
+        fs::write(self.directory.join(STATE_FILE), serde_json::to_vec(&state)?).await
+            .wrap_err("While writing merge cache")?;
+
+        let mut files = fs::read_dir(&self.directory).await?;
+        while let Some(file) = files.next().await.transpose()? {
+            let name = file.file_name().to_string_lossy().into_owned();
+            if name.starts_with("rows-") && !kept.contains(&name) {
+                fs::remove_file(file.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the rows analysis yields from a file, on their way to the merge
+pub(crate) struct Recorder<'m> {
+    merge_xl: &'m MergeXL,
+    rows: Mutex<Vec<CachedRow>>
+}
+
+impl<'m> Recorder<'m> {
+    pub(crate) fn new(merge_xl: &'m MergeXL) -> Self {
+        Self { merge_xl, rows: Mutex::default() }
+    }
+}
+
+impl RowDestination for Recorder<'_> {
+    async fn add_row(&self, timestamp: Timestamp, row: RowData, provisional: bool) {
+        self.rows.lock().unwrap().push(CachedRow::new(CachedKey::from(timestamp), &row, provisional));
+        self.merge_xl.add_row(timestamp, row, provisional).await
+    }
+
+    async fn add_cross_section_row(&self, kind: &'static str, institution: Institution, row: RowData) {
+        let key = CachedKey::CrossSection { sheet: kind.to_string(), institution: institution.to_string() };
+        self.rows.lock().unwrap().push(CachedRow::new(key, &row, false));
+        self.merge_xl.add_cross_section_row(kind, institution, row).await
+    }
+}
+
+/// Where a restored row goes
+enum RowKey {
+    Timestamp(Timestamp),
+    CrossSection(&'static str, Institution)
+}
+
+impl CachedRow {
+    fn new(key: CachedKey, row: &RowData, provisional: bool) -> Self {
+        let cells = row.iter()
+            .map(|(column, value)| CachedCell {
+                labels: (0..column.depth()).filter_map(|level| column.label_at(level)).map(String::from).collect(),
+                value: value.clone(),
+                percentage: row.is_percentage(column),
+                unit: row.unit(column),
+                filled: row.is_filled(column)
+            })
+            .collect();
+        Self { key, provisional, revised: row.is_revised(), cells }
+    }
+
+    fn restore(self) -> Result<(RowKey, RowData, bool)> {
+        let mut row = RowData::default();
+        if self.revised {
+            row.mark_revised();
+        }
+        for cell in self.cells {
+            let labels = cell.labels.iter()
+                .map(|label| ColumnLabel::create(label))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| eyre::eyre!("Blank label in {:?}", cell.labels))?;
+            let column = Column::new(labels)?;
+            if cell.percentage {
+                row.mark_percentage(&column);
+            }
+            if let Some(unit) = cell.unit {
+                row.mark_unit(&column, unit);
+            }
+            if cell.filled {
+                row.mark_filled(&column);
+            }
+            row.populate(&column, cell.value);
+        }
+        let key = match self.key {
+            CachedKey::CrossSection { sheet, institution } => {
+                let kind = analysis::cross_section_kind(&sheet)
+                    .ok_or_else(|| eyre::eyre!("Unknown cross-section {}", sheet))?;
+                let institution = Institution::create(&institution)
+                    .ok_or_else(|| eyre::eyre!("Blank institution"))?;
+                RowKey::CrossSection(kind, institution)
+            }
+            key => RowKey::Timestamp(Timestamp::try_from(key)?)
+        };
+        Ok((key, row, self.provisional))
+    }
+}
+
+impl From<Timestamp> for CachedKey {
+    fn from(value: Timestamp) -> Self {
+        match value {
+            Timestamp::CalendarYear(year) => Self::CalendarYear { year: year.0.get() },
+            Timestamp::FiscalYear(year) => Self::FiscalYear { year: year.0.get() },
+            Timestamp::BiAnnually(year, half) => Self::BiAnnual { year: year.0.get(), half: half as u8 },
+            Timestamp::Quarterly(year, quarter) => Self::Quarterly { year: year.0.get(), quarter: quarter as u8 },
+            Timestamp::Monthly(report) => Self::Monthly { year: report.year.0.get(), month: report.month.as_numeric() }
+        }
+    }
+}
+
+impl TryFrom<CachedKey> for Timestamp {
+    type Error = eyre::Report;
+
+    fn try_from(value: CachedKey) -> Result<Self, Self::Error> {
+        let year = |year| NonZeroU16::new(year).map(Year).ok_or_else(|| eyre::eyre!("Year zero"));
+        Ok(match value {
+            CachedKey::CalendarYear { year: y } => Self::CalendarYear(year(y)?),
+            CachedKey::FiscalYear { year: y } => Self::FiscalYear(year(y)?),
+            CachedKey::BiAnnual { year: y, half } => {
+                let half = [HalfYear::JanThruJun, HalfYear::JulThruDec]
+                    .get(usize::from(half))
+                    .ok_or_else(|| eyre::eyre!("Invalid half-year {}", half))?;
+                Self::BiAnnually(year(y)?, *half)
+            }
+            CachedKey::Quarterly { year: y, quarter } => {
+                let quarter = [Quarter::JanFebMar, Quarter::AprMayJun, Quarter::JulAugSep, Quarter::OctNovDec]
+                    .get(usize::from(quarter))
+                    .ok_or_else(|| eyre::eyre!("Invalid quarter {}", quarter))?;
+                Self::Quarterly(year(y)?, *quarter)
+            }
+            CachedKey::Monthly { year: y, month } => {
+                let month = Month::try_from(month).map_err(|_| eyre::eyre!("Invalid month {}", month))?;
+                Self::Monthly(MonthlyReport::new(year(y)?, month))
+            }
+            CachedKey::CrossSection { .. } => return Err(eyre::eyre!("Cross-sections have no timestamp"))
+        })
+    }
+}
+
+/// The modification time and length of a file
+async fn file_times(file: &Path) -> Result<(Option<u128>, u64)> {
+    let metadata = fs::metadata(file).await?;
+    let modified = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|since_epoch| since_epoch.as_nanos());
+    Ok((modified, metadata.len()))
+}
+
+fn rows_file(hash: u64) -> String {
+    format!("rows-{:016x}.json", hash)
+}
+
+/// The 64-bit FNV-1a hash, which unlike the standard library's hasher is stable across
+/// versions of Rust
+fn fnv_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3))
+}
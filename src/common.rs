@@ -19,14 +19,12 @@
 
 use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
-use std::num::NonZeroU16;
-use assert_matches::assert_matches;
-use chrono::Datelike;
+use std::num::{NonZeroU16, TryFromIntError};
+use chrono::{Datelike, NaiveDate};
 
 pub fn current_year() -> u16 {
     let current_year = chrono::Utc::now();
-    let current_year = current_year.year() as u16;
-    current_year
+    current_year.year() as u16
 }
 
 // Structs
@@ -34,13 +32,13 @@ pub fn current_year() -> u16 {
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct Year(pub NonZeroU16);
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct MonthlyReport {
     pub year: Year,
     pub month: Month
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Timestamp {
     CalendarYear(Year),
     FiscalYear(Year),
@@ -49,6 +47,22 @@ pub enum Timestamp {
     Monthly(MonthlyReport)
 }
 
+/// The kind of period a timestamp covers. Each kind of timestamp is written to its own file.
+///
+/// Calendar and fiscal years are kept apart deliberately. Both are yearly, but the 2019
+/// calendar year and the 2019-20 fiscal year are different periods, and mixing them in one
+/// file would make the rows easy to confuse.
+///
+/// Granularities are ordered from the longest period to the shortest.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Granularity {
+    CalendarYear,
+    FiscalYear,
+    BiAnnual,
+    Quarterly,
+    Monthly
+}
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
 pub enum YearlyTimestamp {
     Calendar(Year),
@@ -70,7 +84,7 @@ pub enum Quarter {
 }
 
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct Month {
     inner: chrono::Month
 }
@@ -79,20 +93,165 @@ pub trait MonthBounds {
     fn start_and_end_month(&self) -> (Month, Month);
 }
 
+impl Year {
+    pub fn next(&self) -> Option<Self> {
+        self.0.checked_add(1).map(Self)
+    }
+}
+
 impl MonthlyReport {
     pub fn new(year: Year, month: Month) -> Self {
         Self { year, month }
     }
+
+    /// The following month
+    pub fn next(&self) -> Option<Self> {
+        if self.month == Month::December {
+            Some(Self::new(self.year.next()?, Month::January))
+        } else {
+            let month = Month::try_from(self.month.as_numeric() + 1).ok()?;
+            Some(Self::new(self.year, month))
+        }
+    }
+}
+
+// Conversions to and from chrono dates, for consumers of the merged data
+
+impl MonthlyReport {
+    /// The first day of the month
+    pub fn to_naive_date(self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(i32::from(self.year.0.get()), u32::from(self.month.as_numeric()), 1)
+            .expect("Every u16 year is within chrono's range")
+    }
+
+    /// The last day of the month
+    pub fn last_naive_date(self) -> NaiveDate {
+        match self.next() {
+            Some(next) => next.to_naive_date().pred_opt().expect("Previous day exists"),
+            None => NaiveDate::from_ymd_opt(i32::from(self.year.0.get()), 12, 31).expect("Valid date")
+        }
+    }
+}
+
+impl Timestamp {
+    /// The first day of the period
+    pub fn start_date(&self) -> NaiveDate {
+        self.date_range().0.to_naive_date()
+    }
+
+    /// The last day of the period
+    pub fn end_date(&self) -> NaiveDate {
+        self.date_range().1.last_naive_date()
+    }
+}
+
+impl TryFrom<NaiveDate> for MonthlyReport {
+    type Error = TryFromIntError;
+
+    /// Gets the month containing the date. Fails for years before 1 or after 65535
+    fn try_from(date: NaiveDate) -> Result<Self, Self::Error> {
+        let year = NonZeroU16::try_from(u16::try_from(date.year())?)?;
+        let month = chrono::Month::try_from(date.month() as u8)
+            .expect("Chrono months are always valid");
+        Ok(Self::new(Year(year), Month::from_chrono(month)))
+    }
 }
 
 impl Timestamp {
-    /// How long a period the timestamp covers, in months
-    fn length_of_period_in_months(&self) -> u8 {
+    pub fn granularity(&self) -> Granularity {
+        match self {
+            Self::CalendarYear(_) => Granularity::CalendarYear,
+            Self::FiscalYear(_) => Granularity::FiscalYear,
+            Self::BiAnnually(..) => Granularity::BiAnnual,
+            Self::Quarterly(..) => Granularity::Quarterly,
+            Self::Monthly(_) => Granularity::Monthly
+        }
+    }
+
+    /// The first and last months covered by the timestamp. Fiscal years run from July to June
+    pub fn date_range(&self) -> (MonthlyReport, MonthlyReport) {
+        fn within_year(year: Year, bounds: &impl MonthBounds) -> (MonthlyReport, MonthlyReport) {
+            let (start, end) = bounds.start_and_end_month();
+            (MonthlyReport::new(year, start), MonthlyReport::new(year, end))
+        }
+        match *self {
+            Self::CalendarYear(year) => {
+                (MonthlyReport::new(year, Month::January), MonthlyReport::new(year, Month::December))
+            },
+            Self::FiscalYear(year) => {
+                let next_year = year.next().unwrap_or(year);
+                (MonthlyReport::new(year, Month::July), MonthlyReport::new(next_year, Month::June))
+            },
+            Self::BiAnnually(year, halfyear) => within_year(year, &halfyear),
+            Self::Quarterly(year, quarter) => within_year(year, &quarter),
+            Self::Monthly(report) => (report, report)
+        }
+    }
+
+    /// How many months the timestamp covers
+    pub fn month_count(&self) -> u32 {
+        let month_index = |report: MonthlyReport| u32::from(report.year.0.get()) * 12 + u32::from(report.month.as_numeric());
+        let (start, end) = self.date_range();
+        month_index(end) + 1 - month_index(start)
+    }
+
+    /// The timestamp of the same kind which immediately follows this one
+    pub fn next(&self) -> Option<Self> {
+        Some(match *self {
+            Self::CalendarYear(year) => Self::CalendarYear(year.next()?),
+            Self::FiscalYear(year) => Self::FiscalYear(year.next()?),
+            Self::BiAnnually(year, HalfYear::JanThruJun) => Self::BiAnnually(year, HalfYear::JulThruDec),
+            Self::BiAnnually(year, HalfYear::JulThruDec) => Self::BiAnnually(year.next()?, HalfYear::JanThruJun),
+            Self::Quarterly(year, Quarter::OctNovDec) => Self::Quarterly(year.next()?, Quarter::JanFebMar),
+            Self::Quarterly(year, quarter) => {
+                let next_quarter = match quarter {
+                    Quarter::JanFebMar => Quarter::AprMayJun,
+                    Quarter::AprMayJun => Quarter::JulAugSep,
+                    _ => Quarter::OctNovDec
+                };
+                Self::Quarterly(year, next_quarter)
+            },
+            Self::Monthly(report) => Self::Monthly(report.next()?)
+        })
+    }
+}
+
+impl Granularity {
+    pub fn values() -> [Self; 5] {
+        [Self::CalendarYear, Self::FiscalYear, Self::BiAnnual, Self::Quarterly, Self::Monthly]
+    }
+
+    /// The period of this granularity which contains the month. Yields none only for the
+    /// fiscal year before year 1
+    pub fn period_containing(&self, report: MonthlyReport) -> Option<Timestamp> {
+        fn containing<B: MonthBounds>(values: impl IntoIterator<Item=B>, month: Month) -> B {
+            values
+                .into_iter()
+                .find(|bounds| {
+                    let (start, end) = bounds.start_and_end_month();
+                    start <= month && month <= end
+                })
+                .expect("Every month falls within a period")
+        }
+        let MonthlyReport { year, month } = report;
+        Some(match self {
+            Self::CalendarYear => Timestamp::CalendarYear(year),
+            Self::FiscalYear if month >= Month::July => Timestamp::FiscalYear(year),
+            Self::FiscalYear => Timestamp::FiscalYear(Year(NonZeroU16::new(year.0.get() - 1)?)),
+            Self::BiAnnual => Timestamp::BiAnnually(year, containing(HalfYear::values(), month)),
+            Self::Quarterly => Timestamp::Quarterly(year, containing(Quarter::values(), month)),
+            Self::Monthly => Timestamp::Monthly(report)
+        })
+    }
+
+    /// A readable name, suitable for file names
+    pub fn name(&self) -> &'static str {
         match self {
-            Self::FiscalYear(_) | Self::CalendarYear(_) => 12,
-            Self::BiAnnually(..) => 6,
-            Self::Quarterly(..) => 3,
-            Self::Monthly(..) => 1,
+            Self::CalendarYear => "calendar-yearly",
+            Self::FiscalYear => "fiscal-yearly",
+            Self::BiAnnual => "biannual",
+            Self::Quarterly => "quarterly",
+            Self::Monthly => "monthly"
         }
     }
 }
@@ -152,46 +311,26 @@ impl From<YearlyTimestamp> for Year {
 
 // Implement ordering so that we can perform sort operations later
 
+/// Timestamps are grouped by granularity, longer periods first, and are chronological within
+/// each granularity. For a purely chronological order across granularities, which
+/// interleaves a 2009 monthly timestamp before a 2020 yearly one, use
+/// [Timestamp::cmp_chronologically].
 impl Ord for Timestamp {
     fn cmp(&self, other: &Self) -> Ordering {
-        let order_period = Ord::cmp(&self.length_of_period_in_months(), &other.length_of_period_in_months());
-        if order_period != Ordering::Equal {
-            // Put longer periods first
-            return order_period.reverse();
-        }
-        // Otherwise, order first by year, then other details
-        match *self {
-            Self::CalendarYear(year) => {
-                let other_year = assert_matches!(other, Self::CalendarYear(oy) => oy);
-                year.cmp(other_year)
-            }
-            Self::FiscalYear(year) => {
-                let other_year = assert_matches!(other, Self::FiscalYear(oy) => oy);
-                year.cmp(other_year)
-            },
-            Self::BiAnnually(year, halfyear) => {
-                let (other_year, other_halfyear) = assert_matches!(other, Self::BiAnnually(oy, ohy) => (oy, ohy));
-                let order_year = year.cmp(other_year);
-                if order_year != Ordering::Equal {
-                    order_year
-                } else {
-                    halfyear.cmp(other_halfyear)
-                }
-            },
-            Self::Quarterly(year, quarter) => {
-                let (other_year, other_quarter) = assert_matches!(other, Self::Quarterly(oy, oq) => (oy, oq));
-                let order_year = year.cmp(other_year);
-                if order_year != Ordering::Equal {
-                    order_year
-                } else {
-                    quarter.cmp(other_quarter)
-                }
-            },
-            Self::Monthly(report) => {
-                let other_report = assert_matches!(other, Self::Monthly(r) => r);
-                report.cmp(other_report)
-            }
-        }
+        self.granularity()
+            .cmp(&other.granularity())
+            .then_with(|| self.date_range().0.cmp(&other.date_range().0))
+    }
+}
+
+impl Timestamp {
+    /// Orders timestamps by the month they start in. Timestamps starting in the same month
+    /// are ordered by granularity, longer periods first. This is a total order consistent
+    /// with equality, like [Ord] for Timestamp.
+    pub fn cmp_chronologically(&self, other: &Self) -> Ordering {
+        self.date_range().0
+            .cmp(&other.date_range().0)
+            .then_with(|| self.granularity().cmp(&other.granularity()))
     }
 }
 
@@ -212,6 +351,19 @@ impl Ord for Month {
     }
 }
 
+macro_rules! impl_partial_ord_using_ord {
+    ($($strct:ty),+) => {
+        $(
+        impl PartialOrd for $strct {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        )+
+    }
+}
+impl_partial_ord_using_ord!(Timestamp, MonthlyReport, Month);
+
 // Display enables us to write all of these structs as strings later on
 
 impl Display for Year {
@@ -220,6 +372,12 @@ impl Display for Year {
     }
 }
 
+impl Display for Granularity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 impl Display for Month {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", &self.name()[0..3])
@@ -238,8 +396,9 @@ impl Display for Timestamp {
         match *self {
             Self::CalendarYear(year) => year.fmt(f),
             Self::FiscalYear(year) => {
-                let next_year = (year.0.get() % 100) + 1;
-                write!(f, "{}-{}", year, next_year)
+                // e.g. 2008-09 and 2099-00
+                let next_year = (year.0.get() + 1) % 100;
+                write!(f, "{}-{:02}", year, next_year)
             },
             Self::BiAnnually(year, half_year) => {
                 // e.g. 2009 Jan-Jun
@@ -256,10 +415,14 @@ impl Display for Timestamp {
 
 impl Month {
     pub fn trim_start_matches_from<'v>(&self, value: &'v str) -> &'v str {
+        if let Some(stripped) = value.strip_prefix(self.name()) {
+            // e.g. September
+            return stripped;
+        }
         let short_name = &self.name()[..3];
         if value.starts_with(short_name) {
             // Trim the short name
-            if value.len() >= 4 && &value[3..4] == &self.name()[3..4] {
+            if value.len() >= 4 && value[3..4] == self.name()[3..4] {
                 // Matches four-character short name
                 &value[4..]
             } else {
@@ -273,9 +436,9 @@ impl Month {
 
     pub fn trim_end_matches_from<'v>(&self, value: &'v str) -> &'v str {
         let name = self.name();
-        for name in [&name[..4], &name[..3]] {
-            if value.ends_with(name) {
-                return &value[..value.len() - name.len()];
+        for name in [name, &name[..4], &name[..3]] {
+            if let Some(stripped) = value.strip_suffix(name) {
+                return stripped;
             }
         }
         value
@@ -338,6 +501,54 @@ impl Display for HalfYear {
 mod tests {
     use std::collections::HashSet;
     use super::*;
+
+    #[test]
+    fn naive_date_round_trip() {
+        for (year, month, day) in [(2009, 1, 1), (2019, 7, 15), (2020, 2, 29), (2023, 12, 31)] {
+            let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+            let report = MonthlyReport::try_from(date).unwrap();
+            assert_eq!(year as u16, report.year.0.get());
+            assert_eq!(month as u8, report.month.as_numeric());
+            assert_eq!(NaiveDate::from_ymd_opt(year, month, 1).unwrap(), report.to_naive_date());
+            assert_eq!(Ok(report), MonthlyReport::try_from(report.to_naive_date()));
+        }
+        assert!(MonthlyReport::try_from(NaiveDate::from_ymd_opt(0, 6, 1).unwrap()).is_err());
+        assert!(MonthlyReport::try_from(NaiveDate::from_ymd_opt(-44, 3, 15).unwrap()).is_err());
+    }
+
+    #[test]
+    fn timestamp_dates() {
+        let year = |y| Year(NonZeroU16::new(y).unwrap());
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+
+        let fiscal = Timestamp::FiscalYear(year(2019));
+        assert_eq!((date(2019, 7, 1), date(2020, 6, 30)), (fiscal.start_date(), fiscal.end_date()));
+        let february = Timestamp::Monthly(MonthlyReport::new(year(2020), Month::February));
+        assert_eq!((date(2020, 2, 1), date(2020, 2, 29)), (february.start_date(), february.end_date()));
+        let quarter = Timestamp::Quarterly(year(2023), Quarter::OctNovDec);
+        assert_eq!((date(2023, 10, 1), date(2023, 12, 31)), (quarter.start_date(), quarter.end_date()));
+    }
+
+    #[test]
+    fn order_timestamps() {
+        let year = |y| Year(NonZeroU16::new(y).unwrap());
+        let calendar_2019 = Timestamp::CalendarYear(year(2019));
+        let fiscal_2019 = Timestamp::FiscalYear(year(2019));
+        let monthly_2009 = Timestamp::Monthly(MonthlyReport::new(year(2009), Month::March));
+        let calendar_2020 = Timestamp::CalendarYear(year(2020));
+        let quarter_2019 = Timestamp::Quarterly(year(2019), Quarter::JanFebMar);
+
+        // Calendar and fiscal years of equal length compare without panicking
+        assert_eq!(Ordering::Less, calendar_2019.cmp(&fiscal_2019));
+        assert_eq!(Ordering::Less, calendar_2020.cmp(&monthly_2009));
+
+        let mut timestamps = vec![calendar_2020, fiscal_2019, monthly_2009, quarter_2019, calendar_2019];
+        timestamps.sort();
+        assert_eq!(vec![calendar_2019, calendar_2020, fiscal_2019, quarter_2019, monthly_2009], timestamps);
+
+        timestamps.sort_by(Timestamp::cmp_chronologically);
+        assert_eq!(vec![monthly_2009, calendar_2019, quarter_2019, fiscal_2019, calendar_2020], timestamps);
+    }
     #[test]
     fn size_of_timestamp() {
         assert!(std::mem::size_of::<Timestamp>() <= std::mem::size_of::<u64>())
@@ -409,6 +620,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fiscal_year_range() {
+        let year_2009 = Year(NonZeroU16::new(2009).unwrap());
+        let year_2010 = Year(NonZeroU16::new(2010).unwrap());
+        assert_eq!(
+            (MonthlyReport::new(year_2009, Month::July), MonthlyReport::new(year_2010, Month::June)),
+            Timestamp::FiscalYear(year_2009).date_range()
+        );
+    }
+
+    #[test]
+    fn next_timestamp() {
+        let year_2009 = Year(NonZeroU16::new(2009).unwrap());
+        let year_2010 = Year(NonZeroU16::new(2010).unwrap());
+        assert_eq!(
+            Some(Timestamp::Monthly(MonthlyReport::new(year_2010, Month::January))),
+            Timestamp::Monthly(MonthlyReport::new(year_2009, Month::December)).next()
+        );
+        assert_eq!(
+            Some(Timestamp::Quarterly(year_2009, Quarter::JulAugSep)),
+            Timestamp::Quarterly(year_2009, Quarter::AprMayJun).next()
+        );
+        assert_eq!(
+            Some(Timestamp::BiAnnually(year_2010, HalfYear::JanThruJun)),
+            Timestamp::BiAnnually(year_2009, HalfYear::JulThruDec).next()
+        );
+        assert_eq!(Some(Timestamp::FiscalYear(year_2010)), Timestamp::FiscalYear(year_2009).next());
+    }
+
     #[test]
     fn all_months_present() {
         let mut months_map = HashSet::new();
@@ -0,0 +1,186 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Comparison of merged data, to track revisions between releases
+
+use std::collections::HashMap;
+use futures_io::AsyncWrite;
+use eyre::Result;
+use crate::merge::{Column, RowKey, Sheet};
+use crate::output::OutputOptions;
+use crate::value::Value;
+
+/// How a single cell differs between two versions of a sheet
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Revised { previous: Value, revised: Value }
+}
+
+/// A changed cell, and where it was found
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellChange<K> {
+    pub key: K,
+    pub column: Column,
+    pub change: Change
+}
+
+impl Change {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Added(_) => "added",
+            Self::Removed(_) => "removed",
+            Self::Revised { .. } => "revised"
+        }
+    }
+
+    pub fn previous(&self) -> Option<&Value> {
+        match self {
+            Self::Added(_) => None,
+            Self::Removed(previous) | Self::Revised { previous, .. } => Some(previous)
+        }
+    }
+
+    pub fn revised(&self) -> Option<&Value> {
+        match self {
+            Self::Removed(_) => None,
+            Self::Added(revised) | Self::Revised { revised, .. } => Some(revised)
+        }
+    }
+}
+
+/// Compares every cell of two versions of a sheet. Empty values count as absent.
+/// Changes are ordered by row, in the order rows are written, then by column
+pub fn compare<K>(previous: &Sheet<K>, revised: &Sheet<K>) -> Vec<CellChange<K>> where K: RowKey {
+    let mut keys = previous.keys_in_order();
+    keys.extend(revised.keys_in_order());
+    keys.sort_by(K::cmp_for_output);
+    keys.dedup();
+
+    let mut changes = Vec::new();
+    for key in keys {
+        let previous_row = previous.rows.get(&key);
+        let revised_row = revised.rows.get(&key);
+        let mut previous_cells = previous_row
+            .iter()
+            .flat_map(|row| row.iter())
+            .filter(|(_, value)| !value.is_empty())
+            .collect::<HashMap<_, _>>();
+
+        let mut row_changes = Vec::new();
+        for (column, value) in revised_row.iter().flat_map(|row| row.iter()).filter(|(_, value)| !value.is_empty()) {
+            let change = match previous_cells.remove(column) {
+                None => Change::Added(value.clone()),
+                Some(previous) if previous != value => Change::Revised {
+                    previous: previous.clone(),
+                    revised: value.clone()
+                },
+                // Unchanged
+                Some(_) => continue
+            };
+            row_changes.push((column.clone(), change));
+        }
+        row_changes.extend(previous_cells
+            .into_iter()
+            .map(|(column, value)| (column.clone(), Change::Removed(value.clone()))));
+        row_changes.sort_by_cached_key(|(column, _)| column.display_full_labeling());
+
+        changes.extend(row_changes.into_iter().map(|(column, change)| CellChange {
+            key: key.clone(), column, change
+        }));
+    }
+    changes
+}
+
+/// Writes changes in CSV form, one per line. Absent values are left blank
+pub async fn write_changes<W, K>(writer: W, changes: &[CellChange<K>], options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    let mut writer = options.csv_writer(writer);
+    writer.write_record([K::HEADER, "column", "change", "previous", "revised"]).await?;
+    for CellChange { key, column, change } in changes {
+        let display = |value: Option<&Value>| value.map(ToString::to_string).unwrap_or_default();
+        writer.write_record([
+            key.output_value(options),
+            options.display_column(column),
+            change.name().to_string(),
+            display(change.previous()),
+            display(change.revised())
+        ]).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use async_std::task;
+    use crate::common::*;
+    use crate::merge::{ColumnLabel, RowData};
+    use super::*;
+
+    #[test]
+    fn compare_revisions() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let sheet = |rows: &[(u16, &str, Value)]| {
+            let sheet = Sheet::default();
+            for (timestamp, label, value) in rows {
+                let mut row = RowData::default();
+                row.populate(&column(label), value.clone());
+                sheet.add_row(year(*timestamp), row);
+            }
+            sheet
+        };
+        let previous = sheet(&[
+            (2009, "Exports", Value::from(1.0)),
+            (2009, "Imports", Value::from(2.0)),
+            (2010, "Exports", Value::from(3.0)),
+            (2010, "Imports", Value::Empty)
+        ]);
+        let revised = sheet(&[
+            (2009, "Exports", Value::from(1.0)),
+            (2009, "Imports", Value::from(2.5)),
+            (2011, "Exports", Value::from("4 (P)"))
+        ]);
+        let changes = compare(&previous, &revised);
+        assert_eq!(vec![
+            CellChange {
+                key: year(2009),
+                column: column("Imports"),
+                change: Change::Revised { previous: Value::from(2.0), revised: Value::from(2.5) }
+            },
+            CellChange { key: year(2010), column: column("Exports"), change: Change::Removed(Value::from(3.0)) },
+            CellChange { key: year(2011), column: column("Exports"), change: Change::Added(Value::from("4 (P)")) }
+        ], changes);
+        assert!(compare(&revised, &revised).is_empty());
+
+        let mut output = Vec::new();
+        task::block_on(write_changes(&mut output, &changes, &OutputOptions::default())).unwrap();
+        assert_eq!(
+            "timestamp-primary-key,column,change,previous,revised\n\
+            2009,Imports,revised,2,2.5\n\
+            2010,Exports,removed,3,\n\
+            2011,Exports,added,,4 (P)\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
@@ -20,95 +20,597 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::num::NonZeroU16;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use async_std::{fs, io};
+use async_std::io::ReadExt;
 use async_std::path::{Path, PathBuf};
-use async_std::stream::StreamExt;
-use eyre::Result;
+use async_std::future::TimeoutError;
+use async_std::stream::{Stream, StreamExt};
+use dashmap::DashMap;
+use eyre::{Result, WrapErr};
+use futures::future::{BoxFuture, Future};
 use futures::stream::FuturesUnordered;
-use hyper::Uri;
+use hyper::{header, HeaderMap, Uri};
 use crate::common::{current_year, MonthlyReport, Year, Month};
-use crate::http::{Connection, DownloadHandler};
+use crate::http::{Connection, DownloadHandler, Host, TransferFailed};
+use crate::index::ReportIndex;
+use crate::wayback;
 
-const WEBSITE_PREFIX: &'static str = "https://www.bb.org.bd/pub/monthly/econtrds";
+const WEBSITE_PREFIX: &str = "https://www.bb.org.bd/pub/monthly/econtrds";
+const URL_TEMPLATES: [&str; 4] = [
+    "et{month}{year}.{ext}",
+    "econtrends_{month}{year}.{ext}",
+    "ET{month}{year}.{ext}",
+    "{month}{year}/statisticaltable.{ext}"
+];
 const XL_EXTENSIONS: [SheetExtension; 2] = [SheetExtension::Xlsx, SheetExtension::Xls];
 
 pub struct Download<'d> {
     data_dir: &'d Path,
-    total_hit_count: AtomicUsize
+    sources: UrlSources,
+    total_hit_count: AtomicUsize,
+    /// Once this many URLs of the central bank website are accessed, no further months
+    /// are started. Months in progress are finished, so the budget may be slightly exceeded
+    max_hits: Option<usize>,
+    /// How long the whole run may take. Once it passes, months in progress are abandoned
+    /// and the run ends with a summary of the months finished
+    deadline: Option<Duration>,
+    /// Whether to download reports again even if they exist
+    force: bool,
+    /// Set once the user presses Ctrl-C. No further months are started afterward
+    interrupted: Arc<AtomicBool>,
+    /// Whether to connect over IPv6 when the host has both kinds of address
+    prefer_ipv6: bool,
+    /// Whether network access is forbidden, such that reports missing locally are errors
+    offline: bool,
+    /// Whether to discover reports from the listing page, trying URL patterns only for
+    /// months it does not list
+    use_index: bool,
+    /// Whether to look for reports missing from the website in the Wayback Machine
+    use_wayback: bool,
+    wayback_hit_count: AtomicUsize,
+    /// How many reports were found at each URL pattern, to tell which patterns are still used
+    pattern_tally: DashMap<UrlPattern, usize>,
+    /// Called as each report is downloaded or found to exist already
+    report_hook: Option<ReportHook<'d>>,
+    /// Websites tried in order for reports which the central bank website does not serve
+    mirrors: Vec<UrlSources>,
+    /// How each mirror was used, by its website prefix
+    mirror_usage: DashMap<String, MirrorUsage>,
+    /// The outcomes of the months finished so far, by the year in progress. A year abandoned
+    /// at the deadline is summarized from here
+    finished_months: DashMap<Year, HashMap<Month, ReportStatus>>
+}
+
+/// What the years downloaded, in total
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct DownloadTotals {
+    downloads: usize,
+    refreshes: usize,
+    bytes: u64,
+    /// Months attempted to the end, whatever became of them
+    months: usize
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+struct MirrorUsage {
+    hit_count: usize,
+    /// How many reports the mirror served
+    served: usize
+}
+
+/// A callback given each report available locally and the path of its file
+type ReportHook<'d> = Box<dyn Fn(MonthlyReport, PathBuf) -> BoxFuture<'d, Result<()>> + Send + Sync + 'd>;
+
+/// Which URL template a report was found at, and how its month and year were spelled
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct UrlPattern {
+    /// The index of the template among the URL sources
+    template: usize,
+    month: MonthSpelling,
+    year: YearSpelling
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum MonthSpelling {
+    /// e.g. July
+    Full,
+    /// e.g. july
+    Lowercase,
+    /// e.g. Jul
+    Abbreviated,
+    /// e.g. jul
+    LowercaseAbbreviated
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum YearSpelling {
+    /// e.g. 2014
+    Full,
+    /// e.g. 14
+    TwoDigit
+}
+
+/// A connection opened only once it is first needed
+struct LazyConnection {
+    host: Host,
+    prefer_ipv6: bool,
+    offline: bool,
+    connection: Option<Connection>
+}
+
+/// The connections shared by every month of a year
+struct Connections {
+    website: LazyConnection,
+    /// In the order mirrors are tried
+    mirrors: Vec<Mirror>,
+    /// Present only if the Wayback Machine is searched
+    wayback: Option<Wayback>
+}
+
+/// A website mirroring the reports of the central bank
+struct Mirror {
+    sources: UrlSources,
+    connection: LazyConnection,
+    /// How many reports were downloaded from this mirror
+    served: usize
+}
+
+/// Connections to the Wayback Machine, each opened only once it is first needed
+struct Wayback {
+    availability: LazyConnection,
+    archive: LazyConnection
+}
+
+/// Where reports are found on the central bank website. Each template is relative to the
+/// website prefix and has {month}, {year}, and {ext} placeholders
+#[derive(Clone, Debug)]
+pub struct UrlSources {
+    website_prefix: String,
+    host: Host,
+    templates: Vec<String>,
+    /// The page listing available reports. Defaults to the website prefix as a directory
+    index_url: String
 }
 
 impl<'d> Download<'d> {
-    pub fn new(data_dir: &'d Path) -> Self {
+    pub fn new(data_dir: &'d Path, sources: UrlSources) -> Self {
         Self {
             data_dir,
-            total_hit_count: AtomicUsize::default()
+            sources,
+            total_hit_count: AtomicUsize::default(),
+            max_hits: None,
+            deadline: None,
+            force: false,
+            interrupted: Arc::default(),
+            prefer_ipv6: false,
+            offline: false,
+            use_index: false,
+            use_wayback: false,
+            wayback_hit_count: AtomicUsize::default(),
+            pattern_tally: DashMap::default(),
+            report_hook: None,
+            mirrors: Vec::new(),
+            mirror_usage: DashMap::default(),
+            finished_months: DashMap::default()
         }
     }
 
-    async fn download_year(&self, year: Year) -> Result<YearlyReport> {
+    /// Sets the mirrors to try in order, for each month the central bank website fails to
+    /// serve. Mirrors are searched by URL pattern, since the listing page is not theirs
+    pub fn mirrors(mut self, mirrors: Vec<UrlSources>) -> Self {
+        self.mirrors = mirrors;
+        self
+    }
+
+    /// Sets a callback to run as each report is downloaded, or found to exist already, such as
+    /// to parse it at once. Errors from the callback are logged without stopping the download
+    pub fn on_report<F, Fut>(mut self, hook: F) -> Self
+        where F: Fn(MonthlyReport, PathBuf) -> Fut + Send + Sync + 'd, Fut: Future<Output=Result<()>> + Send + 'd {
+        self.report_hook = Some(Box::new(move |report, path| Box::pin(hook(report, path))));
+        self
+    }
+
+    /// Sets whether to look for reports missing from the website in the Wayback Machine
+    pub fn use_wayback(mut self, use_wayback: bool) -> Self {
+        self.use_wayback = use_wayback;
+        self
+    }
+
+    /// Sets whether to fetch the listing page first, and download the reports it links to.
+    /// URL patterns are then tried only for months the page does not list
+    pub fn use_index(mut self, use_index: bool) -> Self {
+        self.use_index = use_index;
+        self
+    }
+
+    /// Sets whether to refuse any network access. Existing reports are still checked
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Sets whether to connect over IPv6 when the host has both IPv4 and IPv6 addresses
+    pub fn prefer_ipv6(mut self, prefer_ipv6: bool) -> Self {
+        self.prefer_ipv6 = prefer_ipv6;
+        self
+    }
+
+    /// Sets whether to re-download reports which exist already, replacing them
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Sets the most URLs to access on the central bank website before stopping
+    pub fn max_hits(mut self, max_hits: Option<usize>) -> Self {
+        self.max_hits = max_hits;
+        self
+    }
+
+    /// Sets how long the whole run may take, for fitting it into a fixed time slot
+    pub fn deadline(mut self, deadline: Option<Duration>) -> Self {
+        self.deadline = deadline;
+        self
+    }
 
-        let mut outcomes = HashMap::new();
+    /// Whether the hit budget is used up, such that no further months should be started
+    fn budget_spent(&self) -> bool {
+        self.max_hits.is_some_and(|max_hits| self.total_hit_count.load(Ordering::Acquire) >= max_hits)
+    }
+
+    async fn download_year(&self, year: Year, index: Option<&ReportIndex>) -> Result<YearlyReport> {
+
+        // Shared by every month, and opened only once a month needs downloading
+        let mut connections = Connections {
+            website: LazyConnection::new(self.sources.host.clone(), self.prefer_ipv6, self.offline),
+            mirrors: self.mirrors.iter().map(|sources| Mirror {
+                sources: sources.clone(),
+                connection: LazyConnection::new(sources.host.clone(), self.prefer_ipv6, self.offline),
+                served: 0
+            }).collect(),
+            wayback: if self.use_wayback {
+                Some(Wayback::new(self.prefer_ipv6, self.offline)?)
+            } else {
+                None
+            }
+        };
 
+        // Hits are counted after every month, so that parallel years see the budget shrink
+        let mut counted_hits = 0;
         for month in Month::values() {
 
+            if self.interrupted.load(Ordering::Acquire) || self.budget_spent() {
+                break;
+            }
             let report = MonthlyReport {
                 month, year
             };
-            let (status, hit_count) = report.download_if_possible(self.data_dir).await?;
-            outcomes.insert(month, status);
-            self.total_hit_count.fetch_add(hit_count, Ordering::AcqRel);
+            let status = report.download_if_possible(
+                self.data_dir, &self.sources, self.force, index, &mut connections, &self.pattern_tally
+            ).await?;
+            if let Some(hook) = &self.report_hook {
+                if let ReportStatus::ExistsPreviously(extension)
+                | ReportStatus::Downloaded(extension, _)
+                | ReportStatus::Refreshed(extension, _) = status {
+                    let path = self.data_dir.join(format!("{}{}", report.filename_prefix(), extension));
+                    if let Err(error) = hook(report, path).await {
+                        log::warn!("Post-download hook failed for {} {}: {}", month.name(), year, error);
+                    }
+                }
+            }
+            self.finished_months.entry(year).or_default().insert(month, status);
+
+            let hits = connections.website.hit_count();
+            self.total_hit_count.fetch_add(hits - counted_hits, Ordering::AcqRel);
+            counted_hits = hits;
+        }
+        for mirror in connections.mirrors {
+            let mut usage = self.mirror_usage.entry(mirror.sources.website_prefix.clone()).or_default();
+            usage.hit_count += mirror.connection.hit_count();
+            usage.served += mirror.served;
+        }
+        if let Some(wayback) = connections.wayback {
+            for connection in [wayback.availability.connection, wayback.archive.connection].into_iter().flatten() {
+                self.wayback_hit_count.fetch_add(connection.hit_count(), Ordering::AcqRel);
+            }
         }
+        let outcomes = self.finished_months.remove(&year).map(|(_year, outcomes)| outcomes).unwrap_or_default();
         Ok(YearlyReport { year, outcomes })
     }
 
+    /// Fetches the listing page once, to learn which reports exist and where
+    async fn fetch_index(&self) -> Result<ReportIndex> {
+        let index_url = self.sources.index_url();
+        let mut connection = Connection::open_connection(&self.sources.host, self.prefer_ipv6).await?;
+        let page = connection.fetch(index_url).await;
+        self.total_hit_count.fetch_add(connection.hit_count(), Ordering::AcqRel);
+
+        let page = page?.ok_or_else(|| eyre::eyre!("No listing page found at {}", index_url))?;
+        let index = ReportIndex::parse(index_url, &String::from_utf8_lossy(&page))?;
+        log::info!("Found {} reports listed at {}.", index.report_count(), index_url);
+        Ok(index)
+    }
+
     pub async fn download_all(&self) -> Result<()> {
-        // Parallelize per year
-        let mut yearly_reports = FuturesUnordered::new();
-        for year in 2013..=current_year() {
-            let year = Year(NonZeroU16::new(year).expect("Non-zero year"));
-            yearly_reports.push(self.download_year(year));
-        }
-        let mut total_downloads = 0;
-        while let Some(YearlyReport { year, outcomes }) = yearly_reports.next().await.transpose()? {
-            let download_count = outcomes
-                .iter()
-                .filter(|(_month, status)| {
-                    if let ReportStatus::Downloaded(_ext) = **status {
-                        true
-                    } else {
-                        false
-                    }
-                })
-                .count();
-            let missing_months = outcomes
-                .iter()
-                .filter_map(|(month, status)| {
-                    if let ReportStatus::Missing = status {
-                        Some(month)
-                    } else {
-                        None
-                    }
-                })
-                .map(Month::name)
-                .collect::<Vec<_>>();
-            if missing_months.is_empty() {
-                log::info!("Downloaded {} files for {}.", download_count, year);
-            } else {
-                let missing_months = missing_months.join(", ");
-                log::info!(
-                    "Downloaded {} files for {}. However, data is unavailable for months {}.",
-                    download_count, year, missing_months
-                );
+        let deadline = self.deadline.map(|deadline| Instant::now() + deadline);
+        let interrupted = self.interrupted.clone();
+        let handler_result = ctrlc::set_handler(move || {
+            if !interrupted.swap(true, Ordering::AcqRel) {
+                log::warn!("Interrupted. Finishing downloads in progress, then stopping...");
             }
-            total_downloads += download_count;
+        });
+        if let Err(error) = handler_result {
+            log::warn!("Unable to handle Ctrl-C; interrupting will not be graceful: {}", error);
         }
+        // The listing page cannot be fetched offline, but existing reports can still be checked
+        let index = if self.use_index && !self.offline {
+            Some(self.fetch_index().await?)
+        } else {
+            None
+        };
+        let start = Instant::now();
+        let years = (2013..=current_year()).map(|year| Year(NonZeroU16::new(year).expect("Non-zero year")));
+        let totals = self.download_years(years, index.as_ref(), deadline).await?;
         let total_hit_count = self.total_hit_count.load(Ordering::Acquire);
+        if self.interrupted.load(Ordering::Acquire) {
+            log::info!("Downloading was interrupted; some months were not attempted.");
+        }
+        if let Some(max_hits) = self.max_hits.filter(|_| self.budget_spent()) {
+            log::info!(
+                "Stopped after reaching the budget of {} URLs; some months were not attempted. \
+                 Run again later to continue.",
+                max_hits
+            );
+        }
         log::info!(
-            "Accessed {} URLs and downloaded {} files total from the central bank website.",
-            total_hit_count, total_downloads
+            "Accessed {} URLs, downloaded {} new files, and refreshed {} existing files total \
+             from the central bank website, over {} months.",
+            total_hit_count, totals.downloads, totals.refreshes, totals.months
+        );
+        if self.use_wayback {
+            log::info!(
+                "Accessed {} URLs on the Wayback Machine.", self.wayback_hit_count.load(Ordering::Acquire)
+            );
+        }
+        for line in self.summarize_patterns() {
+            log::info!("{}", line);
+        }
+        for line in self.summarize_mirrors() {
+            log::info!("{}", line);
+        }
+        const MEGABYTE: f64 = 1024.0 * 1024.0;
+        let elapsed = start.elapsed().as_secs_f64();
+        log::info!(
+            "Downloaded {:.2} MB in {:.0} seconds, averaging {:.2} MB/s.",
+            totals.bytes as f64 / MEGABYTE,
+            elapsed,
+            if elapsed > 0.0 { totals.bytes as f64 / MEGABYTE / elapsed } else { 0.0 }
         );
         Ok(())
     }
+
+    /// Downloads the years in parallel, until all are done or the deadline passes. The months
+    /// which years abandoned at the deadline had finished are tallied as well
+    async fn download_years(&self, years: impl IntoIterator<Item=Year>, index: Option<&ReportIndex>,
+                            deadline: Option<Instant>) -> Result<DownloadTotals> {
+
+        let mut yearly_reports = years
+            .into_iter()
+            .map(|year| self.download_year(year, index))
+            .collect::<FuturesUnordered<_>>();
+        let mut totals = DownloadTotals::default();
+        loop {
+            let Ok(next_year) = next_before(&mut yearly_reports, deadline).await else {
+                break;
+            };
+            let Some(YearlyReport { year, outcomes }) = next_year.transpose()? else {
+                return Ok(totals);
+            };
+            totals.add_year(year, &outcomes, false);
+        }
+        // Dropping the years in progress cancels their downloads. Partial files are left with
+        // a .part suffix, never mistaken for reports
+        let abandoned_years = yearly_reports.len();
+        drop(yearly_reports);
+        log::warn!(
+            "Stopped at the deadline of {} seconds. Months in progress for {} years were abandoned; \
+             run again later to continue.",
+            self.deadline.unwrap_or_default().as_secs(), abandoned_years
+        );
+        let mut abandoned = self.finished_months.iter().map(|entry| *entry.key()).collect::<Vec<_>>();
+        abandoned.sort();
+        for year in abandoned {
+            if let Some((year, outcomes)) = self.finished_months.remove(&year) {
+                totals.add_year(year, &outcomes, true);
+            }
+        }
+        Ok(totals)
+    }
+}
+
+impl DownloadTotals {
+    /// Counts the outcomes of the year's months, and logs what became of them. A year may be
+    /// abandoned at the deadline, with only some of its months finished
+    fn add_year(&mut self, year: Year, outcomes: &HashMap<Month, ReportStatus>, abandoned: bool) {
+        let download_count = outcomes
+            .iter()
+            .filter(|(_month, status)| matches!(status, ReportStatus::Downloaded(..)))
+            .count();
+        let refresh_count = outcomes
+            .iter()
+            .filter(|(_month, status)| matches!(status, ReportStatus::Refreshed(..)))
+            .count();
+        self.bytes += outcomes
+            .values()
+            .map(|status| match status {
+                ReportStatus::Downloaded(_ext, bytes)
+                | ReportStatus::Refreshed(_ext, bytes)
+                | ReportStatus::Corrupt(_ext, bytes) => *bytes,
+                _ => 0
+            })
+            .sum::<u64>();
+        for (month, status) in outcomes {
+            if let ReportStatus::Corrupt(extension, _bytes) = status {
+                log::warn!(
+                    "The {} file downloaded for {} {} is not a valid spreadsheet and was discarded.",
+                    extension, month.name(), year
+                );
+            }
+        }
+        let missing_months = outcomes
+            .iter()
+            .filter_map(|(month, status)| {
+                if let ReportStatus::Missing = status {
+                    Some(month)
+                } else {
+                    None
+                }
+            })
+            .map(Month::name)
+            .collect::<Vec<_>>();
+        let refreshed = if refresh_count > 0 {
+            format!(" and refreshed {}", refresh_count)
+        } else {
+            String::new()
+        };
+        let before_deadline = if abandoned { " before the deadline" } else { "" };
+        if missing_months.is_empty() {
+            log::info!("Downloaded {} new files{} for {}{}.", download_count, refreshed, year, before_deadline);
+        } else {
+            let missing_months = missing_months.join(", ");
+            log::info!(
+                "Downloaded {} new files{} for {}{}. However, data is unavailable for months {}.",
+                download_count, refreshed, year, before_deadline, missing_months
+            );
+        }
+        self.downloads += download_count;
+        self.refreshes += refresh_count;
+        self.months += outcomes.len();
+    }
+}
+
+/// Awaits the next item of the stream, unless the deadline passes first
+async fn next_before<S>(stream: &mut S, deadline: Option<Instant>) -> Result<Option<S::Item>, TimeoutError>
+    where S: Stream + Unpin {
+
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            async_std::future::timeout(remaining, stream.next()).await
+        }
+        None => Ok(stream.next().await)
+    }
+}
+
+impl Download<'_> {
+    /// Describes how many reports were found at each URL pattern, most used first
+    fn summarize_patterns(&self) -> Vec<String> {
+        let mut tally = self.pattern_tally
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect::<Vec<_>>();
+        tally.sort_by(|(pattern, count), (other_pattern, other_count)| {
+            other_count.cmp(count).then_with(|| pattern.template.cmp(&other_pattern.template))
+        });
+        tally.into_iter().map(|(pattern, count)| {
+            format!(
+                "Pattern {} ({}) with months like {} and years like {} was used {} times.",
+                pattern.template + 1, self.sources.templates[pattern.template],
+                pattern.month.example(), pattern.year.example(), count
+            )
+        }).collect()
+    }
+
+    /// Describes how many reports each mirror served, in the order mirrors are tried
+    fn summarize_mirrors(&self) -> Vec<String> {
+        self.mirrors.iter().filter_map(|sources| {
+            let usage = *self.mirror_usage.get(&sources.website_prefix)?;
+            Some(format!(
+                "Accessed {} URLs on the mirror {}, which served {} files.",
+                usage.hit_count, sources.website_prefix, usage.served
+            ))
+        }).collect()
+    }
+}
+
+impl MonthSpelling {
+    const ALL: [Self; 4] = [Self::Full, Self::Lowercase, Self::Abbreviated, Self::LowercaseAbbreviated];
+
+    /// Spells the month
+    fn spell(&self, month: Month) -> String {
+        let full = month.name();
+        match self {
+            Self::Full => full.to_string(),
+            Self::Lowercase => full.to_lowercase(),
+            Self::Abbreviated => full[0..3].to_string(),
+            Self::LowercaseAbbreviated => full[0..3].to_lowercase()
+        }
+    }
+
+    fn example(&self) -> String {
+        self.spell(Month::July)
+    }
+}
+
+impl YearSpelling {
+    const ALL: [Self; 2] = [Self::Full, Self::TwoDigit];
+
+    /// Spells the year
+    fn spell(&self, year: Year) -> String {
+        let full = year.to_string();
+        match self {
+            Self::Full => full,
+            Self::TwoDigit => full[2..].to_string()
+        }
+    }
+
+    fn example(&self) -> &'static str {
+        match self {
+            Self::Full => "2014",
+            Self::TwoDigit => "14"
+        }
+    }
+}
+
+/// Downloads urls to the destinations a handler chooses. Abstracts over the connection, so
+/// that the order in which urls are attempted can be tested without a server
+trait UrlDownloader {
+    async fn download<DH>(&mut self, url: String, handler: &DH) -> Result<Option<u64>> where DH: DownloadHandler;
+
+    /// Whether anything may exist at the url, asked without downloading it
+    async fn probe<DH>(&mut self, url: &str, handler: &DH) -> Result<bool> where DH: DownloadHandler;
+}
+
+impl UrlDownloader for Connection {
+    async fn download<DH>(&mut self, url: String, handler: &DH) -> Result<Option<u64>> where DH: DownloadHandler {
+        Connection::download(self, url, handler).await
+    }
+
+    async fn probe<DH>(&mut self, url: &str, handler: &DH) -> Result<bool> where DH: DownloadHandler {
+        Connection::probe(self, url, handler).await
+    }
+}
+
+/// Attempts a single url. A file which fails to download or be written is logged and kept,
+/// unless a failure was kept already, rather than returned, so that the caller may go on to
+/// the remaining urls. Other errors, such as unexpected statuses, are returned at once: a
+/// server throttling or blocking requests should not be sent the remaining urls
+async fn attempt_url<C, DH>(connection: &mut C, url: String, handler: &DH,
+                            failure: &mut Option<eyre::Report>) -> Result<Option<u64>>
+    where C: UrlDownloader, DH: DownloadHandler {
+
+    match connection.download(url.clone(), handler).await {
+        Ok(downloaded) => Ok(downloaded),
+        Err(error) if error.downcast_ref::<TransferFailed>().is_some() => {
+            log::warn!("Failed to download {}: {:#}. Trying the remaining urls", url, error);
+            failure.get_or_insert(error);
+            Ok(None)
+        }
+        Err(error) => Err(error)
+    }
 }
 
 struct YearlyReport {
@@ -118,87 +620,347 @@ struct YearlyReport {
 
 impl MonthlyReport {
 
-    async fn attempt_urls<DH>(&self, connection: &mut Connection<'_, DH>)
-        -> Result<ReportStatus> where DH: DownloadHandler {
+    /// The name of the report's file, up to its extension, e.g. 2014-7.
+    fn filename_prefix(&self) -> String {
+        format!("{}-{}.", self.year, self.month.as_numeric())
+    }
 
-        fn populate_urls(month: &str, year: &str, extension: SheetExtension) -> [String; 4] {
-            let prefix = WEBSITE_PREFIX;
-            [
-                format!("{}/et{}{}.{}", prefix, month, year, extension),
-                format!("{}/econtrends_{}{}.{}", prefix, month, year, extension),
-                format!("{}/ET{}{}.{}", prefix, month, year, extension),
-                format!("{}/{}{}/statisticaltable.{}", prefix, month, year, extension)
-            ]
-        }
+    /// Attempts the URLs the index lists for this report, if any. Otherwise, every URL
+    /// pattern is probed with a HEAD request, and downloaded only if something exists there.
+    /// Yields the pattern the report was found at, if any.
+    ///
+    /// A URL whose file fails to download or be written does not stop the others from being
+    /// attempted. If none has the report and any failed, the first failure is yielded, since
+    /// the report may exist. An unexpected status stops the attempts straight away
+    async fn attempt_urls<C, DH>(&self, sources: &UrlSources, index: Option<&ReportIndex>,
+                                 connection: &mut C, handler: &DH)
+        -> Result<(ReportStatus, Option<UrlPattern>)> where C: UrlDownloader, DH: DownloadHandler {
 
-        async fn attempt_urls_using<const M: usize, const Y: usize, DH>(months: [&str; M],
-                                                                        years: [&str; Y],
-                                                                        connection: &mut Connection<'_, DH>)
-            -> Result<ReportStatus> where DH: DownloadHandler {
+        async fn attempt_urls_using<const M: usize, const Y: usize, C, DH>(months: [(MonthSpelling, String); M],
+                                                                           years: [(YearSpelling, String); Y],
+                                                                           sources: &UrlSources,
+                                                                           connection: &mut C,
+                                                                           handler: &DH)
+            -> Result<(ReportStatus, Option<UrlPattern>)> where C: UrlDownloader, DH: DownloadHandler {
 
-            for month in months {
-                for year in years {
+            let mut failure = None;
+            for (month_spelling, month) in &months {
+                for (year_spelling, year) in &years {
                     for extension in XL_EXTENSIONS {
-                        for url in populate_urls(month, year, extension) {
-                            if connection.download(url).await? {
-                                return Ok(ReportStatus::Downloaded(extension));
+                        for (template, url) in sources.populate_urls(month, year, extension).enumerate() {
+                            // Most patterns have nothing, so they are probed before any download
+                            if !connection.probe(&url, handler).await? {
+                                continue;
+                            }
+                            if let Some(bytes) = attempt_url(connection, url, handler, &mut failure).await? {
+                                let pattern = UrlPattern { template, month: *month_spelling, year: *year_spelling };
+                                return Ok((ReportStatus::Downloaded(extension, bytes), Some(pattern)));
                             }
                         }
                     }
                 }
             }
-            Ok(ReportStatus::Missing)
+            failure.map_or(Ok((ReportStatus::Missing, None)), Err)
+        }
+        if let Some(indexed_urls) = index.and_then(|index| index.urls(self)) {
+            let mut failure = None;
+            for (url, extension) in indexed_urls {
+                if let Some(bytes) = attempt_url(connection, url.clone(), handler, &mut failure).await? {
+                    return Ok((ReportStatus::Downloaded(*extension, bytes), None));
+                }
+            }
+            return failure.map_or(Ok((ReportStatus::Missing, None)), Err);
         }
-        let month = self.month.name();
-        let lower_month = month.to_lowercase();
-        let short_month = &month[0..3];
-        let lower_short_month = &lower_month[0..3];
-
-        let year = self.year.to_string();
-        let short_year = &year[2..];
-
         attempt_urls_using(
-            [month, &lower_month, short_month, lower_short_month],
-            [&year, short_year],
-            connection
+            MonthSpelling::ALL.map(|spelling| (spelling, spelling.spell(self.month))),
+            YearSpelling::ALL.map(|spelling| (spelling, spelling.spell(self.year))),
+            sources,
+            connection,
+            handler
         ).await
     }
 
-    async fn download_if_possible(&self, data_dir: &Path) -> Result<(ReportStatus, usize)> {
-        let mut filename_prefix = format!("{}-{}.", self.year, self.month.as_numeric());
+    /// Searches the Wayback Machine for the report at its canonical urls
+    async fn attempt_wayback<DH>(&self, sources: &UrlSources, wayback: &mut Wayback, handler: &DH)
+        -> Result<ReportStatus> where DH: DownloadHandler {
+
+        for (url, extension) in sources.canonical_urls(self) {
+            let availability = wayback.availability.get().await?
+                .fetch(&wayback::availability_url(&url)).await?;
+            let Some(availability) = availability else {
+                continue;
+            };
+            let Some(snapshot) = wayback::snapshot_url(&url, &availability)? else {
+                continue;
+            };
+            if let Some(bytes) = wayback.archive.get().await?.download(snapshot, handler).await? {
+                log::info!("Recovered {} {} from the Wayback Machine.", self.month.name(), self.year);
+                return Ok(ReportStatus::Downloaded(extension, bytes));
+            }
+        }
+        Ok(ReportStatus::Missing)
+    }
+
+    /// Downloads the report unless it exists already. If forced, existing files are
+    /// set aside and replaced, or restored if no valid replacement is found
+    async fn download_if_possible(&self, data_dir: &Path, sources: &UrlSources, force: bool,
+                                  index: Option<&ReportIndex>, connections: &mut Connections,
+                                  pattern_tally: &DashMap<UrlPattern, usize>) -> Result<ReportStatus> {
+        let filename_prefix = self.filename_prefix();
+        let mut set_aside = Vec::new();
         for extension in XL_EXTENSIONS {
-            filename_prefix.push_str(extension.value());
-            if data_dir.join(&filename_prefix).exists().await {
-                return Ok((ReportStatus::ExistsPreviously(extension), 0));
+            let existing = data_dir.join(format!("{}{}", filename_prefix, extension));
+            if existing.exists().await {
+                if force {
+                    let mut backup = existing.as_os_str().to_os_string();
+                    backup.push(".old");
+                    let backup = PathBuf::from(backup);
+                    fs::rename(&existing, &backup).await?;
+                    set_aside.push((existing, backup));
+                    continue;
+                }
+                if extension.has_signature(&existing).await? {
+                    return Ok(ReportStatus::ExistsPreviously(extension));
+                }
+                log::warn!("Existing file {} is corrupt. Downloading it again", existing.to_string_lossy());
+                fs::remove_file(&existing).await?;
+            }
+        }
+        let download_outcome = self.download(
+            data_dir, sources, &filename_prefix, index, connections, pattern_tally
+        ).await;
+
+        let replaced = matches!(download_outcome, Ok(ReportStatus::Downloaded(..)));
+        for (existing, backup) in set_aside.iter() {
+            if replaced {
+                fs::remove_file(backup).await?;
+            } else {
+                fs::rename(backup, existing).await?;
             }
-            for _ in extension.value().chars() {
-                filename_prefix.pop();
+        }
+        match download_outcome? {
+            ReportStatus::Downloaded(extension, bytes) if !set_aside.is_empty() => {
+                Ok(ReportStatus::Refreshed(extension, bytes))
             }
+            other => Ok(other)
         }
-        // No existing files found; try URLs to download
+    }
+
+    async fn download(&self, data_dir: &Path, sources: &UrlSources, filename_prefix: &str,
+                      index: Option<&ReportIndex>, connections: &mut Connections,
+                      pattern_tally: &DashMap<UrlPattern, usize>) -> Result<ReportStatus> {
         let handler = Handler {
             data_dir,
-            filename_prefix: &filename_prefix,
+            filename_prefix,
         };
-        let website_prefix = WEBSITE_PREFIX.parse::<Uri>()?;
-        let host = website_prefix.host().expect("No host");
-        let mut connection = Connection::open_connection(&handler, host).await?;
-        let download_outcome = self.attempt_urls(&mut connection).await?;
-        let hit_count = connection.hit_count();
-        Ok((download_outcome, hit_count))
+        let primary = match connections.website.get().await {
+            Ok(connection) => self.attempt_urls(sources, index, connection, &handler).await,
+            Err(error) => Err(error)
+        };
+        // Failures of the central bank website are yielded only if no mirror has the report
+        let mut failure = None;
+        let (mut download_outcome, mut pattern) = match primary {
+            Ok(found) => found,
+            Err(error) if !connections.mirrors.is_empty() => {
+                log::warn!("Unable to download {} {}: {:#}. Trying mirrors", self.month.name(), self.year, error);
+                failure = Some(error);
+                (ReportStatus::Missing, None)
+            }
+            Err(error) => return Err(error)
+        };
+        for mirror in &mut connections.mirrors {
+            if !matches!(download_outcome, ReportStatus::Missing) {
+                break;
+            }
+            let attempt = match mirror.connection.get().await {
+                Ok(connection) => self.attempt_urls(&mirror.sources, None, connection, &handler).await,
+                Err(error) => Err(error)
+            };
+            match attempt {
+                Ok((status, found_pattern)) => {
+                    if let ReportStatus::Downloaded(..) = status {
+                        log::info!(
+                            "Downloaded {} {} from the mirror {}.", self.month.name(), self.year, mirror.sources.website_prefix
+                        );
+                        mirror.served += 1;
+                    }
+                    (download_outcome, pattern) = (status, found_pattern);
+                }
+                Err(error) => {
+                    log::warn!("Unable to download {} {} from the mirror {}: {:#}", self.month.name(), self.year, mirror.sources.website_prefix, error);
+                    failure.get_or_insert(error);
+                }
+            }
+        }
+        if let (ReportStatus::Missing, Some(wayback)) = (download_outcome, connections.wayback.as_mut()) {
+            // The archive failing should not stop the remaining downloads
+            download_outcome = self.attempt_wayback(sources, wayback, &handler).await.unwrap_or_else(|error| {
+                log::warn!("Unable to search the Wayback Machine for {} {}: {}", self.month.name(), self.year, error);
+                ReportStatus::Missing
+            });
+        }
+        if let (ReportStatus::Missing, Some(failure)) = (download_outcome, failure) {
+            return Err(failure);
+        }
+        if let ReportStatus::Downloaded(extension, bytes) = download_outcome {
+            let downloaded = data_dir.join(format!("{}{}", filename_prefix, extension));
+            if !extension.has_signature(&downloaded).await? {
+                fs::remove_file(&downloaded).await?;
+                download_outcome = ReportStatus::Corrupt(extension, bytes);
+            } else if let Some(pattern) = pattern {
+                *pattern_tally.entry(pattern).or_insert(0) += 1;
+            }
+        }
+        Ok(download_outcome)
+    }
+
+}
+
+impl LazyConnection {
+    fn new(host: Host, prefer_ipv6: bool, offline: bool) -> Self {
+        Self { host, prefer_ipv6, offline, connection: None }
     }
 
+    /// How many URLs were accessed, which is none if the connection was never opened
+    fn hit_count(&self) -> usize {
+        self.connection.as_ref().map_or(0, Connection::hit_count)
+    }
+
+    async fn get(&mut self) -> Result<&mut Connection> {
+        if self.offline {
+            return Err(eyre::eyre!("Refusing to connect to {} in offline mode", self.host));
+        }
+        Ok(match self.connection {
+            Some(ref mut connection) => connection,
+            None => self.connection.insert(Connection::open_connection(&self.host, self.prefer_ipv6).await?)
+        })
+    }
+}
+
+impl Wayback {
+    fn new(prefer_ipv6: bool, offline: bool) -> Result<Self> {
+        let host = |url: &str| -> Result<Host> {
+            let authority = url.parse::<Uri>()?.authority().expect("Wayback urls have hosts").clone();
+            Host::parse(authority.as_str())
+        };
+        Ok(Self {
+            availability: LazyConnection::new(host(wayback::AVAILABILITY_API)?, prefer_ipv6, offline),
+            archive: LazyConnection::new(host(wayback::ARCHIVE_PREFIX)?, prefer_ipv6, offline)
+        })
+    }
+}
+
+impl UrlSources {
+    /// Creates URL sources, checking that the prefix has a host and the templates have
+    /// every placeholder
+    pub fn new(website_prefix: &str, templates: Vec<String>) -> Result<Self> {
+        let website_prefix = website_prefix.trim_end_matches('/');
+        let authority = website_prefix
+            .parse::<Uri>()
+            .wrap_err_with(|| format!("Invalid website prefix {}", website_prefix))?
+            .authority()
+            .ok_or_else(|| eyre::eyre!("Website prefix {} has no host", website_prefix))?
+            .clone();
+        let host = Host::parse(authority.as_str())?;
+        if templates.is_empty() {
+            return Err(eyre::eyre!("No URL templates given"));
+        }
+        for template in &templates {
+            for placeholder in ["{month}", "{year}", "{ext}"] {
+                if !template.contains(placeholder) {
+                    return Err(eyre::eyre!("URL template {} lacks the {} placeholder", template, placeholder));
+                }
+            }
+            if !template.ends_with("{ext}") {
+                return Err(eyre::eyre!("URL template {} must end with the {{ext}} placeholder", template));
+            }
+        }
+        Ok(Self {
+            website_prefix: website_prefix.to_string(),
+            host,
+            templates,
+            index_url: format!("{}/", website_prefix)
+        })
+    }
+
+    /// The same URL templates on another website, such as a mirror
+    pub fn mirror(&self, website_prefix: &str) -> Result<Self> {
+        Self::new(website_prefix, self.templates.clone())
+    }
+
+    /// Sets the page listing available reports, which must be on the same host
+    pub fn with_index_url(mut self, index_url: &str) -> Result<Self> {
+        let authority = index_url
+            .parse::<Uri>()
+            .wrap_err_with(|| format!("Invalid index url {}", index_url))?
+            .authority()
+            .map(|authority| Host::parse(authority.as_str()))
+            .transpose()?;
+        if authority.as_ref() != Some(&self.host) {
+            return Err(eyre::eyre!("Index url {} must be on the host {}", index_url, self.host));
+        }
+        self.index_url = index_url.to_string();
+        Ok(self)
+    }
+
+    pub fn index_url(&self) -> &str {
+        &self.index_url
+    }
+
+    pub fn website_prefix(&self) -> &str {
+        &self.website_prefix
+    }
+
+    pub fn templates(&self) -> &[String] {
+        &self.templates
+    }
+
+    /// The urls a report is expected at, per the first template: the lowercase three-letter
+    /// month and four-digit year, with each extension
+    fn canonical_urls(&self, report: &MonthlyReport) -> Vec<(String, SheetExtension)> {
+        let month = report.month.name()[0..3].to_lowercase();
+        let year = report.year.to_string();
+        XL_EXTENSIONS
+            .into_iter()
+            .filter_map(|extension| {
+                self.populate_urls(&month, &year, extension).next().map(|url| (url, extension))
+            })
+            .collect()
+    }
+
+    fn populate_urls<'s>(&'s self, month: &'s str, year: &'s str,
+                         extension: SheetExtension) -> impl Iterator<Item=String> + 's {
+        self.templates.iter().map(move |template| {
+            let path = template
+                .replace("{month}", month)
+                .replace("{year}", year)
+                .replace("{ext}", extension.value());
+            format!("{}/{}", self.website_prefix, path)
+        })
+    }
+}
+
+impl Default for UrlSources {
+    fn default() -> Self {
+        Self::new(WEBSITE_PREFIX, URL_TEMPLATES.map(String::from).to_vec())
+            .expect("Default URL sources are valid")
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum ReportStatus {
     ExistsPreviously(SheetExtension),
-    Downloaded(SheetExtension),
+    /// Downloaded this many bytes
+    Downloaded(SheetExtension, u64),
+    /// Downloaded this many bytes, replacing an existing file
+    Refreshed(SheetExtension, u64),
+    /// Downloaded, but the file was not a spreadsheet of the expected format
+    Corrupt(SheetExtension, u64),
     Missing
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-enum SheetExtension {
+pub(crate) enum SheetExtension {
     Xlsx,
     Xls
 }
@@ -210,6 +972,28 @@ impl SheetExtension {
             Self::Xls => "xls"
         }
     }
+
+    /// The bytes every file of this format begins with. XLSX files are ZIP archives, whereas
+    /// XLS files are OLE compound documents
+    pub(crate) fn signature(&self) -> &'static [u8] {
+        match self {
+            Self::Xlsx => b"PK\x03\x04",
+            Self::Xls => b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1"
+        }
+    }
+
+    /// Checks whether the file begins with the signature of this format. The central bank
+    /// sometimes serves HTML error pages in place of spreadsheets
+    async fn has_signature(&self, file: &Path) -> Result<bool> {
+        let signature = self.signature();
+        let mut start = vec![0; signature.len()];
+        let mut file = fs::File::open(file).await?;
+        match file.read_exact(&mut start).await {
+            Ok(()) => Ok(start == signature),
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(error) => Err(error.into())
+        }
+    }
 }
 
 impl Display for SheetExtension {
@@ -227,7 +1011,7 @@ struct Handler<'h> {
 impl Handler<'_> {
     fn filename(&self, url: &str) -> Result<String> {
         for extension in XL_EXTENSIONS {
-            if url.ends_with(extension.value()) {
+            if url.to_ascii_lowercase().ends_with(extension.value()) {
                 return Ok(format!("{}{}", self.filename_prefix, extension));
             }
         }
@@ -240,5 +1024,348 @@ impl<'h> DownloadHandler for Handler<'h> {
         let filename = self.filename(url)?;
         Ok(self.data_dir.join(filename))
     }
+
+    /// Rejects the HTML error pages which the central bank serves in place of spreadsheets.
+    /// The signature of downloaded files is checked still, since not every page says it is HTML
+    fn accept(&self, _url: &str, response_headers: &HeaderMap) -> bool {
+        let content_type = response_headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        !content_type.trim_start().to_ascii_lowercase().starts_with("text/html")
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches;
+    use async_std::task;
+    use crate::http::UnexpectedStatus;
+    use crate::testing::TempDir;
+    use super::*;
+
+    #[test]
+    fn reject_html_responses() {
+        let handler = Handler { data_dir: Path::new("data"), filename_prefix: "2014-7." };
+        let headers = |content_type: Option<&'static str>| {
+            let mut headers = HeaderMap::new();
+            if let Some(content_type) = content_type {
+                headers.insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+            }
+            headers
+        };
+        let url = "https://www.bb.org.bd/econdata/et july2014.xlsx";
+        assert!(handler.accept(url, &headers(None)));
+        assert!(handler.accept(url, &headers(Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"))));
+        assert!(handler.accept(url, &headers(Some("application/octet-stream"))));
+        assert!(!handler.accept(url, &headers(Some("text/html; charset=UTF-8"))));
+        assert!(!handler.accept(url, &headers(Some("Text/HTML"))));
+    }
+
+    #[test]
+    fn run_hook_per_report() {
+        let temp_dir = TempDir::new("hook");
+        for month in 1..=12 {
+            std::fs::write(temp_dir.join(format!("2014-{}.xlsx", month)), b"PK\x03\x04 existing").unwrap();
+        }
+        let seen = std::sync::Mutex::new(Vec::new());
+        let data_dir = PathBuf::from(temp_dir.to_path_buf());
+        let download = Download::new(&data_dir, UrlSources::default())
+            .offline(true)
+            .on_report(|report, path| {
+                seen.lock().unwrap().push((report.month, path));
+                async move {
+                    // A failing hook does not stop the download
+                    if report.month == Month::March { Err(eyre::eyre!("Hook failed")) } else { Ok(()) }
+                }
+            });
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let report = task::block_on(download.download_year(year, None)).unwrap();
+        drop(download);
+
+        assert_eq!(12, report.outcomes.len());
+        let seen = seen.into_inner().unwrap();
+        assert_eq!(Month::values().to_vec(), seen.iter().map(|(month, _)| *month).collect::<Vec<_>>());
+        assert_eq!(data_dir.join("2014-3.xlsx"), seen[2].1);
+    }
+
+    /// Fails to download urls under broken/, is refused access under blocked/, and has reports
+    /// only under good/
+    #[derive(Default)]
+    struct FlakyConnection {
+        probed: Vec<String>,
+        attempted: Vec<String>
+    }
+
+    impl UrlDownloader for FlakyConnection {
+        async fn download<DH>(&mut self, url: String, _handler: &DH) -> Result<Option<u64>> where DH: DownloadHandler {
+            self.attempted.push(url.clone());
+            if url.contains("/broken/") {
+                Err(eyre::eyre!("Connection reset").wrap_err(TransferFailed { url }))
+            } else if url.contains("/blocked/") {
+                Err(UnexpectedStatus { url, status: hyper::StatusCode::FORBIDDEN }.into())
+            } else {
+                Ok(url.contains("/good/").then_some(1024))
+            }
+        }
+
+        async fn probe<DH>(&mut self, url: &str, _handler: &DH) -> Result<bool> where DH: DownloadHandler {
+            self.probed.push(url.to_string());
+            if url.contains("/blocked/") {
+                Err(UnexpectedStatus { url: url.to_string(), status: hyper::StatusCode::FORBIDDEN }.into())
+            } else {
+                Ok(url.contains("/broken/") || url.contains("/good/"))
+            }
+        }
+    }
+
+    #[test]
+    fn continue_after_failed_url() {
+        let report = MonthlyReport::new(Year(NonZeroU16::new(2014).unwrap()), Month::July);
+        let handler = Handler { data_dir: Path::new("data"), filename_prefix: "2014-7." };
+        let attempt = |templates: &[&str]| {
+            let sources = UrlSources::new("https://example.org", templates.iter().map(|t| t.to_string()).collect()).unwrap();
+            let mut connection = FlakyConnection::default();
+            let outcome = task::block_on(report.attempt_urls(&sources, None, &mut connection, &handler));
+            (outcome, connection.probed, connection.attempted)
+        };
+
+        let (outcome, probed, attempted) = attempt(&["broken/{month}{year}.{ext}", "good/{month}{year}.{ext}"]);
+        let (status, pattern) = outcome.unwrap();
+        assert_matches!(status, ReportStatus::Downloaded(SheetExtension::Xlsx, 1024));
+        assert_eq!(Some(1), pattern.map(|pattern| pattern.template));
+        assert_eq!(vec![
+            "https://example.org/broken/July2014.xlsx", "https://example.org/good/July2014.xlsx"
+        ], attempted);
+        assert_eq!(attempted, probed);
+
+        // Every url is probed before the failure is reported, but only those which exist are downloaded
+        let (outcome, probed, attempted) = attempt(&["broken/{month}{year}.{ext}", "missing/{month}{year}.{ext}"]);
+        assert!(outcome.is_err());
+        let url_count = 2 * MonthSpelling::ALL.len() * YearSpelling::ALL.len() * XL_EXTENSIONS.len();
+        assert_eq!(url_count, probed.len());
+        assert_eq!(url_count / 2, attempted.len());
+        assert!(attempted.iter().all(|url| url.contains("/broken/")));
+
+        // An unexpected status stops the attempts, lest a throttling server be sent the rest
+        let (outcome, probed, attempted) = attempt(&["blocked/{month}{year}.{ext}", "good/{month}{year}.{ext}"]);
+        assert_matches!(
+            outcome.unwrap_err().downcast_ref::<UnexpectedStatus>(),
+            Some(UnexpectedStatus { status: hyper::StatusCode::FORBIDDEN, .. })
+        );
+        assert_eq!(vec!["https://example.org/blocked/July2014.xlsx"], probed);
+        assert!(attempted.is_empty());
+    }
+
+    #[test]
+    fn interrupt_before_year() {
+        let data_dir = Path::new("nonexistent-data-dir");
+        let download = Download::new(data_dir, UrlSources::default());
+        download.interrupted.store(true, Ordering::Release);
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let report = task::block_on(download.download_year(year, None)).unwrap();
+        assert!(report.outcomes.is_empty());
+        assert_eq!(0, download.total_hit_count.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn stop_at_hit_budget() {
+        let data_dir = Path::new("nonexistent-data-dir");
+        let download = Download::new(data_dir, UrlSources::default()).max_hits(Some(40));
+        assert!(!download.budget_spent());
+
+        // Another year has used up the budget
+        download.total_hit_count.store(40, Ordering::Release);
+        assert!(download.budget_spent());
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let report = task::block_on(download.download_year(year, None)).unwrap();
+        assert!(report.outcomes.is_empty());
+        assert_eq!(40, download.total_hit_count.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn tally_year_abandoned_at_deadline() {
+        let temp_dir = TempDir::new("deadline");
+        for month in 1..=12 {
+            std::fs::write(temp_dir.join(format!("2014-{}.xlsx", month)), b"PK\x03\x04 existing").unwrap();
+        }
+        let data_dir = PathBuf::from(temp_dir.to_path_buf());
+        let download = Download::new(&data_dir, UrlSources::default())
+            .offline(true)
+            .on_report(|report, _path| async move {
+                // July holds up the year until long after the deadline
+                if report.month == Month::July {
+                    task::sleep(Duration::from_secs(60)).await;
+                }
+                Ok(())
+            });
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let deadline = Some(Instant::now() + Duration::from_millis(200));
+        let totals = task::block_on(download.download_years([year], None, deadline)).unwrap();
+        // January to June were finished before the year was abandoned
+        assert_eq!(DownloadTotals { months: 6, ..Default::default() }, totals);
+        assert!(download.finished_months.is_empty());
+    }
+
+    #[test]
+    fn stop_at_deadline() {
+        let mut ready = futures::stream::iter([1, 2]);
+        let passed = Some(Instant::now());
+        assert_eq!(Ok(Some(1)), task::block_on(next_before(&mut ready, None)));
+        // Items already available are taken even at the deadline
+        assert_eq!(Ok(Some(2)), task::block_on(next_before(&mut ready, passed)));
+        let mut pending = futures::stream::pending::<()>();
+        assert!(task::block_on(next_before(&mut pending, passed)).is_err());
+        let soon = Some(Instant::now() + Duration::from_millis(20));
+        assert!(task::block_on(next_before(&mut pending, soon)).is_err());
+    }
+
+    #[test]
+    fn restore_after_failed_refresh() {
+        let data_dir = TempDir::new("refresh");
+        let existing = data_dir.join("2014-7.xlsx");
+        std::fs::write(&existing, b"PK\x03\x04 original").unwrap();
+
+        // The local server hangs up on every connection, so the refresh fails
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || listener.incoming().for_each(drop));
+        let website = format!("https://127.0.0.1:{}", port);
+        let sources = UrlSources::new(&website, URL_TEMPLATES.map(String::from).to_vec()).unwrap();
+        let report = MonthlyReport::new(Year(NonZeroU16::new(2014).unwrap()), Month::July);
+        let outcome = task::block_on(
+            report.download_if_possible(Path::new(&*data_dir), &sources, true, None, &mut Connections {
+                website: LazyConnection::new(sources.host.clone(), false, false),
+                mirrors: Vec::new(),
+                wayback: None
+            }, &DashMap::default())
+        );
+        let restored = std::fs::read(&existing).unwrap();
+        let leftovers = std::fs::read_dir(&data_dir).unwrap().count();
+        assert!(outcome.is_err());
+        assert_eq!(b"PK\x03\x04 original".as_slice(), restored);
+        assert_eq!(1, leftovers);
+    }
+
+    #[test]
+    fn refuse_connections_offline() {
+        let data_dir = TempDir::new("offline");
+        std::fs::write(data_dir.join("2014-7.xlsx"), b"PK\x03\x04 existing").unwrap();
+
+        let sources = UrlSources::default();
+        let download = |month| {
+            let report = MonthlyReport::new(Year(NonZeroU16::new(2014).unwrap()), month);
+            let mut connections = Connections {
+                website: LazyConnection::new(sources.host.clone(), false, true),
+                mirrors: Vec::new(),
+                wayback: None
+            };
+            let outcome = task::block_on(
+                report.download_if_possible(Path::new(&*data_dir), &sources, false, None, &mut connections, &DashMap::default())
+            );
+            assert!(connections.website.connection.is_none());
+            outcome
+        };
+        let existing = download(Month::July);
+        let missing = download(Month::August);
+        assert_matches!(existing, Ok(ReportStatus::ExistsPreviously(SheetExtension::Xlsx)));
+        assert!(missing.unwrap_err().to_string().contains("offline mode"));
+    }
+
+    #[test]
+    fn summarize_mirrors() {
+        let sources = UrlSources::default();
+        let mirror = sources.mirror("https://mirror.example.org/econdata/").unwrap();
+        assert_eq!(sources.templates(), mirror.templates());
+        assert_eq!("https://mirror.example.org/econdata", mirror.website_prefix());
+        assert_eq!("mirror.example.org", mirror.host.name());
+
+        let unused = sources.mirror("https://unused.example.org").unwrap();
+        let download = Download::new(Path::new("nonexistent-data-dir"), sources).mirrors(vec![mirror, unused]);
+        assert!(download.summarize_mirrors().is_empty());
+        download.mirror_usage.insert(String::from("https://mirror.example.org/econdata"), MirrorUsage { hit_count: 40, served: 3 });
+        assert_eq!(
+            vec!["Accessed 40 URLs on the mirror https://mirror.example.org/econdata, which served 3 files."],
+            download.summarize_mirrors()
+        );
+    }
+
+    #[test]
+    fn summarize_patterns() {
+        let download = Download::new(Path::new("nonexistent-data-dir"), UrlSources::default());
+        assert!(download.summarize_patterns().is_empty());
+        download.pattern_tally.insert(UrlPattern { template: 0, month: MonthSpelling::Full, year: YearSpelling::Full }, 2);
+        download.pattern_tally.insert(UrlPattern { template: 3, month: MonthSpelling::LowercaseAbbreviated, year: YearSpelling::TwoDigit }, 5);
+        assert_eq!(vec![
+            "Pattern 4 ({month}{year}/statisticaltable.{ext}) with months like jul and years like 14 was used 5 times.",
+            "Pattern 1 (et{month}{year}.{ext}) with months like July and years like 2014 was used 2 times."
+        ], download.summarize_patterns());
+
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        assert_eq!(
+            vec!["September", "september", "Sep", "sep"],
+            MonthSpelling::ALL.map(|spelling| spelling.spell(Month::September)).to_vec()
+        );
+        assert_eq!(vec!["2009", "09"], YearSpelling::ALL.map(|spelling| spelling.spell(year)).to_vec());
+    }
+
+    #[test]
+    fn default_urls() {
+        let urls = UrlSources::default()
+            .populate_urls("July", "2014", SheetExtension::Xlsx)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![
+            "https://www.bb.org.bd/pub/monthly/econtrds/etJuly2014.xlsx",
+            "https://www.bb.org.bd/pub/monthly/econtrds/econtrends_July2014.xlsx",
+            "https://www.bb.org.bd/pub/monthly/econtrds/ETJuly2014.xlsx",
+            "https://www.bb.org.bd/pub/monthly/econtrds/July2014/statisticaltable.xlsx"
+        ], urls);
+    }
+
+    #[test]
+    fn custom_urls() {
+        let sources = UrlSources::new(
+            "https://example.org/reports/", vec![String::from("{year}/{month}.{ext}")]
+        ).unwrap();
+        assert_eq!("example.org", sources.host.name());
+        assert_eq!(
+            vec!["https://example.org/reports/2014/jul.xls"],
+            sources.populate_urls("jul", "2014", SheetExtension::Xls).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                (String::from("https://example.org/reports/2014/jul.xlsx"), SheetExtension::Xlsx),
+                (String::from("https://example.org/reports/2014/jul.xls"), SheetExtension::Xls)
+            ],
+            sources.canonical_urls(&MonthlyReport::new(Year(NonZeroU16::new(2014).unwrap()), Month::July))
+        );
+        assert_eq!("https://example.org/reports/", sources.index_url());
+        let sources = sources.with_index_url("https://example.org/listing.html").unwrap();
+        assert_eq!("https://example.org/listing.html", sources.index_url());
+        assert!(sources.clone().with_index_url("https://example.com/listing.html").is_err());
+        assert!(sources.with_index_url("/listing.html").is_err());
+        assert!(UrlSources::new("https://example.org", vec![String::from("{month}.{ext}")]).is_err());
+        assert!(UrlSources::new("not a url", vec![String::from("{month}{year}.{ext}")]).is_err());
+    }
+
+    #[test]
+    fn detect_signatures() {
+        let dir = TempDir::new("signatures");
+        let xlsx = dir.join("valid.xlsx");
+        let html = dir.join("error-page.xlsx");
+        let empty = dir.join("empty.xls");
+        std::fs::write(&xlsx, b"PK\x03\x04\x14\x00\x06\x00").unwrap();
+        std::fs::write(&html, b"<!DOCTYPE html><html>Not found</html>").unwrap();
+        std::fs::write(&empty, b"").unwrap();
+
+        let results = task::block_on(async {
+            [
+                SheetExtension::Xlsx.has_signature(Path::new(&xlsx)).await.unwrap(),
+                SheetExtension::Xlsx.has_signature(Path::new(&html)).await.unwrap(),
+                SheetExtension::Xls.has_signature(Path::new(&xlsx)).await.unwrap(),
+                SheetExtension::Xls.has_signature(Path::new(&empty)).await.unwrap()
+            ]
+        });
+        assert_eq!([true, false, false, false], results);
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Arrow IPC output, also known as Feather v2, for loading into dataframes without parsing
+// text. Enabled by the arrow feature
+
+use std::io::Write;
+use std::sync::Arc;
+use arrow::array::{ArrayRef, Float64Builder, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use eyre::Result;
+use crate::merge::{Column, RowKey, Sheet};
+use crate::output::OutputOptions;
+use crate::quality;
+use crate::quality::ColumnType;
+use crate::value::Value;
+
+/// Arranges a sheet as a single record batch, with rows in the order of their keys. The key
+/// comes first, then the data columns in order of their labels. Numeric columns are typed as
+/// such, while others are text. Missing cells and placeholders in numeric columns are null
+pub(crate) fn sheet_batch<K>(sheet: &Sheet<K>, options: &OutputOptions) -> Result<RecordBatch>
+    where K: RowKey {
+
+    let types = quality::column_types(sheet);
+    let mut columns = sheet.columns().collect::<Vec<_>>();
+    columns.sort_by_cached_key(Column::display_full_labeling);
+    let rows = sheet.keys_in_order()
+        .into_iter()
+        .filter_map(|key| Some((key.output_value(options), sheet.get(&key)?)))
+        .collect::<Vec<_>>();
+
+    let mut fields = vec![Field::new(K::HEADER, DataType::Utf8, false)];
+    let mut keys = StringBuilder::new();
+    for (key, _) in &rows {
+        keys.append_value(key);
+    }
+    let mut arrays: Vec<ArrayRef> = vec![Arc::new(keys.finish())];
+
+    for column in &columns {
+        let values = rows
+            .iter()
+            .map(|(_, data)| data.get(column).filter(|value| !value.is_empty()))
+            .collect::<Vec<_>>();
+        let numeric = match types.get(column) {
+            Some(ColumnType::Numeric) | None => true,
+            Some(ColumnType::Text) => false,
+            Some(ColumnType::Mixed) => {
+                log::warn!("Column {} mixes numbers and text, so it is written as text", options.display_column(column));
+                false
+            }
+        };
+        if numeric {
+            let mut builder = Float64Builder::with_capacity(values.len());
+            for value in values {
+                builder.append_option(match value {
+                    Some(Value::Number(number)) => Some(*number),
+                    _ => None
+                });
+            }
+            arrays.push(Arc::new(builder.finish()));
+        } else {
+            let mut builder = StringBuilder::new();
+            for value in values {
+                builder.append_option(value.map(Value::to_string));
+            }
+            arrays.push(Arc::new(builder.finish()));
+        }
+        let data_type = if numeric { DataType::Float64 } else { DataType::Utf8 };
+        fields.push(Field::new(options.display_column(column), data_type, true));
+    }
+    Ok(RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)?)
+}
+
+/// Writes a record batch as an Arrow IPC file
+pub(crate) fn write_batch<W>(writer: W, batch: &RecordBatch) -> Result<()> where W: Write {
+    let mut writer = FileWriter::try_new(writer, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::num::NonZeroU16;
+    use arrow::array::{Array, Float64Array, StringArray};
+    use arrow::ipc::reader::FileReader;
+    use crate::common::{Timestamp, Year};
+    use crate::merge::{ColumnLabel, RowData};
+    use super::*;
+
+    #[test]
+    fn feather_round_trip() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let sheet = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&column("Reserves"), "32.5");
+        row.populate(&column("Remarks"), "Estimated");
+        sheet.add_row(year(2021), row);
+        let mut row = RowData::default();
+        row.populate(&column("Remarks"), "12");
+        sheet.add_row(year(2020), row);
+
+        let mut file = Vec::new();
+        let batch = sheet_batch(&sheet, &OutputOptions::default()).unwrap();
+        write_batch(&mut file, &batch).unwrap();
+        let mut reader = FileReader::try_new(Cursor::new(file), None).unwrap();
+        let read = reader.next().unwrap().unwrap();
+        assert!(reader.next().is_none());
+        assert_eq!(batch.schema(), read.schema());
+
+        let schema = read.schema();
+        let types = schema.fields().iter().map(|field| (field.name().as_str(), field.data_type())).collect::<Vec<_>>();
+        assert_eq!(vec![
+            ("timestamp-primary-key", &DataType::Utf8), ("Remarks", &DataType::Utf8), ("Reserves", &DataType::Float64)
+        ], types);
+        let keys = read.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(vec![Some("2020"), Some("2021")], keys.iter().collect::<Vec<_>>());
+        // Text columns keep their numbers as written
+        let remarks = read.column(1).as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(vec![Some("12"), Some("Estimated")], remarks.iter().collect::<Vec<_>>());
+        let reserves = read.column(2).as_any().downcast_ref::<Float64Array>().unwrap();
+        assert!(reserves.is_null(0));
+        assert_eq!(32.5, reserves.value(1));
+    }
+}
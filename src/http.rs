@@ -17,122 +17,380 @@
  * and navigate to version 3 of the GNU General Public License.
  */
 
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
+use std::net::{Ipv6Addr, SocketAddr};
 use std::pin::Pin;
 use std::ptr;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll};
 use futures_io::{AsyncRead, AsyncWrite};
-use async_std::net::TcpStream;
+use async_std::net::{TcpStream, ToSocketAddrs};
 use async_std::path::{Path, PathBuf};
-use async_std::{io, task};
+use async_std::{fs, io, task};
 use async_std::fs::OpenOptions;
 use async_tls::TlsConnector;
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use rustls::client::ClientSessionMemoryCache;
 use http_body_util::{BodyExt, Empty};
 use hyper::body::{Bytes, Incoming};
 use hyper::client::conn::http1::SendRequest;
-use hyper::{header, Method, Request, Response, StatusCode, Uri};
-use eyre::Result;
+use hyper::{header, HeaderMap, Method, Request, Response, StatusCode, Uri, Version};
+use eyre::{Result, WrapErr};
 use futures::AsyncWriteExt;
 
 static TLS_CONNECTOR: OnceLock<TlsConnector> = OnceLock::new();
 
+/// Sessions remembered for resumption. Reports come from a single host, so few are needed
+const TLS_SESSION_CACHE_SIZE: usize = 32;
+
 pub trait DownloadHandler: Debug {
     fn destination_file(&self, url: &str) -> Result<PathBuf>;
+
+    /// Whether to download the body of a successful response, judging by its headers.
+    /// Rejected responses are treated as if nothing exists at the url
+    fn accept(&self, _url: &str, _response_headers: &HeaderMap) -> bool {
+        true
+    }
 }
 
-pub struct Connection<'dh, DH> {
-    handler: &'dh DH,
-    host: (Box<str>, u16),
+/// A connection to a host, which may be reused for many downloads. If the server declines
+/// to keep the connection alive, it is transparently reopened
+pub struct Connection {
+    host: Host,
+    prefer_ipv6: bool,
     sender: SendRequest<Empty<Bytes>>,
     hit_count: usize
 }
 
-impl<'dh, DH> Connection<'dh, DH> where DH: DownloadHandler {
-    pub async fn open_connection(handler: &'dh DH, host: &str) -> Result<Connection<'dh, DH>> {
-        let host = (Box::from(host), 443);
-        Self::open_connection_internal(handler, host, 0).await
+/// A found file which failed to download in full, or to be written. Another url may yet
+/// serve the same file
+#[derive(Debug)]
+pub struct TransferFailed {
+    pub url: String
+}
+
+/// A response with a status other than success, not found or a redirect. The server may be
+/// throttling or blocking requests, in which case more requests only make matters worse
+#[derive(Debug)]
+pub struct UnexpectedStatus {
+    pub url: String,
+    pub status: StatusCode
+}
+
+/// A host name or IP address, and port
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Host {
+    /// IPv6 addresses are kept without their brackets
+    name: Box<str>,
+    port: u16
+}
+
+impl Host {
+    /// Parses a host as written in a URL authority: a name or IPv4 address, or an IPv6 address
+    /// in brackets, optionally followed by a port. The port defaults to 443
+    pub fn parse(authority: &str) -> Result<Self> {
+        let (name, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (address, remainder) = bracketed.split_once(']')
+                .ok_or_else(|| eyre::eyre!("Unclosed bracket in host {}", authority))?;
+            address.parse::<Ipv6Addr>()
+                .wrap_err_with(|| format!("Invalid IPv6 address in host {}", authority))?;
+            let port = match remainder {
+                "" => None,
+                other => Some(other.strip_prefix(':')
+                    .ok_or_else(|| eyre::eyre!("Unexpected {} after IPv6 address in host {}", other, authority))?)
+            };
+            (address, port)
+        } else {
+            match authority.split_once(':') {
+                Some((_, port)) if port.contains(':') => {
+                    return Err(eyre::eyre!("IPv6 address {} must be in brackets", authority));
+                }
+                Some((name, port)) => (name, Some(port)),
+                None => (authority, None)
+            }
+        };
+        if name.is_empty() {
+            return Err(eyre::eyre!("No host name in {}", authority));
+        }
+        let port = match port {
+            Some(port) => port.parse().wrap_err_with(|| format!("Invalid port in host {}", authority))?,
+            None => 443
+        };
+        Ok(Self { name: Box::from(name), port })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    async fn open_connection_internal(handler: &'dh DH, (domain, port): (Box<str>, u16),
-                                      hit_count: usize) -> Result<Connection<'dh, DH>> {
-        let tls = TLS_CONNECTOR.get_or_init(|| TlsConnector::default());
+    /// Resolves the host to its addresses. IPv6 addresses come first if preferred, otherwise
+    /// the resolver's order is kept
+    async fn resolve(&self, prefer_ipv6: bool) -> Result<Vec<SocketAddr>> {
+        let mut addresses = (&self.name as &str, self.port).to_socket_addrs().await
+            .wrap_err_with(|| format!("While resolving {}", self))?
+            .collect::<Vec<_>>();
+        if prefer_ipv6 {
+            addresses.sort_by_key(|address| !address.is_ipv6());
+        }
+        Ok(addresses)
+    }
+}
+
+impl Display for Host {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.name.contains(':') {
+            write!(f, "[{}]:{}", self.name, self.port)
+        } else {
+            write!(f, "{}:{}", self.name, self.port)
+        }
+    }
+}
 
-        let stream = TcpStream::connect((&domain as &str, port)).await?;
-        let stream = StreamWrapper(tls.connect(&domain, stream).await?);
+impl Display for TransferFailed {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "While downloading {}", self.url)
+    }
+}
+
+impl Display for UnexpectedStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown status code {} for {}", self.status, self.url)
+    }
+}
+
+impl std::error::Error for UnexpectedStatus {}
+
+impl Connection {
+    /// Opens a connection to the host. When the host has several addresses, they are tried
+    /// in turn. TLS certificates are verified against the host name, so an IP address needs
+    /// a certificate issued for the address
+    pub async fn open_connection(host: &Host, prefer_ipv6: bool) -> Result<Connection> {
+        Self::open_connection_internal(host.clone(), prefer_ipv6, 0).await
+    }
+
+    async fn open_connection_internal(host: Host, prefer_ipv6: bool,
+                                      hit_count: usize) -> Result<Connection> {
+        let tls = TLS_CONNECTOR.get_or_init(|| TlsConnector::from(Arc::new(tls_config())));
+
+        let mut stream = Err(eyre::eyre!("No addresses found for {}", host));
+        for address in host.resolve(prefer_ipv6).await? {
+            stream = TcpStream::connect(address).await
+                .wrap_err_with(|| format!("While connecting to {} at {}", host, address));
+            if stream.is_ok() {
+                break;
+            }
+        }
+        let stream = StreamWrapper(tls.connect(host.name(), stream?).await?);
         let (sender, connection) = hyper::client::conn::http1::handshake(stream).await?;
 
-        log::debug!("Opened connection using {:?}", handler);
+        log::debug!("Opened connection to {}", host);
         task::spawn(async move {
             if let Err(e) = connection.await {
                 log::warn!("Error while polling HTTP connection: {}", e);
             }
         });
         Ok(Connection {
-            handler,
-            host: (domain, port),
+            host,
+            prefer_ipv6,
             sender,
             hit_count
         })
     }
 
-    pub async fn download(&mut self, url: String) -> Result<bool> {
-        log::debug!("Connecting to url {}", &url);
+    /// Downloads the url to the destination the handler chooses. Returns the number of bytes
+    /// downloaded, or none if nothing exists at the url.
+    ///
+    /// A body which fails to download or be written yields a [TransferFailed] error, and an
+    /// unexpected status an [UnexpectedStatus] error
+    pub async fn download<DH>(&mut self, url: String, handler: &DH) -> Result<Option<u64>> where DH: DownloadHandler {
+        let response = self.send_request(Method::GET, &url).await?;
+        // Determine whether we can keep re-using the existing connection
+        let refresh_connection = !keeps_alive(response.version(), response.headers());
+        let downloaded = match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::FOUND | StatusCode::MOVED_PERMANENTLY => {
+                // Drain the body so that the connection may be reused
+                response.into_body().collect().await?;
+                None
+            },
+            StatusCode::OK if !handler.accept(&url, response.headers()) => {
+                log::debug!("Rejected response from {}", url);
+                response.into_body().collect().await?;
+                None
+            },
+            StatusCode::OK => {
+                let destination = handler.destination_file(&url)?;
+                let bytes = match Self::complete_download(response, &destination).await {
+                    Ok(bytes) => bytes,
+                    Err(error) => {
+                        // The rest of the body is never read, so the connection is unusable. If it
+                        // cannot be reopened, no further url can be attempted over it
+                        if let Err(reopen_error) = self.reopen_if(true).await {
+                            log::warn!("Failed to download {}: {:#}", url, error);
+                            return Err(reopen_error.wrap_err(format!("Unable to reopen connection to {}", self.host)));
+                        }
+                        return Err(error.wrap_err(TransferFailed { url }));
+                    }
+                };
+                log::debug!("Downloaded {} bytes from {}", bytes, url);
+                Some(bytes)
+            },
+            status => {
+                // Drained as well, since the caller may go on to other urls
+                response.into_body().collect().await?;
+                self.reopen_if(refresh_connection).await?;
+                return Err(UnexpectedStatus { url, status }.into());
+            }
+        };
+        self.reopen_if(refresh_connection).await?;
+        Ok(downloaded)
+    }
+
+    /// Fetches the url into memory, meant for pages rather than spreadsheets. Returns none if
+    /// nothing exists at the url
+    pub async fn fetch(&mut self, url: &str) -> Result<Option<Bytes>> {
+        let response = self.send_request(Method::GET, url).await?;
+        let refresh_connection = !keeps_alive(response.version(), response.headers());
+        let status = response.status();
+        let body = response.into_body().collect().await?.to_bytes();
+        let fetched = match status {
+            StatusCode::NOT_FOUND | StatusCode::FOUND | StatusCode::MOVED_PERMANENTLY => None,
+            StatusCode::OK => {
+                log::debug!("Fetched {} bytes from {}", body.len(), url);
+                Some(body)
+            },
+            status => return Err(eyre::eyre!("Unknown status code: {}", status))
+        };
+        self.reopen_if(refresh_connection).await?;
+        Ok(fetched)
+    }
+
+    /// Asks whether anything exists at the url with a HEAD request, which spares downloading
+    /// the body. A successful response counts only if the handler would accept it. Servers
+    /// which do not support HEAD are assumed to have something, so that a GET may tell
+    pub async fn probe<DH>(&mut self, url: &str, handler: &DH) -> Result<bool> where DH: DownloadHandler {
+        let response = self.send_request(Method::HEAD, url).await?;
+        let refresh_connection = !keeps_alive(response.version(), response.headers());
+        let exists = match response.status() {
+            StatusCode::NOT_FOUND | StatusCode::FOUND | StatusCode::MOVED_PERMANENTLY => false,
+            StatusCode::OK => handler.accept(url, response.headers()),
+            StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED => true,
+            status => {
+                response.into_body().collect().await?;
+                self.reopen_if(refresh_connection).await?;
+                return Err(UnexpectedStatus { url: url.to_string(), status }.into());
+            }
+        };
+        // Responses to HEAD have no body, but may still need reading to the end
+        response.into_body().collect().await?;
+        self.reopen_if(refresh_connection).await?;
+        Ok(exists)
+    }
+
+    async fn send_request(&mut self, method: Method, url: &str) -> Result<Response<Incoming>> {
+        log::debug!("Connecting to url {}", url);
 
         let parsed_uri = url.parse::<Uri>()?;
         let authority = parsed_uri.authority().expect("No authority").clone();
 
         let request = Request::builder()
             .uri(parsed_uri)
-            .method(Method::GET)
+            .method(method)
             .header(header::HOST, authority.as_str())
             .body(Empty::<Bytes>::new())?;
 
         self.sender.ready().await?;
         self.hit_count += 1;
 
-        let response = self.sender.send_request(request).await?;
-        match response.status() {
-            StatusCode::NOT_FOUND | StatusCode::FOUND | StatusCode::MOVED_PERMANENTLY => Ok(false),
-            StatusCode::OK => {
-                let destination = self.handler.destination_file(&url)?;
-                self.complete_download(response, &destination).await?;
-                Ok(true)
-            },
-            status => Err(eyre::eyre!("Unknown status code: {}", status))
+        Ok(self.sender.send_request(request).await?)
+    }
+
+    /// Reopens the connection if the server declined to keep it alive
+    async fn reopen_if(&mut self, refresh_connection: bool) -> Result<()> {
+        if refresh_connection {
+            *self = Self::open_connection_internal(self.host.clone(), self.prefer_ipv6, self.hit_count).await?;
         }
+        Ok(())
     }
 
-    async fn complete_download(&mut self, mut response: Response<Incoming>, filename: &Path) -> Result<()> {
-        // Determine whether we can keep re-using the existing connection
-        let refresh_connection = {
-            match response.headers().get(header::CONNECTION).map(|header| header.as_bytes()) {
-                Some(b"Keep-Alive") | Some(b"keep-alive") => false,
-                _else => true
-            }
-        };
+    /// Writes the response body to a temporary file, renamed into place only once complete.
+    /// An interrupted download thus never leaves a truncated file behind
+    async fn complete_download(mut response: Response<Incoming>, filename: &Path) -> Result<u64> {
+        let mut partial_filename = filename.as_os_str().to_os_string();
+        partial_filename.push(".part");
+        let partial_filename = PathBuf::from(partial_filename);
+
+        // Overwrite leftovers from a previous interrupted run
         let file = OpenOptions::new()
-            .create_new(true)
+            .create(true)
+            .truncate(true)
             .write(true)
-            .open(filename).await?;
+            .open(&partial_filename).await?;
         let mut file = io::BufWriter::new(file);
-        while let Some(frame) = response.frame().await.transpose()? {
-            if let Some(next_chunk) = frame.data_ref() {
-                file.write_all(&next_chunk).await?;
+        let written = async {
+            let mut bytes = 0;
+            while let Some(frame) = response.frame().await.transpose()? {
+                if let Some(next_chunk) = frame.data_ref() {
+                    file.write_all(next_chunk).await?;
+                    bytes += next_chunk.len() as u64;
+                }
+            }
+            // BufWriter discards its buffer if dropped without flushing
+            file.flush().await?;
+            Ok::<_, eyre::Report>(bytes)
+        }.await;
+        drop(file);
+        match written {
+            Ok(bytes) => {
+                fs::rename(&partial_filename, filename).await?;
+                Ok(bytes)
+            }
+            Err(error) => {
+                // Nothing is gained from keeping a truncated file
+                let _ = fs::remove_file(&partial_filename).await;
+                Err(error)
             }
         }
-        if refresh_connection {
-            let host = std::mem::take(&mut self.host);
-            *self = Self::open_connection_internal(self.handler, host, self.hit_count).await?;
-        }
-        Ok(())
     }
 
-    pub fn hit_count(self) -> usize {
+    pub fn hit_count(&self) -> usize {
         self.hit_count
     }
 }
 
+/// The TLS configuration shared by every connection. Its session cache lets connections
+/// opened later in the run resume an earlier session, abbreviating their handshakes
+fn tls_config() -> ClientConfig {
+    let mut root_certs = RootCertStore::empty();
+    root_certs.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(anchor.subject, anchor.spki, anchor.name_constraints)
+    }));
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_certs)
+        .with_no_client_auth();
+    config.session_storage = ClientSessionMemoryCache::new(TLS_SESSION_CACHE_SIZE);
+    config.enable_tickets = true;
+    config
+}
+
+/// Whether the server lets the connection be reused after this response. HTTP/1.1 connections
+/// persist unless the server says to close them, but HTTP/1.0 connections must ask to persist
+fn keeps_alive(version: Version, headers: &HeaderMap) -> bool {
+    let has_option = |option: &str| {
+        headers
+            .get_all(header::CONNECTION)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|token| token.trim().eq_ignore_ascii_case(option))
+    };
+    if version == Version::HTTP_10 {
+        has_option("keep-alive")
+    } else {
+        !has_option("close")
+    }
+}
+
 struct StreamWrapper<IO>(IO);
 
 impl<IO> hyper::rt::Read for StreamWrapper<IO> where IO: AsyncRead + Unpin {
@@ -169,3 +427,58 @@ impl<IO> hyper::rt::Write for StreamWrapper<IO> where IO: AsyncWrite + Unpin {
         pinned_self.poll_close(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hyper::header::HeaderValue;
+    use super::*;
+
+    #[test]
+    fn keep_alive_semantics() {
+        fn headers(connection: Option<&'static str>) -> HeaderMap {
+            let mut headers = HeaderMap::new();
+            if let Some(connection) = connection {
+                headers.insert(header::CONNECTION, HeaderValue::from_static(connection));
+            }
+            headers
+        }
+        // HTTP/1.1 defaults to keep-alive when the header is absent
+        assert!(keeps_alive(Version::HTTP_11, &headers(None)));
+        assert!(keeps_alive(Version::HTTP_11, &headers(Some("Keep-Alive"))));
+        assert!(!keeps_alive(Version::HTTP_11, &headers(Some("close"))));
+        assert!(!keeps_alive(Version::HTTP_11, &headers(Some("Upgrade, Close"))));
+        // HTTP/1.0 defaults to closing
+        assert!(!keeps_alive(Version::HTTP_10, &headers(None)));
+        assert!(keeps_alive(Version::HTTP_10, &headers(Some("keep-alive"))));
+    }
+
+    #[test]
+    fn resumable_tls_sessions() {
+        let config = tls_config();
+        assert!(config.enable_tickets);
+        // Sessions stored by one connection are available to the next
+        assert!(config.session_storage.put(b"session".to_vec(), b"ticket".to_vec()));
+        assert_eq!(Some(b"ticket".to_vec()), config.session_storage.get(b"session"));
+    }
+
+    #[test]
+    fn parse_hosts() {
+        let host = |name: &str, port| Host { name: Box::from(name), port };
+        assert_eq!(host("example.org", 443), Host::parse("example.org").unwrap());
+        assert_eq!(host("example.org", 8443), Host::parse("example.org:8443").unwrap());
+        assert_eq!(host("192.0.2.1", 443), Host::parse("192.0.2.1").unwrap());
+        assert_eq!(host("2001:db8::1", 443), Host::parse("[2001:db8::1]").unwrap());
+        assert_eq!(host("::1", 8443), Host::parse("[::1]:8443").unwrap());
+        assert_eq!("[::1]:8443", Host::parse("[::1]:8443").unwrap().to_string());
+        for invalid in ["2001:db8::1", "[2001:db8::1", "[example.org]", "[::1]8443", "example.org:port", ":443"] {
+            assert!(Host::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn resolve_ipv6_literal() {
+        let host = Host::parse("[::1]:8443").unwrap();
+        let addresses = task::block_on(host.resolve(true)).unwrap();
+        assert_eq!(vec![SocketAddr::from((Ipv6Addr::LOCALHOST, 8443))], addresses);
+    }
+}
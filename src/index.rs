@@ -0,0 +1,209 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Discovery of reports from the listing page of the central bank website, so that only
+// files which exist are requested
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use eyre::{Result, WrapErr};
+use hyper::Uri;
+use regex::Regex;
+use crate::common::{Month, MonthlyReport, Year};
+use crate::download::SheetExtension;
+
+static ANCHOR_HREF: OnceLock<Regex> = OnceLock::new();
+static REPORT_FILENAME: OnceLock<Regex> = OnceLock::new();
+
+/// The spreadsheets linked from a listing page, by the report they hold
+#[derive(Clone, Debug, Default)]
+pub struct ReportIndex {
+    reports: HashMap<MonthlyReport, Vec<(String, SheetExtension)>>
+}
+
+impl ReportIndex {
+    /// Finds the spreadsheet links on a listing page. Relative links are resolved against
+    /// the page's url, and links to other hosts are ignored
+    pub fn parse(page_url: &str, html: &str) -> Result<Self> {
+        let page = page_url.parse::<Uri>()
+            .wrap_err_with(|| format!("Invalid listing page url {}", page_url))?;
+        let Some(origin) = origin(&page) else {
+            return Err(eyre::eyre!("Listing page url {} has no host", page_url));
+        };
+        let anchor_href = ANCHOR_HREF.get_or_init(||
+            Regex::new(r#"(?i)<a\s[^>]*?href\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#)
+                .expect("Regex compilation failure")
+        );
+        let mut reports = HashMap::<_, Vec<_>>::new();
+        for captures in anchor_href.captures_iter(html) {
+            let Some(href) = captures.get(1).or_else(|| captures.get(2)).or_else(|| captures.get(3)) else {
+                continue;
+            };
+            let href = href.as_str().trim().replace("&amp;", "&");
+            let Some(url) = resolve_href(&origin, page.path(), &href) else {
+                continue;
+            };
+            let Some((report, extension)) = report_from_url(&url) else {
+                continue;
+            };
+            let urls = reports.entry(report).or_default();
+            if !urls.iter().any(|(existing, _)| existing == &url) {
+                urls.push((url, extension));
+            }
+        }
+        // Prefer the newer format when both are listed
+        for urls in reports.values_mut() {
+            urls.sort_by_key(|(_, extension)| *extension == SheetExtension::Xls);
+        }
+        Ok(Self { reports })
+    }
+
+    /// How many distinct reports the listing page links to
+    pub fn report_count(&self) -> usize {
+        self.reports.len()
+    }
+
+    pub(crate) fn urls(&self, report: &MonthlyReport) -> Option<&[(String, SheetExtension)]> {
+        self.reports.get(report).map(Vec::as_slice)
+    }
+}
+
+/// The scheme and authority of a url, e.g. https://www.bb.org.bd
+fn origin(uri: &Uri) -> Option<String> {
+    Some(format!("{}://{}", uri.scheme_str()?, uri.authority()?))
+}
+
+/// Resolves a link found on a page, dropping its query and fragment. None if the link leads
+/// elsewhere than the page's origin
+fn resolve_href(origin: &str, page_path: &str, href: &str) -> Option<String> {
+    let href = href.split(['#', '?']).next().filter(|href| !href.is_empty())?;
+    let path = if href.contains("://") {
+        let linked = href.parse::<Uri>().ok()?;
+        if !self::origin(&linked)?.eq_ignore_ascii_case(origin) {
+            return None;
+        }
+        linked.path().to_string()
+    } else if let Some(scheme_relative) = href.strip_prefix("//") {
+        let scheme = origin.split_once("://")?.0;
+        return resolve_href(origin, page_path, &format!("{}://{}", scheme, scheme_relative));
+    } else if href.starts_with('/') {
+        href.to_string()
+    } else {
+        let directory = &page_path[..=page_path.rfind('/').unwrap_or(0)];
+        format!("{}{}", directory, href)
+    };
+    // Apply . and .. segments
+    let mut segments = Vec::new();
+    for segment in path.split('/').skip(1) {
+        match segment {
+            "." => {},
+            ".." => { segments.pop(); },
+            segment => segments.push(segment)
+        }
+    }
+    Some(format!("{}/{}", origin, segments.join("/")))
+}
+
+/// Recognizes the report a spreadsheet holds from its url. The month and year come just
+/// before the extension, or name the directory of a generically named file, as in
+/// etjan2014.xls or January2014/statisticaltable.xlsx
+fn report_from_url(url: &str) -> Option<(MonthlyReport, SheetExtension)> {
+    let report_filename = REPORT_FILENAME.get_or_init(||
+        Regex::new(r"(?i)([a-z]+)[_-]?(\d{4}|\d{2})(?:/[^/]*)?\.(xlsx|xls)$")
+            .expect("Regex compilation failure")
+    );
+    let captures = report_filename.captures(url)?;
+    // The month follows any prefix such as et or econtrends_, so try the longest ending first
+    let letters = &captures[1];
+    let month = (0..letters.len()).find_map(|start| Month::from_str(&letters[start..]).ok())?;
+    let year = match &captures[2] {
+        short_year if short_year.len() == 2 => Year::from_str(&format!("20{}", short_year)).ok()?,
+        year => Year::from_str(year).ok()?
+    };
+    let extension = if captures[3].eq_ignore_ascii_case("xlsx") {
+        SheetExtension::Xlsx
+    } else {
+        SheetExtension::Xls
+    };
+    Some((MonthlyReport::new(year, month), extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use super::*;
+
+    fn report(year: u16, month: Month) -> MonthlyReport {
+        MonthlyReport::new(Year(NonZeroU16::new(year).unwrap()), month)
+    }
+
+    #[test]
+    fn resolve_links() {
+        let origin = "https://www.bb.org.bd";
+        let page_path = "/pub/monthly/econtrds/index.php";
+        let resolve = |href| resolve_href(origin, page_path, href);
+        assert_eq!(Some("https://www.bb.org.bd/pub/monthly/econtrds/etjan2014.xls"), resolve("etjan2014.xls").as_deref());
+        assert_eq!(Some("https://www.bb.org.bd/pub/monthly/etjan2014.xls"), resolve("../etjan2014.xls").as_deref());
+        assert_eq!(Some("https://www.bb.org.bd/files/a.xlsx"), resolve("/files/./a.xlsx?download=1#top").as_deref());
+        assert_eq!(Some("https://www.bb.org.bd/a.xlsx"), resolve("//www.bb.org.bd/a.xlsx").as_deref());
+        assert_eq!(Some("https://www.bb.org.bd/a.xlsx"), resolve("https://WWW.bb.org.bd/a.xlsx").as_deref());
+        assert_eq!(None, resolve("https://example.org/a.xlsx"));
+        assert_eq!(None, resolve("#contents"));
+    }
+
+    #[test]
+    fn parse_listing() {
+        let html = r#"
+            <ul>
+              <li><a href="etjan2014.xls">January 2014</a></li>
+              <li><A class="file" HREF='econtrends_february2014.xlsx'>February 2014</A></li>
+              <li><a href="ETMar14.xls">March 2014</a> (<a href="ETMar14.xlsx">new format</a>)</li>
+              <li><a href=/pub/monthly/econtrds/June2015/statisticaltable.xlsx>June 2015</a></li>
+              <li><a href="https://example.org/etjul2014.xls">Mirror</a></li>
+              <li><a href="annual2014.pdf">Annual report</a></li>
+              <li><a href="et2014.xls">Unknown month</a></li>
+            </ul>
+        "#;
+        let index = ReportIndex::parse("https://www.bb.org.bd/pub/monthly/econtrds/", html).unwrap();
+        assert_eq!(4, index.report_count());
+        let urls = |year, month| index.urls(&report(year, month)).map(<[_]>::to_vec);
+        let prefix = "https://www.bb.org.bd/pub/monthly/econtrds";
+        assert_eq!(
+            Some(vec![(format!("{}/etjan2014.xls", prefix), SheetExtension::Xls)]),
+            urls(2014, Month::January)
+        );
+        assert_eq!(
+            Some(vec![(format!("{}/econtrends_february2014.xlsx", prefix), SheetExtension::Xlsx)]),
+            urls(2014, Month::February)
+        );
+        assert_eq!(
+            Some(vec![
+                (format!("{}/ETMar14.xlsx", prefix), SheetExtension::Xlsx),
+                (format!("{}/ETMar14.xls", prefix), SheetExtension::Xls)
+            ]),
+            urls(2014, Month::March)
+        );
+        assert_eq!(
+            Some(vec![(format!("{}/June2015/statisticaltable.xlsx", prefix), SheetExtension::Xlsx)]),
+            urls(2015, Month::June)
+        );
+        assert_eq!(None, urls(2014, Month::July));
+    }
+}
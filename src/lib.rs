@@ -0,0 +1,41 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+
+//! Downloads the central bank's monthly economic trends and condenses their sheets into
+//! one table per granularity. The command line program in main.rs drives these modules.
+
+pub mod download;
+pub mod merge;
+pub mod http;
+pub mod index;
+pub mod common;
+pub mod parse;
+pub mod quality;
+pub mod analysis;
+pub mod output;
+pub mod value;
+pub mod diff;
+pub mod transform;
+pub mod wayback;
+pub mod cache;
+#[cfg(feature = "arrow")]
+pub mod feather;
+#[cfg(test)]
+mod testing;
@@ -17,22 +17,27 @@
  * and navigate to version 3 of the GNU General Public License.
  */
 
-mod download;
-mod merge;
-mod http;
-mod common;
-mod parse;
-mod analysis;
+mod settings;
 
 use std::env;
 use std::ffi::OsString;
-use async_std::path::PathBuf;
-use log::LevelFilter;
-use simplelog::{ColorChoice, Config, TerminalMode, TermLogger};
+use std::num::NonZeroU16;
+use std::str::FromStr;
+use std::time::Duration;
+use async_std::path::{Path, PathBuf};
+use regex::Regex;
+use simplelog::{ColorChoice, CombinedLogger, Config, ConfigBuilder, SharedLogger, TerminalMode, TermLogger, WriteLogger};
 use async_std::{fs, fs::OpenOptions, io, io::WriteExt, task};
-use crate::download::Download;
-use crate::merge::MergeXL;
-use eyre::Result;
+use bank_data::cache::MergeCache;
+use bank_data::analysis::{AnalysisOptions, Orientation, ProvisionalPolicy, SheetPreview};
+use bank_data::download::{Download, UrlSources};
+use bank_data::common::{Granularity, Year};
+use bank_data::diff;
+use bank_data::transform::{self, Aggregation, FillMethod, Reducer};
+use bank_data::merge::{self, MergeXL};
+use bank_data::output;
+use bank_data::output::OutputOptions;
+use eyre::{Result, WrapErr};
 use futures::StreamExt;
 
 fn main() -> core::result::Result<(), eyre::Error> {
@@ -42,9 +47,18 @@ fn main() -> core::result::Result<(), eyre::Error> {
     }
     stable_eyre::install()?;
 
-    TermLogger::init(
-        LevelFilter::Info, Config::default(), TerminalMode::default(), ColorChoice::Auto
-    )?;
+    let level = settings::log_level()?;
+    let mut loggers: Vec<Box<dyn SharedLogger>> = vec![
+        TermLogger::new(level, Config::default(), TerminalMode::default(), ColorChoice::Auto)
+    ];
+    if let Some(log_file) = settings::setting("log-file") {
+        // Appended to, so that the log of several runs is kept. Dated, unlike the terminal
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(&log_file)
+            .wrap_err_with(|| format!("While opening log file {}", log_file))?;
+        let config = ConfigBuilder::new().set_time_format_rfc3339().build();
+        loggers.push(WriteLogger::new(level, config, file));
+    }
+    CombinedLogger::init(loggers)?;
     task::block_on(async_main())
 }
 
@@ -105,22 +119,92 @@ async fn async_main() -> Result<()> {
 
                      \n1. Download new
                      \n2. Condense existing
+                     \n4. Compare two condensed files
+                     \n5. List the sheets of each file
+                     \n6. Preview what condensing would extract, without writing
                      \nYour choice:").await?;
         match choice.as_str() {
             "1" => {
                 console.output(b"Downloading new datasets").await?;
-                let download = Download::new(&data_dir);
+                let sources = url_sources()?;
+                let mirrors = settings::setting("mirrors")
+                    .map(|mirrors| mirrors.split(',').map(|mirror| sources.mirror(mirror.trim())).collect::<Result<Vec<_>>>())
+                    .transpose()?
+                    .unwrap_or_default();
+                let download = Download::new(&data_dir, sources)
+                    .mirrors(mirrors)
+                    .force(settings::flag("force"))
+                    .max_hits(settings::parsed_setting("max-hits")?)
+                    .deadline(settings::parsed_setting("deadline-seconds")?.map(Duration::from_secs))
+                    .prefer_ipv6(settings::flag("prefer-ipv6"))
+                    .offline(settings::flag("offline"))
+                    .use_index(settings::flag("use-index"))
+                    .use_wayback(settings::flag("use-wayback"));
                 download.download_all().await?;
                 break
             }
             "2" => {
                 console.output(b"Merging existing datasets").await?;
-                let destination_prefix = OsString::from("./output");
-                let merge_xl = MergeXL::default();
-                merge_xl.load_all_from(&data_dir).await?;
-                merge_xl.write_to(&destination_prefix).await?;
+                let destination_prefix = settings::setting("output")
+                    .map(OsString::from)
+                    .unwrap_or_else(|| OsString::from("./output"));
+                let analysis_options = analysis_options()?;
+                let cache = match settings::setting("merge-cache") {
+                    Some(cache_dir) => Some(MergeCache::open(Path::new(&cache_dir), &analysis_options).await?),
+                    None => None
+                };
+                let merge_xl = MergeXL::new(analysis_options);
+                let load_report = match &cache {
+                    Some(cache) => merge_xl.load_all_cached(&data_dir, cache).await?,
+                    None => merge_xl.load_all_from(&data_dir).await?
+                };
+                if let Some(cache) = cache {
+                    cache.save().await?;
+                }
+                load_report.log_summary();
+                if let Some(report_path) = settings::setting("load-report") {
+                    load_report.write_json(Path::new(&report_path)).await?;
+                }
+                // Nothing is written, so that failures cannot go unnoticed
+                if settings::flag("strict") {
+                    load_report.ensure_strict()?;
+                }
+                if settings::flag("consolidate-footnotes") {
+                    for (granularity, sheet) in merge_xl.sheets().await {
+                        for (column, unmarked) in transform::consolidate_footnotes(&sheet) {
+                            log::info!(
+                                "Merged column {} into {} in the {} sheet",
+                                column.display_full_labeling(), unmarked.display_full_labeling(), granularity
+                            );
+                        }
+                    }
+                }
+                // After footnotes, so that marked columns are first merged into their unmarked casing
+                if settings::flag("consolidate-case") {
+                    for (granularity, sheet) in merge_xl.sheets().await {
+                        for (column, canonical) in transform::consolidate_case(&sheet) {
+                            log::info!(
+                                "Merged column {} into {} in the {} sheet",
+                                column.display_full_labeling(), canonical.display_full_labeling(), granularity
+                            );
+                        }
+                    }
+                }
+                let output_options = output_options()?;
+                merge_xl.write_to(&destination_prefix, &output_options).await?;
+                #[cfg(feature = "arrow")]
+                if settings::flag("feather") {
+                    merge_xl.write_feather(&destination_prefix, &output_options).await?;
+                }
+                if let Some(aggregation) = aggregation()? {
+                    merge_xl.write_aggregate(&destination_prefix, &aggregation, &output_options).await?;
+                }
+                if let Some(fill_method) = settings::parsed_setting::<FillMethod>("fill-gaps")? {
+                    merge_xl.write_filled(&destination_prefix, fill_method, &output_options).await?;
+                }
                 console.output(b"-- Critical reminders! --").await?;
                 console.output(b"Please note if you are using CPI data, there is sometimes a base year change in 2012-2013").await?;
+                console.output(b"Set price-base-cutover to split the CPI into old-base and new-base columns").await?;
                 break
             }
             "3" => {
@@ -131,6 +215,42 @@ async fn async_main() -> Result<()> {
                 ).as_bytes()).await?;
                 break;
             }
+            "4" => {
+                let previous = console.input(b"Previously condensed file:").await?;
+                let revised = console.input(b"Revised file of the same granularity:").await?;
+                let destination = settings::setting("changes").unwrap_or_else(|| String::from("./changes.csv"));
+                let change_count = compare_outputs(Path::new(&previous), Path::new(&revised), Path::new(&destination)).await?;
+                console.output(format!("Found {} changed cells, written to {}", change_count, destination).as_bytes()).await?;
+                break;
+            }
+            "5" => {
+                let inventory = merge::list_sheets(&data_dir, &analysis_options()?.exclusions).await?;
+                for (file, sheet_names) in &inventory {
+                    console.output(file.as_bytes()).await?;
+                    for sheet_name in sheet_names {
+                        console.output(format!("  {}", sheet_name).as_bytes()).await?;
+                    }
+                }
+                if let Some(destination) = settings::setting("sheet-list") {
+                    let file = fs::File::create(&destination).await?;
+                    merge::write_sheet_list(file, &inventory, &output_options()?).await?;
+                    console.output(format!("Sheet list written to {}", destination).as_bytes()).await?;
+                }
+                break;
+            }
+            "6" => {
+                for (file, sheets) in merge::preview_sheets(&data_dir, &analysis_options()?).await? {
+                    console.output(file.as_bytes()).await?;
+                    for (sheet_name, preview) in sheets {
+                        let summary = match preview {
+                            Ok(preview) => describe_preview(&preview),
+                            Err(error) => error.to_string()
+                        };
+                        console.output(format!("  {}: {}", sheet_name, summary).as_bytes()).await?;
+                    }
+                }
+                break;
+            }
             _ => {
                 console.output(b"Invalid answer. Try again.").await?;
             }
@@ -140,6 +260,175 @@ async fn async_main() -> Result<()> {
     Ok(())
 }
 
+/// Describes what analysis found in a sheet, e.g. "4 rows under 2 columns labeled in rows 0-1,
+/// starting 2022 at (2, 0)"
+fn describe_preview(preview: &SheetPreview) -> String {
+    let mut description = format!("{} rows under {} columns", preview.rows, preview.columns);
+    match &preview.label_rows {
+        Some(label_rows) => description.push_str(&format!(" labeled in rows {}-{}", label_rows.start, label_rows.end.saturating_sub(1))),
+        None => description.push_str(" named by position")
+    }
+    let (row, col) = preview.data_start;
+    match preview.start {
+        Some(start) => description.push_str(&format!(", starting {} at ({}, {})", start, row, col)),
+        None => description.push_str(&format!(", starting at ({}, {})", row, col))
+    }
+    description
+}
+
+/// Writes the cell-level differences between two condensed files. Yields the number of changes
+async fn compare_outputs(previous: &Path, revised: &Path, destination: &Path) -> Result<usize> {
+    let Some(granularity) = merge::output_file_granularity(previous)
+        .filter(|granularity| Some(*granularity) == merge::output_file_granularity(revised)) else {
+        return Err(eyre::eyre!("Both files must be condensed files of the same granularity"));
+    };
+    let options = output_options()?;
+    let mut sheets = Vec::with_capacity(2);
+    for path in [previous, revised] {
+        let merge_xl = MergeXL::default();
+        merge_xl.load_csv(path, &options.label_separator).await?;
+        sheets.push(merge_xl.sheet(granularity).await.unwrap_or_default());
+    }
+    let changes = diff::compare(&sheets[0], &sheets[1]);
+    let file = fs::File::create(destination).await?;
+    diff::write_changes(file, &changes, &options).await?;
+    Ok(changes.len())
+}
+
+/// Which longer periods to aggregate monthly data into, if any, and how
+fn aggregation() -> Result<Option<Aggregation>> {
+    let Some(target) = settings::setting("aggregate") else {
+        return Ok(None);
+    };
+    let target = Granularity::from_str(&target)
+        .map_err(|_| eyre::eyre!("Unknown granularity {} to aggregate into", target))?;
+    let reducer = settings::parsed_setting::<Reducer>("aggregate-reducer")?.unwrap_or_default();
+    Ok(Some(Aggregation::new(target, reducer)))
+}
+
+/// Analysis options from the command line and environment
+fn analysis_options() -> Result<AnalysisOptions> {
+    let mut options = AnalysisOptions::default();
+    options.values.normalize_units = settings::flag("normalize-units");
+    options.values.percent_as_fraction = settings::flag("percent-as-fraction");
+    if let Some(min_row_fill) = settings::parsed_setting::<f32>("min-row-fill")? {
+        if !(0.0..=1.0).contains(&min_row_fill) {
+            return Err(eyre::eyre!("min-row-fill must be between 0 and 1, but was {}", min_row_fill));
+        }
+        options.min_row_fill = min_row_fill;
+    }
+    if let Some(max_empty_columns) = settings::parsed_setting("max-empty-columns")? {
+        options.max_empty_columns = max_empty_columns;
+    }
+    options.positional_labels = settings::flag("positional-labels");
+    options.cross_sectional = settings::flag("cross-sectional");
+    if let Some(excluded_sheets) = settings::setting("excluded-sheets") {
+        options.exclusions.names = excluded_sheets.split(',').map(|name| name.trim().to_string()).collect();
+    }
+    if let Some(pattern) = settings::setting("excluded-sheet-pattern") {
+        options.exclusions.pattern = Some(Regex::new(&pattern)?);
+    }
+    options.exclusions.appendices = !settings::flag("include-appendices");
+    for (setting, orientation) in [("vertical-sheets", Orientation::Vertical), ("horizontal-sheets", Orientation::Horizontal)] {
+        if let Some(sheets) = settings::setting(setting) {
+            options.orientations.extend(sheets.split(',').map(|name| (name.trim().to_string(), orientation)));
+        }
+    }
+    // Entries are sheet=column, where the column is a header such as Period or 2021, or a
+    // column number after # such as #2
+    if let Some(timestamp_columns) = settings::setting("timestamp-columns") {
+        for entry in timestamp_columns.split(',') {
+            let Some((sheet, column)) = entry.rsplit_once('=') else {
+                return Err(eyre::eyre!("Invalid timestamp column {}. Use sheet=header or sheet=#number", entry));
+            };
+            options.timestamp_columns.push((sheet.trim().to_string(), column.parse()?));
+        }
+    }
+    if let Some(earliest_year) = settings::parsed_setting("earliest-year")? {
+        options.years.earliest = earliest_year;
+    }
+    if let Some(future_years) = settings::parsed_setting("future-years")? {
+        options.years.future_tolerance = future_years;
+    }
+    if let Some(pivot) = settings::parsed_setting::<u8>("two-digit-year-pivot")? {
+        if pivot >= 100 {
+            return Err(eyre::eyre!("two-digit-year-pivot must be a two-digit year, but was {}", pivot));
+        }
+        options.years.two_digit_pivot = Some(pivot);
+    }
+    if let Some(provisional) = settings::parsed_setting("provisional")? {
+        options.provisional = provisional;
+    }
+    if let Some(conflicts) = settings::parsed_setting("conflicts")? {
+        options.conflicts = conflicts;
+    }
+    if let Some(cutover) = settings::parsed_setting::<u16>("price-base-cutover")? {
+        let cutover = NonZeroU16::new(cutover).ok_or_else(|| eyre::eyre!("The price base cutover must be a year"))?;
+        options.price_base_cutover = Some(Year(cutover));
+    }
+    Ok(options)
+}
+
+/// Where to find reports, from the command line and environment. Templates are comma-separated
+fn url_sources() -> Result<UrlSources> {
+    let website_prefix = settings::setting("website-prefix");
+    let templates = settings::setting("url-templates");
+    let defaults = UrlSources::default();
+    let sources = UrlSources::new(
+        website_prefix.as_deref().unwrap_or(defaults.website_prefix()),
+        match templates {
+            Some(templates) => templates.split(',').map(|template| template.trim().to_string()).collect(),
+            None => defaults.templates().to_vec()
+        }
+    )?;
+    match settings::setting("index-url") {
+        Some(index_url) => sources.with_index_url(&index_url),
+        None => Ok(sources)
+    }
+}
+
+/// Output options from the command line and environment
+fn output_options() -> Result<OutputOptions> {
+    let mut options = OutputOptions::default();
+    if let Some(format) = settings::parsed_setting("output-format")? {
+        options.format = format;
+    }
+    if let Some(delimiter) = settings::setting("delimiter") {
+        options.delimiter = output::parse_delimiter(&delimiter)?;
+    }
+    if let Some(quoting) = settings::parsed_setting("quoting")? {
+        options.quoting = quoting;
+    }
+    options.byte_order_mark = settings::flag("byte-order-mark");
+    options.manifest = settings::flag("manifest");
+    options.coverage_report = settings::flag("coverage-report");
+    options.iso_date_column = settings::flag("iso-date-column");
+    options.combined = settings::flag("combined-output");
+    options.split_by_category = settings::flag("split-by-category");
+    options.provisional_column = settings::parsed_setting("provisional")? == Some(ProvisionalPolicy::Flag);
+    options.revised_column = settings::flag("revised-column");
+    options.sum_check_tolerance = settings::parsed_setting("check-quarterly-sums")?;
+    if let Some(fiscal_years) = settings::parsed_setting("fiscal-year-format")? {
+        options.fiscal_years = fiscal_years;
+    }
+    if let Some(months) = settings::parsed_setting("monthly-format")? {
+        options.months = months;
+    }
+    if let Some(periods) = settings::parsed_setting("period-format")? {
+        options.periods = periods;
+    }
+    if let Some(label_separator) = settings::setting("label-separator") {
+        if label_separator.is_empty() {
+            return Err(eyre::eyre!("The label separator cannot be empty"));
+        }
+        options.label_separator = label_separator;
+    }
+    if let Some(labeling) = settings::parsed_setting("column-labeling")? {
+        options.labeling = labeling;
+    }
+    Ok(options)
+}
+
 async fn count_csv_data() -> Result<(usize, usize)> {
     let mut files = fs::read_dir(PathBuf::from(".")).await?;
     let mut column_count = 0;
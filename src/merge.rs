@@ -17,104 +17,475 @@
  * and navigate to version 3 of the GNU General Public License.
  */
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fmt::{Debug, Display, Formatter, Write};
-use std::mem;
+use std::hash::Hash;
+use std::ops::Deref;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 use arc_interner::ArcIntern;
 use async_std::{fs, task};
 use async_std::fs::{DirEntry, OpenOptions};
+use async_std::io::WriteExt;
 use async_std::path::{Path, PathBuf};
 use dashmap::{DashMap, DashSet};
+use dashmap::mapref::entry::Entry;
 use eyre::{Result, WrapErr};
 use futures::stream::FuturesUnordered;
+use futures_io::AsyncWrite;
 use async_std::stream::StreamExt;
 use async_std::sync::RwLock;
 use calamine::{DataType, Range, Reader};
 use smallvec::SmallVec;
-use crate::analysis::{AnalysisError, AnalysisResult, SheetAnalyzer};
+use serde::{Deserialize, Serialize};
+use crate::cache::{MergeCache, Recorder};
+use crate::analysis::{AnalysisError, AnalysisErrorKind, AnalysisOptions, AnalysisResult, SheetAnalyzer, SheetExclusions, SheetPreview};
 use crate::common::*;
+use crate::download::SheetExtension;
+use crate::output;
+use crate::output::{OutputFormat, OutputOptions};
+use crate::transform;
+use crate::transform::{Aggregation, FillMethod};
+use crate::value::{Unit, Value};
 
 #[derive(Default)]
 pub struct MergeXL {
-    sheets: RwLock<HashMap<mem::Discriminant<Timestamp>, Arc<Sheet>>>
+    /// Ordered so that sheets are written and reported from the longest granularity to
+    /// the shortest, the same on every run
+    sheets: RwLock<BTreeMap<Granularity, Arc<Sheet>>>,
+    /// Sheets keyed by institution, by the kind of cross-section they hold
+    cross_sections: RwLock<BTreeMap<&'static str, Arc<Sheet<Institution>>>>,
+    options: AnalysisOptions
 }
 
+/// The outcome of loading a single file from the data directory
 #[derive(Debug, Eq, PartialEq)]
-enum FileStatus {
+pub enum FileStatus {
     HiddenFile,
     UnknownExtension,
     XlsUnsupported(PathBuf),
-    Merged(usize, Option<FileErrorReport>)
+    PasswordProtected(PathBuf),
+    Merged(FileReport)
 }
 
+/// The sheets of a file which were merged, and those which could not be
 #[derive(Debug, Eq, PartialEq)]
-pub struct FileErrorReport {
+pub struct FileReport {
     path: PathBuf,
-    errors: Vec<String>
+    merged: Vec<SheetRows>,
+    errors: Vec<SheetError>
 }
 
-impl MergeXL {
-    /// Writes the data in memory to the given destination
-    pub async fn write_to(self, destination: &OsStr) -> Result<()> {
-        let mut tasks = FuturesUnordered::new();
-        for (identifier, sheet) in self.sheets.into_inner() {
-            tasks.push(async move {
-
-                let mut destination = destination.to_os_string();
-                destination.push(&format!("-timestamp-{:?}.csv", identifier));
-                log::info!("Writing to output file {}", destination.to_string_lossy());
-                let destination = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .open(destination).await?;
-                let mut writer = csv_async::AsyncWriter::from_writer(destination);
-                if let Some(sheet) = Arc::into_inner(sheet) {
-
-                    let columns = sheet.columns.into_iter().collect::<Vec<_>>();
-                    let record_length = columns.len() + 1;
-                    // Write the header
-                    let mut header = Vec::with_capacity(record_length);
-                    header.push(String::from("timestamp-primary-key"));
-                    for column in &columns {
-                        header.push(column.display_full_labeling());
-                    }
-                    writer.write_record(&header).await?;
-
-                    // Write all the data
-                    for (timestamp, data) in sheet.rows {
-                        let mut record = Vec::<&str>::with_capacity(record_length);
-
-                        // Timestamp comes first
-                        let timestamp = timestamp.to_string();
-                        record.push(&timestamp);
-                        // Then the regular data columns
-                        for column in &columns {
-                            let item = if let Some(item) = data.data.get(column) {
-                                item.as_ref()
-                            } else {
-                                "NA"
-                            };
-                            record.push(item);
-                        }
-                        writer.write_record(record).await?;
+impl FileStatus {
+    /// Whether the file was merged without any of its sheets failing
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Merged(report) if report.errors.is_empty())
+    }
+
+    /// The number of rows merged from all the sheets of the file
+    pub fn rows_added(&self) -> usize {
+        match self {
+            Self::Merged(report) => report.merged.iter().map(SheetRows::rows).sum(),
+            _ => 0
+        }
+    }
+
+    /// The sheets of the file which could not be merged
+    pub fn errors(&self) -> &[SheetError] {
+        match self {
+            Self::Merged(report) => report.errors(),
+            _ => &[]
+        }
+    }
+}
+
+impl FileReport {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The sheets which were merged, in the order they appear in the file
+    pub fn merged(&self) -> &[SheetRows] {
+        &self.merged
+    }
+
+    pub fn errors(&self) -> &[SheetError] {
+        &self.errors
+    }
+}
+
+/// A sheet which was merged, and how many rows it contributed
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SheetRows {
+    sheet: String,
+    rows: usize
+}
+
+impl SheetRows {
+    pub fn sheet(&self) -> &str {
+        &self.sheet
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+}
+
+/// A sheet which could not be merged, and why
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SheetError {
+    sheet: String,
+    kind: AnalysisErrorKind,
+    reason: String
+}
+
+impl SheetError {
+    pub fn sheet(&self) -> &str {
+        &self.sheet
+    }
+
+    pub fn kind(&self) -> AnalysisErrorKind {
+        self.kind
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl Display for SheetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.sheet)?;
+        self.kind.describe(&self.reason, f)
+    }
+}
+
+/// The outcome of loading a data directory, in a form suited for tooling
+#[derive(Debug, Default, Serialize)]
+pub struct LoadReport {
+    loaded_files: usize,
+    loaded_sheets: usize,
+    /// Every merged sheet, with the number of rows it contributed
+    merged_sheets: Vec<LoadedSheet>,
+    /// Files skipped because the XLS format is unsupported
+    xls_unsupported: Vec<String>,
+    /// Files skipped because they are encrypted, which must be unlocked by hand
+    password_protected: Vec<String>,
+    /// How many sheets failed with each kind of error
+    error_counts: ErrorCounts,
+    /// How many rows merged into each output sheet were redundant, by the sheet's name
+    redundant_rows: BTreeMap<String, usize>,
+    /// Files whose rows were reused from the merge cache rather than parsed again
+    cached_files: usize,
+    errors: Vec<LoadError>
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+struct ErrorCounts {
+    unsupported: usize,
+    no_data: usize,
+    other_failure: usize
+}
+
+impl ErrorCounts {
+    fn count_mut(&mut self, kind: AnalysisErrorKind) -> &mut usize {
+        match kind {
+            AnalysisErrorKind::Unsupported => &mut self.unsupported,
+            AnalysisErrorKind::NoData => &mut self.no_data,
+            AnalysisErrorKind::OtherFailure => &mut self.other_failure
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LoadedSheet {
+    file: String,
+    #[serde(flatten)]
+    sheet: SheetRows
+}
+
+#[derive(Debug, Serialize)]
+struct LoadError {
+    file: String,
+    #[serde(flatten)]
+    error: SheetError
+}
+
+impl LoadReport {
+    fn from_statuses(file_statuses: Vec<FileStatus>) -> Self {
+        let mut report = Self::default();
+        for status in file_statuses {
+            match status {
+                FileStatus::HiddenFile | FileStatus::UnknownExtension => {}
+                FileStatus::XlsUnsupported(path) => {
+                    report.xls_unsupported.push(path.to_string_lossy().into_owned());
+                }
+                FileStatus::PasswordProtected(path) => {
+                    report.password_protected.push(path.to_string_lossy().into_owned());
+                }
+                FileStatus::Merged(FileReport { path, merged, errors }) => {
+                    let file = path.to_string_lossy();
+                    report.loaded_files += 1;
+                    report.loaded_sheets += merged.len();
+                    report.merged_sheets.extend(merged.into_iter().map(|sheet| LoadedSheet {
+                        file: file.clone().into_owned(),
+                        sheet
+                    }));
+                    for error in &errors {
+                        *report.error_counts.count_mut(error.kind) += 1;
                     }
-                    writer.flush().await?;
-                    Ok(())
-                } else {
-                    Err(eyre::eyre!("Sheet not held exclusively"))
+                    report.errors.extend(errors.into_iter().map(|error| LoadError {
+                        file: file.clone().into_owned(),
+                        error
+                    }));
                 }
-            })
+            }
         }
-        while let Some(_) = tasks.next().await.transpose()? {
-            // Keep polling
+        report
+    }
+
+    pub fn loaded_files(&self) -> usize {
+        self.loaded_files
+    }
+
+    pub fn loaded_sheets(&self) -> usize {
+        self.loaded_sheets
+    }
+
+    pub fn cached_files(&self) -> usize {
+        self.cached_files
+    }
+
+    /// Every merged sheet, the file it is in, and the rows it contributed
+    pub fn merged_sheets(&self) -> impl Iterator<Item=(&str, &SheetRows)> {
+        self.merged_sheets.iter().map(|LoadedSheet { file, sheet }| (file.as_str(), sheet))
+    }
+
+    /// Sheets which were merged successfully, yet contributed no rows
+    pub fn empty_sheets(&self) -> impl Iterator<Item=(&str, &SheetRows)> {
+        self.merged_sheets().filter(|(_, sheet)| sheet.rows == 0)
+    }
+
+    pub fn xls_unsupported(&self) -> &[String] {
+        &self.xls_unsupported
+    }
+
+    pub fn password_protected(&self) -> &[String] {
+        &self.password_protected
+    }
+
+    /// How many sheets failed to load with the given kind of error
+    pub fn error_count(&self, kind: AnalysisErrorKind) -> usize {
+        match kind {
+            AnalysisErrorKind::Unsupported => self.error_counts.unsupported,
+            AnalysisErrorKind::NoData => self.error_counts.no_data,
+            AnalysisErrorKind::OtherFailure => self.error_counts.other_failure
+        }
+    }
+
+    /// How many rows merged into each output sheet were redundant, by the sheet's name
+    pub fn redundant_rows(&self) -> &BTreeMap<String, usize> {
+        &self.redundant_rows
+    }
+
+    /// Every sheet which failed to load, and the file it is in
+    pub fn errors(&self) -> impl Iterator<Item=(&str, &SheetError)> {
+        self.errors.iter().map(|LoadError { file, error }| (file.as_str(), error))
+    }
+
+    /// Fails if any file or sheet could not be read, save for sheets without data. Lets
+    /// automated runs notice when the central bank changes a format
+    pub fn ensure_strict(&self) -> Result<()> {
+        let mut failures = self.xls_unsupported
+            .iter()
+            .map(|file| format!("\n  {}: XLS files are unsupported", file))
+            .collect::<Vec<_>>();
+        failures.extend(self.errors
+            .iter()
+            .filter(|load_error| load_error.error.kind != AnalysisErrorKind::NoData)
+            .map(|LoadError { file, error }| format!("\n  {}: {}", file, error)));
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre::eyre!("{} files or sheets failed to load in strict mode:{}", failures.len(), failures.concat()))
+        }
+    }
+
+    /// Logs a human-readable summary of this report
+    pub fn log_summary(&self) {
+        let mut error_report = String::new();
+        if !self.xls_unsupported.is_empty() {
+            error_report.push_str("\nXLS files are unsupported. XLS files: ");
+            error_report.push_str(&self.xls_unsupported.join(", "));
+        }
+        if !self.password_protected.is_empty() {
+            error_report.push_str("\nPassword-protected files must be unlocked by hand. Protected files: ");
+            error_report.push_str(&self.password_protected.join(", "));
+        }
+        for kind in AnalysisErrorKind::ALL {
+            let count = self.error_count(kind);
+            if count == 0 {
+                continue;
+            }
+            let heading = match kind {
+                AnalysisErrorKind::Unsupported => "Sheets in unsupported formats",
+                AnalysisErrorKind::OtherFailure => "Sheets failing otherwise",
+                AnalysisErrorKind::NoData => "Sheets without data"
+            };
+            error_report.push_str(&format!("\n{} ({}):", heading, count));
+            // Errors from the same file are adjacent
+            let mut previous_file = None;
+            for LoadError { file, error } in self.errors.iter().filter(|load_error| load_error.error.kind == kind) {
+                if previous_file != Some(file) {
+                    error_report.push_str(&format!("\n  {}:", file));
+                    previous_file = Some(file);
+                }
+                error_report.push_str(&format!("\n    {}", error));
+            }
+        }
+        let mut empty_sheets = self.empty_sheets().peekable();
+        if empty_sheets.peek().is_some() {
+            error_report.push_str("\nSheets merged without contributing any rows:");
+            for (file, sheet) in empty_sheets {
+                error_report.push_str(&format!("\n  {}: {}", file, sheet.sheet));
+            }
+        }
+
+        log::info!(
+            "Loaded and merged rows of {} sheets from {} data files.\n-- Report --",
+            self.loaded_sheets, self.loaded_files
+        );
+        for LoadedSheet { file, sheet } in &self.merged_sheets {
+            log::debug!("{}: {} contributed {} rows", file, sheet.sheet, sheet.rows);
+        }
+        for (sheet, redundant_rows) in self.redundant_rows.iter().filter(|(_, count)| **count > 0) {
+            log::info!("{} rows merged into the {} sheet were already present.", redundant_rows, sheet);
+        }
+        if error_report.is_empty() {
+            log::info!("\n  Hooray, all sheets loaded with pure success.\n");
+        } else {
+            log::info!("{}", error_report);
+        }
+    }
+
+    /// Writes this report as JSON to the given file
+    pub async fn write_json(&self, destination: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(destination, json).await
+            .wrap_err_with(|| format!("While writing load report to {}", destination.to_string_lossy()))
+    }
+}
+
+impl MergeXL {
+    pub fn new(options: AnalysisOptions) -> Self {
+        Self {
+            sheets: RwLock::default(),
+            cross_sections: RwLock::default(),
+            options
+        }
+    }
+
+    /// Writes the data in memory to the given destination. Each granularity gets its own file,
+    /// whose rows are in chronological order, unless the output is combined into one file or
+    /// split further by category. Each kind of cross-section always gets its own file
+    pub async fn write_to(&self, destination: &OsStr, options: &OutputOptions) -> Result<()> {
+        // Sheets are only read, so they may be shared with whoever else holds them
+        let sheets = self.sheets.read().await;
+        {
+            let sheet_names = sheets
+                .iter()
+                .map(|(granularity, sheet)| (granularity.to_string(), sheet.as_ref()))
+                .collect::<Vec<_>>();
+            if options.manifest {
+                let manifest = open_output_file(destination, "-manifest.csv", options).await?;
+                output::write_manifest(manifest, &sheet_names, options).await?;
+            }
+            if options.coverage_report {
+                let report = open_output_file(destination, "-coverage.csv", options).await?;
+                output::write_coverage_report(report, &sheet_names, options).await?;
+            }
+            if let (Some(tolerance), Some(quarterly)) = (options.sum_check_tolerance, sheets.get(&Granularity::Quarterly)) {
+                let yearly_sheets = [Granularity::CalendarYear, Granularity::FiscalYear]
+                    .into_iter()
+                    .filter_map(|granularity| Some((granularity.to_string(), sheets.get(&granularity)?.as_ref())))
+                    .collect::<Vec<_>>();
+                let report = open_output_file(destination, "-quarterly-sums.csv", options).await?;
+                output::write_sum_discrepancies(report, quarterly, &yearly_sheets, options, tolerance).await?;
+            }
+        }
+        if options.combined {
+            let combined = sheets
+                .iter()
+                .map(|(granularity, sheet)| (*granularity, sheet.as_ref()))
+                .collect::<Vec<_>>();
+            let file = open_output_file(destination, &format!("-combined.{}", options.extension()), options).await?;
+            output::write_combined(file, &combined, options).await?;
+        } else if options.split_by_category {
+            let split = sheets
+                .iter()
+                .flat_map(|(granularity, sheet)| {
+                    transform::split_by_category(sheet.as_ref()).into_iter().map(move |(category, sheet)| {
+                        (format!("{}-{}", granularity, category_suffix(&category)), Arc::new(sheet))
+                    })
+                })
+                .collect::<Vec<_>>();
+            write_sheets(destination, split.iter().map(|(suffix, sheet)| (suffix.clone(), sheet)), options).await?;
+        } else {
+            write_sheets(destination, sheets.iter().map(|(granularity, sheet)| (granularity.to_string(), sheet)), options).await?;
+        }
+
+        let cross_sections = self.cross_sections.read().await;
+        write_sheets(destination, cross_sections.iter().map(|(kind, sheet)| (kind.to_string(), sheet)), options).await
+    }
+
+    /// Writes each sheet as an Arrow IPC (Feather v2) file, named by its granularity like
+    /// the per-sheet output of [Self::write_to]
+    #[cfg(feature = "arrow")]
+    pub async fn write_feather(&self, destination: &OsStr, options: &OutputOptions) -> Result<()> {
+        for (granularity, sheet) in self.sheets().await {
+            let batch = crate::feather::sheet_batch(sheet.as_ref(), options)?;
+            let mut buffer = Vec::new();
+            crate::feather::write_batch(&mut buffer, &batch)?;
+            let mut file = create_output_file(destination, &format!("-{}.feather", granularity)).await?;
+            file.write_all(&buffer).await?;
+            file.flush().await?;
         }
         Ok(())
     }
 
-    /// Loads all excel files from the given data directory into memory
-    pub async fn load_all_from(&self, data_dir: &Path) -> Result<()> {
+    /// Writes the sheet of the given granularity to any writer, in the same form as
+    /// [Self::write_to] writes it to its file. Yields false, writing nothing, if there is
+    /// no such sheet
+    pub async fn write_sheet_to<W>(&self, granularity: Granularity, writer: W, options: &OutputOptions) -> Result<bool>
+        where W: AsyncWrite + Unpin {
+
+        let Some(sheet) = self.sheet(granularity).await else {
+            return Ok(false);
+        };
+        write_sheet_output(writer, sheet.as_ref(), options).await?;
+        Ok(true)
+    }
+
+    /// Loads all excel and OpenDocument files from the given data directory into memory.
+    /// Reports which files and sheets were loaded, and which could not be
+    pub async fn load_all_from(&self, data_dir: &Path) -> Result<LoadReport> {
+        self.load_all(data_dir, None).await
+    }
+
+    /// Loads all files like [Self::load_all_from], but reuses the rows of files unchanged
+    /// since they were cached, and caches the rows of the others. The cache should be saved
+    /// afterward
+    pub async fn load_all_cached(&self, data_dir: &Path, cache: &MergeCache) -> Result<LoadReport> {
+        let mut report = self.load_all(data_dir, Some(cache)).await?;
+        report.cached_files = cache.reused();
+        log::info!("Reused the rows of {} unchanged files from the merge cache", report.cached_files);
+        Ok(report)
+    }
+
+    async fn load_all(&self, data_dir: &Path, cache: Option<&MergeCache>) -> Result<LoadReport> {
 
         // Load every file in parallel
         let mut tasks = FuturesUnordered::new();
@@ -123,7 +494,8 @@ impl MergeXL {
         while let Some(file) = files.next().await.transpose()? {
 
             let merge_file = MergeFile {
-                merge_xl: &self,
+                merge_xl: self,
+                cache,
                 file
             };
             tasks.push(async move { merge_file.merge().await });
@@ -135,65 +507,132 @@ impl MergeXL {
         }
         if file_statuses.is_empty() {
             log::warn!("No files loaded. Did you specify the correct data directory?");
+        }
+        let mut report = LoadReport::from_statuses(file_statuses);
+        for (granularity, sheet) in self.sheets.read().await.iter() {
+            report.redundant_rows.insert(granularity.to_string(), sheet.redundant_rows());
+        }
+        for (kind, sheet) in self.cross_sections.read().await.iter() {
+            report.redundant_rows.insert(kind.to_string(), sheet.redundant_rows());
+        }
+        Ok(report)
+    }
+
+    /// Aggregates the monthly sheet and writes the result to its own file. The suffix names
+    /// both granularities, e.g. -monthly-as-quarterly.csv
+    pub async fn write_aggregate(&self, destination: &OsStr, aggregation: &Aggregation,
+                                 options: &OutputOptions) -> Result<()> {
+        let Some(monthly) = self.sheet(Granularity::Monthly).await else {
+            log::warn!("No monthly data to aggregate");
             return Ok(());
+        };
+        let aggregated = transform::aggregate(&monthly, aggregation);
+        let suffix = format!("-{}-as-{}.{}", Granularity::Monthly, aggregation.target, options.extension());
+        let file = open_output_file(destination, &suffix, options).await?;
+        output::write_sheet(file, &aggregated, options).await
+    }
+
+    /// Fills the gaps in the monthly sheet and writes the result to its own file, so that the
+    /// observed data stays intact. Each row names the columns whose values were filled
+    pub async fn write_filled(&self, destination: &OsStr, method: FillMethod, options: &OutputOptions) -> Result<()> {
+        let Some(monthly) = self.sheet(Granularity::Monthly).await else {
+            log::warn!("No monthly data to fill");
+            return Ok(());
+        };
+        let filled = transform::fill_gaps(&monthly, method);
+        let options = OutputOptions { filled_column: true, ..options.clone() };
+        let suffix = format!("-{}-filled.{}", Granularity::Monthly, options.extension());
+        let file = open_output_file(destination, &suffix, &options).await?;
+        output::write_sheet(file, &filled, &options).await
+    }
+
+    /// Loads an output file previously written by [Self::write_to] back into memory. The file
+    /// must be in the wide format, with single-row column labels and comma delimiters. Its name
+    /// tells the granularity of its timestamps, as displayed timestamps can be ambiguous.
+    ///
+    /// Labels are split on the separator they were written with. Labels themselves containing
+    /// the separator are indistinguishable from nested labels, so they are split too. Rows whose
+    /// timestamps cannot be read are skipped. Yields the number of rows loaded
+    pub async fn load_csv(&self, path: &Path, label_separator: &str) -> Result<usize> {
+        let path_display = path.to_string_lossy();
+        let granularity = output_file_granularity(path)
+            .ok_or_else(|| eyre::eyre!("Cannot tell the granularity of {} from its name", path_display))?;
+
+        let file = fs::File::open(path).await
+            .wrap_err_with(|| format!("While opening {}", path_display))?;
+        let mut reader = csv_async::AsyncReader::from_reader(file);
+        let header = reader.headers().await?.clone();
+        let mut header = header.iter();
+        let mut key_header = header.next().unwrap_or_default().trim_start_matches('\u{feff}');
+        // A leading date column only repeats the timestamps
+        let key_index = usize::from(key_header == output::DATE_HEADER);
+        if key_index > 0 {
+            key_header = header.next().unwrap_or_default();
         }
-        let mut file_success_count = 0;
-        let mut sheet_success_count = 0;
-        for status in &file_statuses {
-            if let FileStatus::Merged(success_count, _)  = status {
-                file_success_count += 1usize;
-                sheet_success_count += success_count;
-            }
+        if key_header != Timestamp::HEADER {
+            return Err(eyre::eyre!("{} is not a wide output file with dotted labels", path_display));
         }
-        let mut error_report = String::new();
+        let mut header = header.peekable();
+        let flagged = header.next_if_eq(&output::PROVISIONAL_HEADER).is_some();
+        let revised_index = key_index + 1 + usize::from(flagged);
+        let revised = header.next_if_eq(&output::REVISED_HEADER).is_some();
+        let data_index = revised_index + usize::from(revised);
+        let columns = header
+            .map(|labeling| Column::new(labeling.split(label_separator).filter_map(ColumnLabel::create)))
+            .collect::<AnalysisResult<Vec<_>>>()
+            .wrap_err_with(|| format!("While reading the columns of {}", path_display))?;
 
-        macro_rules! format_errors_matching {
-            ($filtermap_impl:expr, $join_separator:literal, $heading:literal) => {
-                {
-                    let combined = file_statuses
-                        .iter()
-                        .filter_map($filtermap_impl)
-                        .collect::<Vec<_>>()
-                        .join($join_separator);
-                    if !combined.is_empty() {
-                        error_report.push_str($heading);
-                        error_report.push_str(&combined);
-                    }
-                }
+        let sheet = self.get_or_create_sheet_of(granularity).await;
+        let mut loaded = 0;
+        let mut records = reader.records();
+        while let Some(record) = records.next().await.transpose()? {
+            let displayed_timestamp = record.get(key_index).unwrap_or_default();
+            let Ok(timestamp) = Timestamp::from_displayed_value(displayed_timestamp, granularity) else {
+                log::warn!("Skipping row with unknown timestamp {} in {}", displayed_timestamp, path_display);
+                continue;
+            };
+            if flagged && record.get(key_index + 1) == Some("true") {
+                sheet.mark_provisional(timestamp);
             }
-        }
-        format_errors_matching!(|status| {
-            if let FileStatus::XlsUnsupported(path) = status {
-                Some(path.to_string_lossy())
-            } else {
-                None
+            let mut row = RowData::default();
+            if revised && record.get(revised_index) == Some("true") {
+                row.mark_revised();
             }
-        }, ", ", "\nXLS files are unsupported. XLS files: ");
-        format_errors_matching!(|status| {
-            if let FileStatus::Merged(_, Some(FileErrorReport { path, errors })) = status {
-                Some(format!(
-                        "  {}:\n    {}", path.to_string_lossy(), errors.join("\n    ")
-                ))
-            } else {
-                None
+            for (column, value) in columns.iter().zip(record.iter().skip(data_index)) {
+                if !value.is_empty() && value != output::MISSING_VALUE {
+                    row.populate(column, Value::parse(value));
+                }
             }
-        }, "\n", "\nFailures while loading files:\n");
-
-        log::info!(
-            "Loaded and merged rows of {} sheets from {} data files.\n-- Report --",
-            sheet_success_count, file_success_count
-        );
-        if error_report.is_empty() {
-            log::info!("\n  Hooray, all sheets loaded with pure success.\n");
-        } else {
-            log::info!("{}", error_report);
+            sheet.add_row(timestamp, row);
+            loaded += 1;
         }
-        Ok(())
+        Ok(loaded)
+    }
+
+    /// The number of sheets, one per granularity found
+    pub async fn sheet_count(&self) -> usize {
+        self.sheets.read().await.len()
+    }
+
+    /// Every sheet and its granularity, from the longest granularity to the shortest
+    pub async fn sheets(&self) -> Vec<(Granularity, Arc<Sheet>)> {
+        self.sheets.read().await
+            .iter()
+            .map(|(granularity, sheet)| (*granularity, sheet.clone()))
+            .collect()
     }
 
-    /// Gets or creates a sheet by name
+    /// Gets the sheet of the given granularity, if it has any rows
+    pub async fn sheet(&self, granularity: Granularity) -> Option<Arc<Sheet>> {
+        self.sheets.read().await.get(&granularity).cloned()
+    }
+
+    /// Gets or creates the sheet holding timestamps of the same granularity
     pub async fn get_or_create_sheet(&self, timestamp_variant: &Timestamp) -> Arc<Sheet> {
-        let variant = mem::discriminant(timestamp_variant);
+        self.get_or_create_sheet_of(timestamp_variant.granularity()).await
+    }
+
+    async fn get_or_create_sheet_of(&self, variant: Granularity) -> Arc<Sheet> {
         {
             let sheets = self.sheets.read().await;
             if let Some(sheet) = sheets.get(&variant) {
@@ -205,14 +644,111 @@ impl MergeXL {
         if let Some(existing) = sheets.get(&variant) {
             return existing.clone();
         }
-        let new = Arc::new(Sheet::default());
+        let new = Arc::new(Sheet::with_conflict_policy(self.options.conflicts));
         sheets.insert(variant, new.clone());
         new
     }
+
+    /// Gets or creates the sheet holding the given kind of cross-section
+    pub async fn get_or_create_cross_section(&self, kind: &'static str) -> Arc<Sheet<Institution>> {
+        {
+            let cross_sections = self.cross_sections.read().await;
+            if let Some(sheet) = cross_sections.get(kind) {
+                return sheet.clone();
+            }
+            // Release read lock
+        }
+        let mut cross_sections = self.cross_sections.write().await;
+        cross_sections
+            .entry(kind)
+            .or_insert_with(|| Arc::new(Sheet::with_conflict_policy(self.options.conflicts)))
+            .clone()
+    }
+}
+
+/// Tells the granularity of an output file from the suffix [MergeXL::write_to] gives its name
+pub fn output_file_granularity(path: &Path) -> Option<Granularity> {
+    let stem = path.file_stem()?.to_string_lossy();
+    Granularity::values().into_iter().find(|granularity| stem.ends_with(&format!("-{}", granularity.name())))
+}
+
+/// Writes each sheet to its own file, named by appending its suffix to the destination prefix
+async fn write_sheets<'s, K, I>(destination: &OsStr, sheets: I, options: &OutputOptions) -> Result<()>
+    where K: RowKey + 's, I: IntoIterator<Item=(String, &'s Arc<Sheet<K>>)> {
+
+    let mut tasks = FuturesUnordered::new();
+    for (suffix, sheet) in sheets {
+        tasks.push(async move {
+            let destination = create_output_file(destination, &format!("-{}.{}", suffix, options.extension())).await?;
+            write_sheet_output(destination, sheet.as_ref(), options).await
+        })
+    }
+    while tasks.next().await.transpose()?.is_some() {
+        // Keep polling
+    }
+    Ok(())
+}
+
+/// The category in a form fit for file names, e.g. External Sector becomes external-sector
+fn category_suffix(category: &str) -> String {
+    category
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
 }
 
+/// Writes a sheet in the output format, preceded by the byte order mark if enabled for CSV
+/// or TSV
+async fn write_sheet_output<W, K>(mut writer: W, sheet: &Sheet<K>, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    if options.byte_order_mark && options.format != OutputFormat::Jsonl {
+        writer.write_all(UTF8_BYTE_ORDER_MARK).await?;
+    }
+    output::write_sheet(writer, sheet, options).await
+}
+
+/// Opens the output file formed by appending the suffix to the destination prefix.
+/// Creates the parent directory if needed, and writes the byte order mark if enabled for CSV
+/// or TSV.
+async fn open_output_file(destination: &OsStr, suffix: &str, options: &OutputOptions) -> Result<fs::File> {
+    let mut file = create_output_file(destination, suffix).await?;
+    if options.byte_order_mark && (suffix.ends_with(".csv") || suffix.ends_with(".tsv")) {
+        file.write_all(UTF8_BYTE_ORDER_MARK).await?;
+    }
+    Ok(file)
+}
+
+/// Creates the output file formed by appending the suffix to the destination prefix, and
+/// its parent directory if needed
+async fn create_output_file(destination: &OsStr, suffix: &str) -> Result<fs::File> {
+    let mut destination = destination.to_os_string();
+    destination.push(suffix);
+    let destination = PathBuf::from(destination);
+    if let Some(parent) = destination.parent() {
+        if !parent.as_os_str().is_empty() {
+            fs::create_dir_all(parent).await
+                .wrap_err_with(|| format!("While creating output directory {}", parent.to_string_lossy()))?;
+        }
+    }
+    log::info!("Writing to output file {}", destination.to_string_lossy());
+    Ok(OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(destination).await?)
+}
+
+const UTF8_BYTE_ORDER_MARK: &[u8] = b"\xEF\xBB\xBF";
+
+/// The number of sheets from the same file analyzed at once
+const MAX_CONCURRENT_SHEETS: usize = 8;
+
 struct MergeFile<'m> {
     merge_xl: &'m MergeXL,
+    cache: Option<&'m MergeCache>,
     file: DirEntry
 }
 
@@ -226,8 +762,8 @@ impl MergeFile<'_> {
         }
         let file = self.file.path();
 
-        Ok(if filename.ends_with(".xlsx") {
-            // Received correct file type
+        Ok(if filename.ends_with(".xlsx") || filename.ends_with(".ods") {
+            // Received correct file type. Calamine reads OpenDocument spreadsheets alike
             self.perform_merge_data(file).await?
 
         } else if filename.ends_with(".xls") {
@@ -241,56 +777,294 @@ impl MergeFile<'_> {
     }
 
     async fn perform_merge_data(&self, file: PathBuf) -> Result<FileStatus> {
+        if let Some(cache) = self.cache {
+            if let Some((merged, errors)) = cache.replay(&file, self.merge_xl).await {
+                return Ok(FileStatus::Merged(FileReport { path: file, merged, errors }));
+            }
+        }
+        let exclusions = self.merge_xl.options.exclusions.clone();
         let (file, sheets) = task::spawn_blocking(move || {
-            let sheets = blocking_load_all_sheets(&file)?;
+            let sheets = blocking_load_all_sheets(&file, &exclusions)?;
             Ok::<_, eyre::Report>((file, sheets))
         }).await?;
+        let Some(sheets) = sheets else {
+            return Ok(FileStatus::PasswordProtected(file));
+        };
 
         let filename = file.to_string_lossy();
-        let mut success_count = 0;
+        let mut merged = Vec::new();
         let mut errors = Vec::new();
 
-        for (name, sheet) in sheets {
-            let analyzer = SheetAnalyzer {
-                source: &filename,
-                name: &name,
-                sheet
+        // Analyze sheets concurrently. Their rows meet only in the shared MergeXL
+        let recorder = self.cache.map(|_| Recorder::new(self.merge_xl));
+        let destination = recorder.as_ref();
+        let mut tasks = FuturesUnordered::new();
+        let mut sheets = sheets.into_iter().enumerate();
+        loop {
+            while tasks.len() < MAX_CONCURRENT_SHEETS {
+                let Some((position, (name, sheet))) = sheets.next() else {
+                    break;
+                };
+                let filename = &filename;
+                tasks.push(async move {
+                    let analyzer = SheetAnalyzer {
+                        source: filename,
+                        name: &name,
+                        sheet,
+                        options: &self.merge_xl.options
+                    };
+                    let result = match destination {
+                        Some(recorder) => analyzer.merge_data_into(recorder).await,
+                        None => analyzer.merge_data(self.merge_xl).await
+                    };
+                    (position, name, result)
+                });
+            }
+            let Some((position, name, result)) = tasks.next().await else {
+                break;
             };
-            match analyzer.merge_data(&self.merge_xl).await {
-                Ok(()) => success_count += 1,
-                Err(error) => errors.push(format!("{}: {}", name, error))
+            match result {
+                Ok(rows) => merged.push((position, SheetRows { sheet: name, rows })),
+                Err(error) => errors.push(SheetError {
+                    kind: error.kind(),
+                    reason: error.reason(),
+                    sheet: name
+                })
             };
         }
-        let error = if !errors.is_empty() {
-            Some(FileErrorReport { path: file, errors })
-        } else {
-            None
-        };
-        Ok(FileStatus::Merged(success_count, error))
+        drop(tasks);
+        // Report sheets in the order they appear in the file
+        merged.sort_by_key(|(position, _)| *position);
+        let merged = merged.into_iter().map(|(_, sheet)| sheet).collect::<Vec<_>>();
+        if let (Some(cache), Some(recorder)) = (self.cache, recorder) {
+            cache.record(&file, recorder, &merged, &errors).await?;
+        }
+        Ok(FileStatus::Merged(FileReport { path: file, merged, errors }))
+    }
+}
+
+
+/// Every named sheet loaded from a file
+type LoadedSheets = Vec<(String, Range<DataType>)>;
+
+/// The names of the sheets in each file, after exclusions, in the order of the filenames
+pub type SheetInventory = Vec<(String, Vec<String>)>;
+
+/// Lists the sheets of every excel and OpenDocument file in the data directory, without
+/// analyzing them. Password-protected files are listed without sheets
+pub async fn list_sheets(data_dir: &Path, exclusions: &SheetExclusions) -> Result<SheetInventory> {
+    let mut inventory = Vec::new();
+    let mut files = fs::read_dir(data_dir).await?;
+    while let Some(file) = files.next().await.transpose()? {
+        let filename = file.file_name().to_string_lossy().into_owned();
+        if !is_readable_spreadsheet(&filename) {
+            continue;
+        }
+        let path = file.path();
+        let exclusions = exclusions.clone();
+        let sheet_names = task::spawn_blocking(move || blocking_list_sheets(&path, &exclusions)).await?;
+        inventory.push((filename, sheet_names.unwrap_or_default()));
+    }
+    inventory.sort();
+    Ok(inventory)
+}
+
+/// Writes the sheet inventory in CSV form, one sheet per line
+pub async fn write_sheet_list<W>(writer: W, inventory: &SheetInventory, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    let mut writer = options.csv_writer(writer);
+    writer.write_record(["file", "sheet"]).await?;
+    for (file, sheet_names) in inventory {
+        for sheet_name in sheet_names {
+            writer.write_record([file, sheet_name]).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The outcome of analyzing each sheet of a file, in the order of the sheets
+pub type FilePreview = Vec<(String, AnalysisResult<SheetPreview>)>;
+
+/// Analyzes every sheet in the data directory as a merge would, but without keeping any rows.
+/// Files are in the order of their names. Password-protected files are listed without sheets
+pub async fn preview_sheets(data_dir: &Path, options: &AnalysisOptions) -> Result<Vec<(String, FilePreview)>> {
+    let mut previews = Vec::new();
+    let mut files = fs::read_dir(data_dir).await?;
+    while let Some(file) = files.next().await.transpose()? {
+        let filename = file.file_name().to_string_lossy().into_owned();
+        if !is_readable_spreadsheet(&filename) {
+            continue;
+        }
+        let path = file.path();
+        let exclusions = options.exclusions.clone();
+        let sheets = task::spawn_blocking(move || blocking_load_all_sheets(&path, &exclusions)).await?;
+        let mut file_preview = Vec::new();
+        for (name, sheet) in sheets.unwrap_or_default() {
+            let analyzer = SheetAnalyzer { source: &filename, name: &name, sheet, options };
+            let preview = analyzer.preview().await;
+            file_preview.push((name, preview));
+        }
+        previews.push((filename, file_preview));
+    }
+    previews.sort_by(|(first, _), (second, _)| first.cmp(second));
+    Ok(previews)
+}
+
+/// Whether the file is one calamine reads, and not hidden
+fn is_readable_spreadsheet(filename: &str) -> bool {
+    !filename.starts_with('.') && (filename.ends_with(".xlsx") || filename.ends_with(".ods"))
+}
+
+/// Opens an excel file. Yields none if the file is password-protected
+fn blocking_open_workbook(source: &Path) -> Result<Option<calamine::Sheets<std::io::BufReader<std::fs::File>>>> {
+    let source_filename = source.to_string_lossy();
+    match calamine::open_workbook_auto(source) {
+        Ok(workbook) => Ok(Some(workbook)),
+        Err(_) if blocking_is_password_protected(source)? => {
+            log::warn!("Excel file {} is password-protected", source_filename);
+            Ok(None)
+        }
+        Err(error) => Err(error).wrap_err_with(|| format!("While loading excel file {}", source_filename))
     }
 }
 
+/// Reads the names of the sheets in an excel file, without loading the sheets themselves
+fn blocking_list_sheets(source: &Path, exclusions: &SheetExclusions) -> Result<Option<Vec<String>>> {
+    Ok(blocking_open_workbook(source)?.map(|workbook| {
+        workbook
+            .sheet_names()
+            .iter()
+            .filter(|sheet_name| !exclusions.excludes(sheet_name))
+            .cloned()
+            .collect()
+    }))
+}
 
-/// Loads a specific excel file into memory
+/// Loads a specific excel file into memory. Yields none if the file is password-protected
 /// Threading: calamine's blocking I/O happens here and not later
-fn blocking_load_all_sheets(source: &Path) -> Result<impl IntoIterator<Item=(String, Range<DataType>)>> {
+fn blocking_load_all_sheets(source: &Path, exclusions: &SheetExclusions)
+    -> Result<Option<LoadedSheets>> {
     let source_filename = source.to_string_lossy();
     log::info!("Loading excel file from {}", source_filename);
-    let mut workbook =  calamine::open_workbook_auto(source)
-        .wrap_err_with(|| format!("While loading excel file {}", source_filename))?;
+    let Some(mut workbook) = blocking_open_workbook(source)? else {
+        return Ok(None);
+    };
     log::info!("Loaded file {}", source_filename);
-    Ok(workbook
+    Ok(Some(workbook
         .worksheets()
         .into_iter()
-        .filter(|(sheet_name, _)| {
-            sheet_name != "Cover Page" && sheet_name != "Contents" && !sheet_name.starts_with("Appendix")
-        }))
+        .filter(|(sheet_name, _)| !exclusions.excludes(sheet_name))
+        .collect()))
 }
 
-#[derive(Default)]
-pub struct Sheet {
-    columns: DashSet<Column>,
-    rows: DashMap<Timestamp, RowData>
+/// Whether the file is an encrypted workbook. Excel encrypts xlsx files by wrapping them in
+/// an OLE compound document, so they lack the ZIP structure calamine expects
+fn blocking_is_password_protected(source: &Path) -> Result<bool> {
+    use std::io::Read;
+
+    let signature = SheetExtension::Xls.signature();
+    let mut start = vec![0; signature.len()];
+    let mut file = std::fs::File::open(source)?;
+    match file.read_exact(&mut start) {
+        Ok(()) => Ok(source.extension() == Some(OsStr::new("xlsx")) && start == signature),
+        Err(error) if error.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(error) => Err(error.into())
+    }
+}
+
+/// Merged rows under a common set of columns. Most sheets are time series keyed by
+/// [Timestamp], while cross-sectional sheets are keyed by [Institution]
+pub struct Sheet<K = Timestamp> where K: Eq + Hash {
+    pub(crate) columns: DashSet<Column>,
+    pub(crate) rows: DashMap<K, RowData>,
+    /// Keys of rows holding provisional figures, if flagged
+    pub(crate) provisional: DashSet<K>,
+    /// Columns whose figures were written as percentages
+    pub(crate) percentages: DashSet<Column>,
+    /// The units each column's figures were converted from, such as crore
+    pub(crate) units: DashSet<(Column, Unit)>,
+    /// How many added rows brought nothing new to the row already at their key
+    redundant_rows: AtomicUsize,
+    /// Which of two values for the same cell is kept
+    conflicts: ConflictPolicy
+}
+
+/// Identifies the rows of a sheet
+pub trait RowKey: Clone + Eq + Hash + Display + Send + Sync {
+    /// The header of the key column in output
+    const HEADER: &'static str;
+
+    /// Orders the keys as their rows are written
+    fn cmp_for_output(&self, other: &Self) -> Ordering;
+
+    /// Whether keys are periods of time, which start on a date
+    const DATED: bool = false;
+
+    /// The key as written in output
+    fn output_value(&self, _options: &OutputOptions) -> String {
+        self.to_string()
+    }
+
+    /// The ISO 8601 date the key's period starts on, if keys are dated
+    fn iso_start_date(&self) -> Option<String> {
+        None
+    }
+}
+
+impl RowKey for Timestamp {
+    const HEADER: &'static str = "timestamp-primary-key";
+
+    fn cmp_for_output(&self, other: &Self) -> Ordering {
+        self.cmp_chronologically(other)
+    }
+
+    const DATED: bool = true;
+
+    fn output_value(&self, options: &OutputOptions) -> String {
+        options.display_timestamp(self)
+    }
+
+    fn iso_start_date(&self) -> Option<String> {
+        Some(format!("{}-01", self.date_range().0))
+    }
+}
+
+/// A bank or other institution, the row key of cross-sectional sheets
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Institution(ArcIntern<str>);
+
+impl Institution {
+    /// Creates from the name as written in the sheet. Yields none if the name is blank
+    pub fn create(name: &str) -> Option<Self> {
+        let name = name.trim();
+        if name.is_empty() {
+            None
+        } else {
+            Some(Self(ArcIntern::from(name)))
+        }
+    }
+}
+
+impl AsRef<str> for Institution {
+    fn as_ref(&self) -> &str {
+        self.0.as_ref()
+    }
+}
+
+impl Display for Institution {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl RowKey for Institution {
+    const HEADER: &'static str = "institution";
+
+    fn cmp_for_output(&self, other: &Self) -> Ordering {
+        self.as_ref().cmp(other.as_ref())
+    }
 }
 
 /// A column in a sheet. Because the central bank likes to exquisitely detail its columns,
@@ -302,9 +1076,33 @@ pub struct Column {
     label_categorization: SmallVec<[ColumnLabel; 6]>
 }
 
+/// Which value is kept when rows combined at the same key both have one for a column
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ConflictPolicy {
+    /// Real values are kept over empty cells and placeholders such as NA. Otherwise, the
+    /// value merged last is kept
+    #[default]
+    PreferFilled,
+    /// The value merged last is kept, whatever it is. Files and sheets are merged
+    /// concurrently, so which one is last may vary between runs
+    Latest,
+    /// Like [ConflictPolicy::PreferFilled], but the longer of two real values as written is kept
+    Longer,
+    /// Like [ConflictPolicy::PreferFilled], but numbers are kept over text
+    PreferNumbers
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct RowData {
-    data: HashMap<Column, Box<str>>
+    data: HashMap<Column, Value>,
+    /// Whether the timestamp or any figure of the row was marked as revised
+    revised: bool,
+    /// Columns whose figures in this row were written as percentages
+    percentages: HashSet<Column>,
+    /// The units figures in this row were converted from, by column
+    units: HashMap<Column, Unit>,
+    /// Columns whose values in this row were filled in rather than observed
+    filled: HashSet<Column>
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -322,14 +1120,34 @@ impl Column {
         }
     }
 
-    fn display_full_labeling(&self) -> String {
-        let mut builder = String::new();
-        for label in &self.label_categorization {
-            builder.push_str(label.as_ref());
-            builder.push('.');
-        }
-        builder.pop();
-        builder
+    pub fn display_full_labeling(&self) -> String {
+        self.display_labeling_with(".")
+    }
+
+    /// Joins the label categorization with the given separator
+    pub fn display_labeling_with(&self, separator: &str) -> String {
+        self.label_categorization
+            .iter()
+            .map(AsRef::as_ref)
+            .collect::<Vec<&str>>()
+            .join(separator)
+    }
+
+    /// The column with one more level of labeling beneath its own
+    pub fn with_sublabel(&self, label: ColumnLabel) -> Column {
+        let mut label_categorization = self.label_categorization.clone();
+        label_categorization.push(label);
+        Self { label_categorization }
+    }
+
+    /// How many levels of categorization this column has. Always at least 1
+    pub fn depth(&self) -> usize {
+        self.label_categorization.len()
+    }
+
+    /// Gets the label at the given level of categorization, starting from the broadest (0)
+    pub fn label_at(&self, level: usize) -> Option<&str> {
+        self.label_categorization.get(level).map(AsRef::as_ref)
     }
 }
 
@@ -346,7 +1164,10 @@ impl Display for Column {
 
 impl ColumnLabel {
     pub fn create(label: &str) -> Option<Self> {
-        let label = label.trim();
+        // Runs of whitespace, including the non-breaking spaces common in xlsx files, would
+        // otherwise split a series across columns
+        let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+        let label = label.as_str();
         let is_number = label.parse::<u8>();
         if is_number.is_ok() {
             // Column labels are not allowed to be numbers
@@ -364,39 +1185,682 @@ impl AsRef<str> for ColumnLabel {
     }
 }
 
-impl Sheet {
+impl<K> Default for Sheet<K> where K: Eq + Hash {
+    fn default() -> Self {
+        Self {
+            columns: DashSet::default(),
+            rows: DashMap::default(),
+            provisional: DashSet::default(),
+            percentages: DashSet::default(),
+            units: DashSet::default(),
+            redundant_rows: AtomicUsize::default(),
+            conflicts: ConflictPolicy::default()
+        }
+    }
+}
+
+impl<K> Sheet<K> where K: RowKey {
+    /// Creates an empty sheet, which settles conflicting values by the given policy
+    pub fn with_conflict_policy(conflicts: ConflictPolicy) -> Self {
+        Self { conflicts, ..Self::default() }
+    }
+
     fn ensure_column(&self, column: &Column) {
         self.columns.insert(column.clone());
     }
 
-    pub fn add_row(&self, timestamp: Timestamp, row: RowData) {
+    /// Iterates over the columns of this sheet, in no particular order
+    pub fn columns(&self) -> impl Iterator<Item=Column> + '_ {
+        self.columns.iter().map(|column| column.key().clone())
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Gets the row at the given key. The row is locked while the guard is held, so avoid
+    /// holding it across adding rows
+    pub fn get(&self, key: &K) -> Option<impl Deref<Target=RowData> + '_> {
+        self.rows.get(key)
+    }
+
+    /// The keys of every row, in the order they are written. Timestamps are chronological
+    pub fn keys_in_order(&self) -> Vec<K> {
+        let mut keys = self.rows.iter().map(|row| row.key().clone()).collect::<Vec<_>>();
+        keys.sort_by(K::cmp_for_output);
+        keys
+    }
+
+    /// Moves the values of one column into another, then removes it. Values already in the
+    /// other column take precedence
+    pub fn merge_column(&self, from: &Column, into: &Column) {
+        let mut moved_any = false;
+        for mut row in self.rows.iter_mut() {
+            if let Some(value) = row.data.remove(from) {
+                row.data.entry(into.clone()).or_insert(value);
+                moved_any = true;
+            }
+        }
+        self.columns.remove(from);
+        if moved_any {
+            self.ensure_column(into);
+        }
+    }
+
+    /// Flags the row at the key as provisional. Rows flagged by any sheet stay so when
+    /// combined with the rows of other sheets
+    pub fn mark_provisional(&self, key: K) {
+        self.provisional.insert(key);
+    }
+
+    /// Whether the row at the key was flagged as provisional
+    pub fn is_provisional(&self, key: &K) -> bool {
+        self.provisional.contains(key)
+    }
+
+    /// Whether the figures of the column were written as percentages
+    pub fn is_percentage(&self, column: &Column) -> bool {
+        self.percentages.contains(column)
+    }
+
+    /// The units the figures of the column were converted from, if any
+    pub fn units(&self, column: &Column) -> Vec<Unit> {
+        Unit::ALL.into_iter().filter(|unit| self.units.contains(&(column.clone(), *unit))).collect()
+    }
+
+    /// How many added rows were redundant, their every value being at their key already
+    pub fn redundant_rows(&self) -> usize {
+        self.redundant_rows.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn add_row(&self, key: K, mut row: RowData) {
         row.data
             .iter()
             .for_each(|(col, _val)| self.ensure_column(col));
+        for column in row.percentages.drain() {
+            self.percentages.insert(column);
+        }
+        for (column, unit) in row.units.drain() {
+            self.units.insert((column, unit));
+        }
 
-        // Try to insert the row. If there is another value at the timestamp, combine them
-        let previous_row = self.rows.insert(timestamp.clone(), row);
-        if let Some(previous_row) = previous_row {
-            // Combine them
-            self.rows.alter(&timestamp, |_, new_row| previous_row.combine(new_row));
+        // Insert the row, or combine it with the row already at the key. The entry
+        // holds the shard lock so that concurrent sheets cannot interleave here
+        match self.rows.entry(key) {
+            Entry::Occupied(mut existing) => {
+                // Typically the same report downloaded twice, or overlapping reports
+                if existing.get().contains_all(&row) {
+                    self.redundant_rows.fetch_add(1, AtomicOrdering::Relaxed);
+                    existing.get_mut().revised |= row.revised;
+                } else {
+                    existing.get_mut().combine(row, self.conflicts);
+                }
+            }
+            Entry::Vacant(vacant) => {
+                vacant.insert(row);
+            }
         }
     }
 }
 
 impl RowData {
-    pub fn populate<V>(&mut self, column: &Column, value: V) where V: Into<Box<str>> {
+    pub fn populate<V>(&mut self, column: &Column, value: V) where V: Into<Value> {
         self.data.insert(column.clone(), value.into());
     }
 
+    pub fn get(&self, column: &Column) -> Option<&Value> {
+        self.data.get(column)
+    }
+
+    /// Iterates over the populated cells of this row
+    pub fn iter(&self) -> impl Iterator<Item=(&Column, &Value)> {
+        self.data.iter()
+    }
+
     pub fn len(&self) -> usize {
         self.data.len()
     }
 
-    fn combine(mut self, other: Self) -> Self {
-        self.data.extend(other.data);
-        self
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Records that the figure of the column was written as a percentage
+    pub fn mark_percentage(&mut self, column: &Column) {
+        self.percentages.insert(column.clone());
+    }
+
+    pub fn is_percentage(&self, column: &Column) -> bool {
+        self.percentages.contains(column)
+    }
+
+    /// Records that the figure of the column was converted from the unit
+    pub fn mark_unit(&mut self, column: &Column, unit: Unit) {
+        self.units.insert(column.clone(), unit);
+    }
+
+    pub fn unit(&self, column: &Column) -> Option<Unit> {
+        self.units.get(column).copied()
+    }
+
+    /// Renames every column of the row, keeping its values and marks
+    pub fn map_columns(self, rename: impl Fn(&Column) -> Column) -> RowData {
+        RowData {
+            data: self.data.iter().map(|(column, value)| (rename(column), value.clone())).collect(),
+            revised: self.revised,
+            percentages: self.percentages.iter().map(&rename).collect(),
+            units: self.units.iter().map(|(column, unit)| (rename(column), *unit)).collect(),
+            filled: self.filled.iter().map(&rename).collect()
+        }
+    }
+
+    /// Records that the value of the column was filled in rather than observed
+    pub fn mark_filled(&mut self, column: &Column) {
+        self.filled.insert(column.clone());
+    }
+
+    pub fn is_filled(&self, column: &Column) -> bool {
+        self.filled.contains(column)
+    }
+
+    pub fn mark_revised(&mut self) {
+        self.revised = true;
+    }
+
+    pub fn is_revised(&self) -> bool {
+        self.revised
+    }
+
+    /// Whether every value of the other row is in this row already
+    fn contains_all(&self, other: &Self) -> bool {
+        other.filled.is_subset(&self.filled)
+            && other.data.iter().all(|(column, value)| self.data.get(column) == Some(value))
+    }
+
+    fn combine(&mut self, other: Self, conflicts: ConflictPolicy) {
+        for (column, value) in other.data {
+            if let Some(existing) = self.data.get(&column) {
+                if !conflicts.prefers_incoming(existing, &value) {
+                    continue;
+                }
+            }
+            // The kept value brings its own filled status
+            if other.filled.contains(&column) {
+                self.filled.insert(column.clone());
+            } else {
+                self.filled.remove(&column);
+            }
+            self.data.insert(column, value);
+        }
+        self.revised |= other.revised;
+    }
+}
+
+impl ConflictPolicy {
+    /// Whether the incoming value replaces the existing one
+    fn prefers_incoming(&self, existing: &Value, incoming: &Value) -> bool {
+        match self {
+            Self::Latest => return true,
+            _ if incoming.is_placeholder() => return existing.is_placeholder(),
+            _ if existing.is_placeholder() => return true,
+            _ => {}
+        }
+        match self {
+            Self::Longer => incoming.to_string().len() >= existing.to_string().len(),
+            Self::PreferNumbers => !matches!((existing, incoming), (Value::Number(_), Value::Text(_))),
+            Self::PreferFilled | Self::Latest => true
+        }
     }
 }
 
+impl FromStr for ConflictPolicy {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "prefer-filled" => Ok(Self::PreferFilled),
+            "latest" => Ok(Self::Latest),
+            "longer" => Ok(Self::Longer),
+            "prefer-numbers" => Ok(Self::PreferNumbers),
+            other => Err(eyre::eyre!("Unknown conflict policy {}. Use prefer-filled, latest, longer, or prefer-numbers", other))
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use assert_matches::assert_matches;
+    use crate::testing::TempDir;
+    use super::*;
+
+    #[test]
+    fn write_to_nested_directory() {
+        let root = TempDir::new("nested-output");
+        let destination = root.join("reports").join("2024").join("output");
+
+        task::block_on(async {
+            let merge_xl = MergeXL::default();
+            let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+            let mut row = RowData::default();
+            row.populate(&Column::new([ColumnLabel::create("Broad Money").unwrap()]).unwrap(), 1.0);
+            merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            merge_xl.write_to(destination.as_os_str(), &OutputOptions::default()).await.unwrap();
+        });
+        let written = std::fs::read_dir(root.join("reports").join("2024"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![String::from("output-calendar-yearly.csv")], written);
+    }
+
+    #[test]
+    fn separate_calendar_and_fiscal_years() {
+        let root = TempDir::new("yearly-output");
+        let destination = root.join("output");
+        let column = Column::new([ColumnLabel::create("Broad Money").unwrap()]).unwrap();
+        let year_2019 = Year(NonZeroU16::new(2019).unwrap());
+
+        task::block_on(async {
+            let merge_xl = MergeXL::default();
+            for (timestamp, value) in [(Timestamp::CalendarYear(year_2019), 1.0), (Timestamp::FiscalYear(year_2019), 2.0)] {
+                let mut row = RowData::default();
+                row.populate(&column, value);
+                merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            }
+            merge_xl.write_to(destination.as_os_str(), &OutputOptions::default()).await.unwrap();
+        });
+        let calendar = std::fs::read_to_string(root.join("output-calendar-yearly.csv")).unwrap();
+        let fiscal = std::fs::read_to_string(root.join("output-fiscal-yearly.csv")).unwrap();
+        assert_eq!("timestamp-primary-key,Broad Money\n2019,1\n", calendar);
+        assert_eq!("timestamp-primary-key,Broad Money\n2019-20,2\n", fiscal);
+    }
+
+    #[test]
+    fn byte_order_mark_once() {
+        let root = TempDir::new("bom-output");
+        let destination = root.join("output");
+
+        task::block_on(async {
+            let merge_xl = MergeXL::default();
+            let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+            let mut row = RowData::default();
+            row.populate(&Column::new([ColumnLabel::create("Deposits (৳)").unwrap()]).unwrap(), 1.0);
+            merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            let options = OutputOptions { byte_order_mark: true, ..Default::default() };
+            merge_xl.write_to(destination.as_os_str(), &options).await.unwrap();
+        });
+        let written = std::fs::read(root.join("output-calendar-yearly.csv")).unwrap();
+        assert!(written.starts_with(UTF8_BYTE_ORDER_MARK));
+        assert_eq!(1, written.windows(UTF8_BYTE_ORDER_MARK.len()).filter(|w| *w == UTF8_BYTE_ORDER_MARK).count());
+        assert_eq!(
+            "timestamp-primary-key,Deposits (৳)\n2009,1\n",
+            std::str::from_utf8(&written[UTF8_BYTE_ORDER_MARK.len()..]).unwrap()
+        );
+    }
+
+    #[test]
+    fn write_sheet_to_buffer() {
+        let (written, absent) = task::block_on(async {
+            let merge_xl = MergeXL::default();
+            let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+            let mut row = RowData::default();
+            row.populate(&Column::new([ColumnLabel::create("Deposits").unwrap()]).unwrap(), 1.0);
+            merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            let options = OutputOptions { byte_order_mark: true, ..Default::default() };
+            let mut written = Vec::new();
+            assert!(merge_xl.write_sheet_to(Granularity::CalendarYear, &mut written, &options).await.unwrap());
+            let mut absent = Vec::new();
+            assert!(!merge_xl.write_sheet_to(Granularity::Monthly, &mut absent, &options).await.unwrap());
+            (written, absent)
+        });
+        assert_eq!(
+            [UTF8_BYTE_ORDER_MARK, b"timestamp-primary-key,Deposits\n2009,1\n"].concat(),
+            written
+        );
+        assert!(absent.is_empty());
+    }
+
+    #[test]
+    fn count_redundant_rows() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let (exports, imports) = (column("Exports"), column("Imports"));
+        let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+        let row = |cells: &[(&Column, f64)]| {
+            let mut row = RowData::default();
+            for (column, value) in cells {
+                row.populate(column, *value);
+            }
+            row
+        };
+        let sheet = Sheet::default();
+        sheet.add_row(timestamp, row(&[(&exports, 1.0), (&imports, 2.0)]));
+        // Identical, then a subset of what is there
+        sheet.add_row(timestamp, row(&[(&exports, 1.0), (&imports, 2.0)]));
+        sheet.add_row(timestamp, row(&[(&imports, 2.0)]));
+        assert_eq!(2, sheet.redundant_rows());
+        // A differing value is no redundancy
+        let mut revised = row(&[(&exports, 1.5)]);
+        revised.mark_revised();
+        sheet.add_row(timestamp, revised);
+        assert_eq!(2, sheet.redundant_rows());
+        let merged = sheet.get(&timestamp).unwrap();
+        assert_eq!(Some(&Value::Number(1.5)), merged.get(&exports));
+        assert!(merged.is_revised());
+    }
+
+    #[test]
+    fn settle_conflicting_values() {
+        let column = Column::new([ColumnLabel::create("Exports").unwrap()]).unwrap();
+        let combined = |conflicts: ConflictPolicy, first: Value, second: Value| {
+            let sheet = Sheet::with_conflict_policy(conflicts);
+            let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+            for value in [first, second] {
+                let mut row = RowData::default();
+                row.populate(&column, value);
+                sheet.add_row(timestamp, row);
+            }
+            let value = sheet.get(&timestamp).unwrap().get(&column).cloned();
+            value.unwrap()
+        };
+        let text = |text: &str| Value::Text(Box::from(text));
+        // Real values are kept over placeholders, either way around
+        for conflicts in [ConflictPolicy::PreferFilled, ConflictPolicy::Longer, ConflictPolicy::PreferNumbers] {
+            assert_eq!(Value::Number(1.5), combined(conflicts, Value::Number(1.5), text("NA")));
+            assert_eq!(Value::Number(1.5), combined(conflicts, text("-"), Value::Number(1.5)));
+            assert_eq!(Value::Number(1.5), combined(conflicts, Value::Number(1.5), Value::Empty));
+        }
+        assert_eq!(text("NA"), combined(ConflictPolicy::Latest, Value::Number(1.5), text("NA")));
+        // Between real values
+        assert_eq!(Value::Number(2.0), combined(ConflictPolicy::PreferFilled, Value::Number(1.5), Value::Number(2.0)));
+        assert_eq!(Value::Number(1.25), combined(ConflictPolicy::Longer, Value::Number(1.25), Value::Number(2.0)));
+        assert_eq!(Value::Number(1.5), combined(ConflictPolicy::PreferNumbers, Value::Number(1.5), text("n/a*")));
+        assert_eq!(Value::Number(1.5), combined(ConflictPolicy::PreferNumbers, text("n/a*"), Value::Number(1.5)));
+    }
+
+    #[test]
+    fn concurrent_rows_combine() {
+        let sheet = Sheet::default();
+        let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+        let columns = (0..32)
+            .map(|n| Column::new([ColumnLabel::create(&format!("Column {}", n + 100)).unwrap()]).unwrap())
+            .collect::<Vec<_>>();
+        std::thread::scope(|scope| {
+            for column in &columns {
+                let sheet = &sheet;
+                scope.spawn(move || {
+                    let mut row = RowData::default();
+                    row.populate(column, 1.0);
+                    sheet.add_row(timestamp, row);
+                });
+            }
+        });
+        assert_eq!(columns.len(), sheet.rows.get(&timestamp).unwrap().len());
+    }
+
+    #[test]
+    fn load_report_json() {
+        let statuses = vec![
+            FileStatus::HiddenFile,
+            FileStatus::XlsUnsupported(PathBuf::from("data/old.xls")),
+            FileStatus::PasswordProtected(PathBuf::from("data/locked.xlsx")),
+            FileStatus::Merged(FileReport {
+                path: PathBuf::from("data/monetary.xlsx"),
+                merged: vec![
+                    SheetRows { sheet: String::from("Deposits"), rows: 12 },
+                    SheetRows { sheet: String::from("Notes"), rows: 0 }
+                ],
+                errors: vec![SheetError {
+                    sheet: String::from("Treasury"),
+                    kind: AnalysisErrorKind::Unsupported,
+                    reason: String::from("Daily timestamps")
+                }]
+            }),
+            FileStatus::Merged(FileReport {
+                path: PathBuf::from("data/trade.xlsx"),
+                merged: vec![SheetRows { sheet: String::from("Exports"), rows: 4 }],
+                errors: vec![]
+            })
+        ];
+        assert_eq!(
+            [false, false, false, false, true],
+            statuses.iter().map(FileStatus::is_success).collect::<Vec<_>>().as_slice()
+        );
+        assert_eq!((12, 4), (statuses[3].rows_added(), statuses[4].rows_added()));
+        assert_eq!(1, statuses[3].errors().len());
+        assert!(statuses[0].errors().is_empty());
+        let report = LoadReport::from_statuses(statuses);
+        assert_eq!(
+            "2 files or sheets failed to load in strict mode:\n  data/old.xls: XLS files are unsupported\
+            \n  data/monetary.xlsx: Treasury: Format unsupported: Daily timestamps",
+            report.ensure_strict().unwrap_err().to_string()
+        );
+        assert_eq!(
+            serde_json::json!({
+                "loaded_files": 2,
+                "loaded_sheets": 3,
+                "merged_sheets": [
+                    {"file": "data/monetary.xlsx", "sheet": "Deposits", "rows": 12},
+                    {"file": "data/monetary.xlsx", "sheet": "Notes", "rows": 0},
+                    {"file": "data/trade.xlsx", "sheet": "Exports", "rows": 4}
+                ],
+                "xls_unsupported": ["data/old.xls"],
+                "password_protected": ["data/locked.xlsx"],
+                "error_counts": {"unsupported": 1, "no_data": 0, "other_failure": 0},
+                "redundant_rows": {},
+                "cached_files": 0,
+                "errors": [{
+                    "file": "data/monetary.xlsx",
+                    "sheet": "Treasury",
+                    "kind": "Unsupported",
+                    "reason": "Daily timestamps"
+                }]
+            }),
+            serde_json::to_value(&report).unwrap()
+        );
+        assert_eq!(
+            "Treasury: Format unsupported: Daily timestamps",
+            report.errors[0].error.to_string()
+        );
+        assert_eq!((2, 3), (report.loaded_files(), report.loaded_sheets()));
+        assert_eq!(
+            [1, 0, 0],
+            AnalysisErrorKind::ALL.map(|kind| report.error_count(kind))
+        );
+        assert_eq!(
+            vec![("data/monetary.xlsx", "Notes")],
+            report.empty_sheets().map(|(file, sheet)| (file, sheet.sheet())).collect::<Vec<_>>()
+        );
+        assert_eq!(["data/locked.xlsx"], report.password_protected());
+        assert_eq!(
+            vec![("data/monetary.xlsx", AnalysisErrorKind::Unsupported)],
+            report.errors().map(|(file, error)| (file, error.kind())).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reload_written_csv() {
+        let root = TempDir::new("reload-output");
+        let destination = root.join("output");
+        let money = Column::new(["Money", "Broad"].map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let note = Column::new([ColumnLabel::create("Note").unwrap()]).unwrap();
+        let year = Year(NonZeroU16::new(2008).unwrap());
+        let fiscal = Timestamp::FiscalYear(year);
+        let october = Timestamp::Monthly(MonthlyReport::new(year, Month::October));
 
+        let reloaded = task::block_on(async {
+            let merge_xl = MergeXL::default();
+            for timestamp in [fiscal, october] {
+                let mut row = RowData::default();
+                row.populate(&money, 12.5);
+                merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            }
+            let mut row = RowData::default();
+            row.populate(&note, "revised");
+            let next_fiscal = Timestamp::FiscalYear(year.next().unwrap());
+            merge_xl.get_or_create_sheet(&next_fiscal).await.add_row(next_fiscal, row);
+            // The leading date column is skipped when reloading
+            let options = OutputOptions { byte_order_mark: true, iso_date_column: true, ..Default::default() };
+            merge_xl.write_to(destination.as_os_str(), &options).await.unwrap();
 
+            let reloaded = MergeXL::default();
+            for suffix in ["-fiscal-yearly.csv", "-monthly.csv"] {
+                let mut path = destination.as_os_str().to_os_string();
+                path.push(suffix);
+                reloaded.load_csv(Path::new(&path), ".").await.unwrap();
+            }
+            let mut unknown = destination.as_os_str().to_os_string();
+            unknown.push("-unknown.csv");
+            assert!(reloaded.load_csv(Path::new(&unknown), ".").await.is_err());
+            reloaded
+        });
+
+        let fiscal_sheet = task::block_on(reloaded.get_or_create_sheet(&fiscal));
+        let row = fiscal_sheet.rows.get(&fiscal).unwrap();
+        assert_eq!(Some(&Value::Number(12.5)), row.get(&money));
+        // The placeholder for missing values is not read back
+        assert_eq!(None, row.get(&note));
+        let next_row = fiscal_sheet.rows.get(&fiscal.next().unwrap()).unwrap();
+        assert_eq!(Some(&Value::from("revised")), next_row.get(&note));
+        let monthly_sheet = task::block_on(reloaded.get_or_create_sheet(&october));
+        assert_eq!(Some(&Value::Number(12.5)), monthly_sheet.rows.get(&october).unwrap().get(&money));
+    }
+
+    #[test]
+    fn normalize_label_whitespace() {
+        let expected = ColumnLabel::create("Broad Money").unwrap();
+        for label in ["Broad  Money", " Broad\tMoney ", "Broad\u{00A0}Money", "Broad \u{00A0}\n Money"] {
+            assert_eq!(expected, ColumnLabel::create(label).unwrap(), "{:?}", label);
+        }
+        assert_eq!(None, ColumnLabel::create(" 12\u{00A0}"));
+    }
+
+    #[test]
+    fn reload_custom_separator() {
+        let root = TempDir::new("reload-separator");
+        let destination = root.join("output");
+        let securities = Column::new(["Govt. securities", "Treasury bills"].map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let year = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+
+        let (written, reloaded) = task::block_on(async {
+            let merge_xl = MergeXL::default();
+            let mut row = RowData::default();
+            row.populate(&securities, 7.5);
+            merge_xl.get_or_create_sheet(&year).await.add_row(year, row);
+            let options = OutputOptions { label_separator: String::from(" > "), ..Default::default() };
+            merge_xl.write_to(destination.as_os_str(), &options).await.unwrap();
+
+            let path = root.join("output-calendar-yearly.csv");
+            let written = std::fs::read_to_string(&path).unwrap();
+            let reloaded = MergeXL::default();
+            reloaded.load_csv(Path::new(&path), " > ").await.unwrap();
+            (written, reloaded)
+        });
+
+        assert_eq!("timestamp-primary-key,Govt. securities > Treasury bills\n2009,7.5\n", written);
+        let sheet = task::block_on(reloaded.sheet(Granularity::CalendarYear)).unwrap();
+        assert_eq!(vec![securities.clone()], sheet.columns().collect::<Vec<_>>());
+        assert_eq!(Some(&Value::Number(7.5)), sheet.rows.get(&year).unwrap().get(&securities));
+    }
+
+    #[test]
+    fn detect_password_protected() {
+        let root = TempDir::new("protected");
+        let mut encrypted = SheetExtension::Xls.signature().to_vec();
+        encrypted.extend_from_slice(&[0; 504]);
+        std::fs::write(root.join("locked.xlsx"), &encrypted).unwrap();
+        std::fs::write(root.join("legacy.xls"), &encrypted).unwrap();
+        std::fs::write(root.join("broken.xlsx"), b"PK").unwrap();
+
+        let locked = blocking_load_all_sheets(Path::new(&root.join("locked.xlsx")), &SheetExclusions::default());
+        let is_protected = |name: &str| blocking_is_password_protected(Path::new(&root.join(name))).unwrap();
+        let (legacy, broken) = (is_protected("legacy.xls"), is_protected("broken.xlsx"));
+        let status = task::block_on(async {
+            let merge_xl = MergeXL::default();
+            let mut files = fs::read_dir(&*root).await.unwrap();
+            let mut status = None;
+            while let Some(file) = files.next().await.transpose().unwrap() {
+                if file.file_name() == "locked.xlsx" {
+                    status = Some(MergeFile { merge_xl: &merge_xl, cache: None, file }.merge().await.unwrap());
+                }
+            }
+            status
+        });
+        assert!(locked.unwrap().is_none());
+        assert!(!legacy);
+        assert!(!broken);
+        assert_matches!(status, Some(FileStatus::PasswordProtected(_)));
+    }
+
+    #[test]
+    fn iterate_sheets_in_order() {
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        let timestamps = [
+            Timestamp::Monthly(MonthlyReport::new(year, Month::July)), Timestamp::FiscalYear(year),
+            Timestamp::Quarterly(year, Quarter::AprMayJun), Timestamp::CalendarYear(year),
+            Timestamp::BiAnnually(year, HalfYear::JulThruDec)
+        ];
+        let expected = vec![
+            Granularity::CalendarYear, Granularity::FiscalYear, Granularity::BiAnnual,
+            Granularity::Quarterly, Granularity::Monthly
+        ];
+        // Whatever order the sheets are created in
+        for rotation in 0..timestamps.len() {
+            let merge_xl = MergeXL::default();
+            let sheets = task::block_on(async {
+                for timestamp in timestamps.iter().cycle().skip(rotation).take(timestamps.len()) {
+                    merge_xl.get_or_create_sheet(timestamp).await;
+                }
+                merge_xl.sheets().await
+            });
+            assert_eq!(expected, sheets.iter().map(|(granularity, _)| *granularity).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn inspect_loaded() {
+        let column = Column::new([ColumnLabel::create("Broad Money").unwrap()]).unwrap();
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        let merge_xl = MergeXL::default();
+        let sheets = task::block_on(async {
+            for timestamp in [Timestamp::Monthly(MonthlyReport::new(year, Month::July)), Timestamp::FiscalYear(year)] {
+                let mut row = RowData::default();
+                row.populate(&column, 1.0);
+                merge_xl.get_or_create_sheet(&timestamp).await.add_row(timestamp, row);
+            }
+            assert_eq!(2, merge_xl.sheet_count().await);
+            merge_xl.sheets().await
+        });
+        assert_eq!(
+            vec![Granularity::FiscalYear, Granularity::Monthly],
+            sheets.iter().map(|(granularity, _)| *granularity).collect::<Vec<_>>()
+        );
+        let fiscal = &sheets[0].1;
+        assert_eq!(1, fiscal.row_count());
+        assert_eq!(vec![column.clone()], fiscal.columns().collect::<Vec<_>>());
+        assert_eq!(Some(&Value::Number(1.0)), fiscal.get(&Timestamp::FiscalYear(year)).unwrap().get(&column));
+        assert!(fiscal.get(&Timestamp::CalendarYear(year)).is_none());
+    }
+
+    #[test]
+    fn write_shared_sheets() {
+        let root = TempDir::new("shared-output");
+        let timestamp = Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap()));
+
+        let written = task::block_on(async {
+            let merge_xl = MergeXL::default();
+            // Keep a clone of the sheet alive while writing
+            let sheet = merge_xl.get_or_create_sheet(&timestamp).await;
+            let mut row = RowData::default();
+            row.populate(&Column::new([ColumnLabel::create("Broad Money").unwrap()]).unwrap(), 1.0);
+            sheet.add_row(timestamp, row);
+            merge_xl.write_to(root.join("output").as_os_str(), &OutputOptions::default()).await
+        });
+        let content = std::fs::read_to_string(root.join("output-calendar-yearly.csv"));
+        written.unwrap();
+        assert_eq!("timestamp-primary-key,Broad Money\n2009,1\n", content.unwrap());
+    }
+}
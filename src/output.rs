@@ -0,0 +1,1214 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Output
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use async_std::io::WriteExt;
+use futures_io::AsyncWrite;
+use serde_json::{Map, Value as JsonValue};
+use eyre::Result;
+use crate::common::{Granularity, MonthlyReport, Timestamp, Year};
+use crate::merge::{Column, RowData, RowKey, Sheet};
+use crate::quality;
+use crate::quality::ColumnType;
+use crate::value::{Unit, Value};
+
+/// Controls how the merged data is written
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    pub labeling: ColumnLabeling,
+    /// The field delimiter. A comma by default. Ignored by the TSV format, which always
+    /// separates fields with tabs
+    pub delimiter: u8,
+    pub quoting: Quoting,
+    /// Whether to begin each file with a UTF-8 byte order mark. Excel needs one to recognize
+    /// non-ASCII labels, but other tools may choke on it
+    pub byte_order_mark: bool,
+    /// Whether to write a manifest describing every column of every sheet
+    pub manifest: bool,
+    /// Whether to write a report of the periods each column covers, and its gaps
+    pub coverage_report: bool,
+    /// If set, quarters are checked to sum to their years within this relative tolerance,
+    /// and the years which do not are reported
+    pub sum_check_tolerance: Option<f64>,
+    pub fiscal_years: FiscalYearFormat,
+    pub months: MonthlyFormat,
+    pub periods: PeriodFormat,
+    /// Whether to begin each row with the ISO 8601 date its period starts on, for tools which
+    /// sort or join on dates. Only sheets keyed by timestamps have dates
+    pub iso_date_column: bool,
+    /// Whether to write the rows of every granularity into one file, tagged with their
+    /// granularity, instead of one file per granularity
+    pub combined: bool,
+    /// Whether to write each top-level category of columns, such as Money or Prices, to its
+    /// own file per granularity. Ignored if the output is combined
+    pub split_by_category: bool,
+    /// Whether to follow each key with whether its row holds provisional figures
+    pub provisional_column: bool,
+    /// Whether to follow each key with whether its timestamp or any of its figures was
+    /// marked as revised, for comparing vintages
+    pub revised_column: bool,
+    /// Whether to follow each key with the columns whose values were filled in rather than
+    /// observed. In the long format, each value is flagged instead
+    pub filled_column: bool,
+    /// Joins the levels of column labels in single-row headers. A dot by default, but labels
+    /// may contain dots themselves, as in Govt. securities
+    pub label_separator: String
+}
+
+impl Default for OutputOptions {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::default(),
+            labeling: ColumnLabeling::default(),
+            delimiter: b',',
+            quoting: Quoting::default(),
+            byte_order_mark: false,
+            manifest: false,
+            coverage_report: false,
+            sum_check_tolerance: None,
+            fiscal_years: FiscalYearFormat::default(),
+            months: MonthlyFormat::default(),
+            periods: PeriodFormat::default(),
+            iso_date_column: false,
+            combined: false,
+            split_by_category: false,
+            provisional_column: false,
+            revised_column: false,
+            filled_column: false,
+            label_separator: String::from(".")
+        }
+    }
+}
+
+impl OutputOptions {
+    pub(crate) fn csv_writer<W>(&self, writer: W) -> csv_async::AsyncWriter<W> where W: AsyncWrite + Unpin {
+        csv_async::AsyncWriterBuilder::new()
+            .delimiter(self.field_delimiter())
+            .quote_style(self.quoting.into())
+            .create_writer(writer)
+    }
+
+    /// The delimiter between fields in the output format
+    fn field_delimiter(&self) -> u8 {
+        match self.format {
+            OutputFormat::Tsv => b'\t',
+            _ => self.delimiter
+        }
+    }
+
+    /// The extension of files written in the output format
+    pub(crate) fn extension(&self) -> &'static str {
+        match self.format {
+            OutputFormat::Wide | OutputFormat::Long => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Jsonl => "jsonl"
+        }
+    }
+
+    /// Displays a column's label categorization, joined with the chosen separator
+    pub(crate) fn display_column(&self, column: &Column) -> String {
+        column.display_labeling_with(&self.label_separator)
+    }
+
+    /// Displays a timestamp, with fiscal years, half-years, quarters, and months in the
+    /// chosen formats
+    pub(crate) fn display_timestamp(&self, timestamp: &Timestamp) -> String {
+        match (timestamp, self.periods) {
+            (Timestamp::FiscalYear(year), _) => self.fiscal_years.display(*year),
+            (Timestamp::Monthly(report), _) => self.months.display(report),
+            (Timestamp::BiAnnually(year, half_year), PeriodFormat::Compact) => {
+                format!("{}-H{}", year, *half_year as u8 + 1)
+            },
+            (Timestamp::Quarterly(year, quarter), PeriodFormat::Compact) => {
+                format!("{}-Q{}", year, *quarter as u8 + 1)
+            },
+            (other, _) => other.to_string()
+        }
+    }
+}
+
+/// How fiscal years, which run from July to June, are written. Condensed files are read back
+/// only in the default format
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FiscalYearFormat {
+    /// The starting year and the last two digits of the ending year, e.g. 2009-10
+    #[default]
+    Hyphenated,
+    /// As above with a slash, e.g. 2009/10
+    Slashed,
+    /// FY and the ending year, e.g. FY2010
+    EndingYear,
+    /// Only the starting year, e.g. 2009
+    StartingYear
+}
+
+impl FiscalYearFormat {
+    /// Displays the fiscal year starting in the given year
+    pub fn display(&self, start: Year) -> String {
+        let end = start.0.get() + 1;
+        match self {
+            Self::Hyphenated => format!("{}-{:02}", start, end % 100),
+            Self::Slashed => format!("{}/{:02}", start, end % 100),
+            Self::EndingYear => format!("FY{}", end),
+            Self::StartingYear => start.to_string()
+        }
+    }
+}
+
+/// How months are written. Condensed files are read back in any of these formats
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum MonthlyFormat {
+    /// The year and two-digit month, e.g. 2009-01
+    #[default]
+    Hyphenated,
+    /// The ISO 8601 date of the first day of the month, e.g. 2009-01-01
+    IsoDate,
+    /// The three-letter month and year, e.g. Jan 2009
+    Named,
+    /// The year, M, and two-digit month, e.g. 2009M01
+    Compact
+}
+
+impl MonthlyFormat {
+    pub fn display(&self, report: &MonthlyReport) -> String {
+        let (year, month) = (report.year, report.month);
+        match self {
+            Self::Hyphenated => report.to_string(),
+            Self::IsoDate => format!("{}-01", report),
+            Self::Named => format!("{} {}", &month.name()[0..3], year),
+            Self::Compact => format!("{}M{:02}", year, month.as_numeric())
+        }
+    }
+}
+
+/// How half-years and quarters are written. Condensed files are read back in either format
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PeriodFormat {
+    /// The year and the months spanned, e.g. 2009 Jan-Jun and 2014 Jul-Sep
+    #[default]
+    Verbose,
+    /// The year and the number of the half-year or quarter, e.g. 2009-H1 and 2014-Q3. These
+    /// sort lexically
+    Compact
+}
+
+impl FromStr for PeriodFormat {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "verbose" => Ok(Self::Verbose),
+            "compact" => Ok(Self::Compact),
+            other => Err(eyre::eyre!("Unknown period format {}. Use verbose or compact", other))
+        }
+    }
+}
+
+impl FromStr for MonthlyFormat {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "hyphenated" => Ok(Self::Hyphenated),
+            "iso-date" => Ok(Self::IsoDate),
+            "named" => Ok(Self::Named),
+            "compact" => Ok(Self::Compact),
+            other => Err(eyre::eyre!("Unknown monthly format {}. Use hyphenated, iso-date, named, or compact", other))
+        }
+    }
+}
+
+impl FromStr for FiscalYearFormat {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "hyphenated" => Ok(Self::Hyphenated),
+            "slashed" => Ok(Self::Slashed),
+            "ending-year" => Ok(Self::EndingYear),
+            "starting-year" => Ok(Self::StartingYear),
+            other => Err(eyre::eyre!(
+                "Unknown fiscal year format {}. Use hyphenated, slashed, ending-year, or starting-year", other
+            ))
+        }
+    }
+}
+
+/// When fields are quoted
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Quoting {
+    /// Only fields containing the delimiter, quotes, or line breaks are quoted
+    #[default]
+    Necessary,
+    Always,
+    /// Fields are never quoted. Beware: the output may not be readable if labels or values
+    /// contain the delimiter
+    Never
+}
+
+impl From<Quoting> for csv_async::QuoteStyle {
+    fn from(value: Quoting) -> Self {
+        match value {
+            Quoting::Necessary => Self::Necessary,
+            Quoting::Always => Self::Always,
+            Quoting::Never => Self::Never
+        }
+    }
+}
+
+impl FromStr for Quoting {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "necessary" => Ok(Self::Necessary),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            other => Err(eyre::eyre!("Unknown quoting {}. Use necessary, always, or never", other))
+        }
+    }
+}
+
+/// Parses a delimiter, which is a single ASCII character or the word "tab"
+pub fn parse_delimiter(value: &str) -> Result<u8> {
+    match value {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        other => Err(eyre::eyre!("Invalid delimiter {}. Use a single character or 'tab'", other))
+    }
+}
+
+/// The shape of the output tables
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One row per timestamp, one column per data column. Missing cells are written as NA
+    #[default]
+    Wide,
+    /// One row per populated cell, in the form (timestamp, column, value). Suited to
+    /// plotting libraries which expect tidy data. Missing cells are skipped entirely.
+    Long,
+    /// The wide format separated by tabs, since labels often contain commas. Fields are
+    /// quoted as in CSV, which happens only for the few containing tabs, quotes, or line
+    /// breaks, unless another quoting is chosen
+    Tsv,
+    /// Newline-delimited JSON, one object per row holding its key and populated columns.
+    /// Suited to loading into databases. Missing cells are skipped entirely
+    Jsonl
+}
+
+/// How column labels appear in the header
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ColumnLabeling {
+    /// A single header row, where each column's label categorization is joined with dots
+    #[default]
+    Dotted,
+    /// One header row per level of label categorization (level1, level2, ...), up to the
+    /// deepest categorization in the sheet. Shorter categorizations are padded with blanks.
+    Hierarchical
+}
+
+impl FromStr for OutputFormat {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "wide" => Ok(Self::Wide),
+            "long" => Ok(Self::Long),
+            "tsv" => Ok(Self::Tsv),
+            "jsonl" => Ok(Self::Jsonl),
+            other => Err(eyre::eyre!("Unknown output format {}. Use wide, long, tsv, or jsonl", other))
+        }
+    }
+}
+
+impl FromStr for ColumnLabeling {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "dotted" => Ok(Self::Dotted),
+            "hierarchical" => Ok(Self::Hierarchical),
+            other => Err(eyre::eyre!("Unknown column labeling {}. Use dotted or hierarchical", other))
+        }
+    }
+}
+
+/// Placeholder for data which is not available
+pub(crate) const MISSING_VALUE: &str = "NA";
+/// The header of the optional column of ISO 8601 dates
+pub(crate) const DATE_HEADER: &str = "date";
+/// The header of the optional column flagging provisional rows
+pub(crate) const PROVISIONAL_HEADER: &str = "provisional";
+/// The header of the optional column flagging revised rows
+pub(crate) const REVISED_HEADER: &str = "revised";
+/// The header of the optional column marking filled values
+pub(crate) const FILLED_HEADER: &str = "filled";
+/// The header of the column naming each row's granularity in combined output
+pub(crate) const GRANULARITY_HEADER: &str = "granularity";
+
+/// Writes a single sheet in the output format. Rows are written in the order of their keys,
+/// chronological for timestamps
+pub(crate) async fn write_sheet<W, K>(writer: W, sheet: &Sheet<K>, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    match options.format {
+        OutputFormat::Wide | OutputFormat::Tsv => write_wide(options.csv_writer(writer), sheet, options).await,
+        OutputFormat::Long => write_long(options.csv_writer(writer), sheet, options).await,
+        OutputFormat::Jsonl => write_jsonl(writer, sheet, options).await
+    }
+}
+
+/// Writes each row as a JSON object on its own line, as soon as it is formed. Objects are
+/// named like the headers of the wide format
+async fn write_jsonl<W, K>(mut writer: W, sheet: &Sheet<K>, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    for key in sheet.keys_in_order() {
+        let Some(data) = sheet.rows.get(&key) else {
+            continue;
+        };
+        let mut object = row_object(sheet, &key, &data, options);
+        if options.iso_date_column && K::DATED {
+            object.insert(String::from(DATE_HEADER), key.iso_start_date().into());
+        }
+        write_json_line(&mut writer, object).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// The key, flags, and populated columns of a row as a JSON object. Numbers stay numbers
+fn row_object<K>(sheet: &Sheet<K>, key: &K, data: &RowData, options: &OutputOptions) -> Map<String, JsonValue>
+    where K: RowKey {
+
+    let mut object = Map::new();
+    object.insert(String::from(K::HEADER), key.output_value(options).into());
+    if options.provisional_column {
+        object.insert(String::from(PROVISIONAL_HEADER), sheet.is_provisional(key).into());
+    }
+    if options.revised_column {
+        object.insert(String::from(REVISED_HEADER), data.is_revised().into());
+    }
+    if options.filled_column {
+        let filled_columns = data
+            .iter()
+            .filter(|(column, _)| data.is_filled(column))
+            .map(|(column, _)| JsonValue::from(options.display_column(column)))
+            .collect::<Vec<_>>();
+        object.insert(String::from(FILLED_HEADER), filled_columns.into());
+    }
+    for (column, value) in data.iter().filter(|(_, value)| !value.is_empty()) {
+        let value = match value {
+            Value::Number(number) => JsonValue::from(*number),
+            other => JsonValue::from(other.to_string())
+        };
+        object.insert(options.display_column(column), value);
+    }
+    object
+}
+
+async fn write_json_line<W>(writer: &mut W, object: Map<String, JsonValue>) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    let mut line = serde_json::to_vec(&object)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+    Ok(())
+}
+
+async fn write_wide<W, K>(mut writer: csv_async::AsyncWriter<W>, sheet: &Sheet<K>, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    let columns = sheet.columns.iter().map(|column| column.key().clone()).collect::<Vec<_>>();
+    let dated = options.iso_date_column && K::DATED;
+    let flagged = options.provisional_column;
+    let revised = options.revised_column;
+    let filled = options.filled_column;
+    let record_length = columns.len() + 1 + usize::from(dated) + usize::from(flagged) + usize::from(revised) + usize::from(filled);
+
+    // Write the header
+    match options.labeling {
+        ColumnLabeling::Dotted => {
+            let mut header = Vec::with_capacity(record_length);
+            if dated {
+                header.push(String::from(DATE_HEADER));
+            }
+            header.push(String::from(K::HEADER));
+            if flagged {
+                header.push(String::from(PROVISIONAL_HEADER));
+            }
+            if revised {
+                header.push(String::from(REVISED_HEADER));
+            }
+            if filled {
+                header.push(String::from(FILLED_HEADER));
+            }
+            for column in &columns {
+                header.push(options.display_column(column));
+            }
+            writer.write_record(&header).await?;
+        }
+        ColumnLabeling::Hierarchical => {
+            let depth = columns.iter().map(Column::depth).max().unwrap_or(0);
+            for level in 0..depth {
+                let mut header = Vec::with_capacity(record_length);
+                if dated {
+                    header.push(String::new());
+                }
+                header.push(format!("level{}", level + 1));
+                // Flags are named on the level nearest the data
+                let last_level = level + 1 == depth;
+                if flagged {
+                    header.push(if last_level { String::from(PROVISIONAL_HEADER) } else { String::new() });
+                }
+                if revised {
+                    header.push(if last_level { String::from(REVISED_HEADER) } else { String::new() });
+                }
+                if filled {
+                    header.push(if last_level { String::from(FILLED_HEADER) } else { String::new() });
+                }
+                for column in &columns {
+                    header.push(column.label_at(level).unwrap_or_default().to_string());
+                }
+                writer.write_record(&header).await?;
+            }
+        }
+    }
+
+    // Write all the data
+    for key in sheet.keys_in_order() {
+        let Some(data) = sheet.rows.get(&key) else {
+            continue;
+        };
+        let mut record = Vec::with_capacity(record_length);
+
+        // Key comes first, after its date
+        if dated {
+            record.push(key.iso_start_date().unwrap_or_default());
+        }
+        record.push(key.output_value(options));
+        if flagged {
+            record.push(sheet.is_provisional(&key).to_string());
+        }
+        if revised {
+            record.push(data.is_revised().to_string());
+        }
+        if filled {
+            let filled_columns = columns
+                .iter()
+                .filter(|column| data.is_filled(column))
+                .map(|column| options.display_column(column))
+                .collect::<Vec<_>>();
+            record.push(filled_columns.join("; "));
+        }
+        // Then the regular data columns
+        for column in &columns {
+            let item = match data.get(column) {
+                Some(value) if !value.is_empty() => value.to_string(),
+                _ => String::from(MISSING_VALUE)
+            };
+            record.push(item);
+        }
+        writer.write_record(record).await?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn write_long<W, K>(mut writer: csv_async::AsyncWriter<W>, sheet: &Sheet<K>, options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin, K: RowKey {
+
+    // With hierarchical labeling, each level of the column label gets its own field
+    let depth = match options.labeling {
+        ColumnLabeling::Dotted => 1,
+        ColumnLabeling::Hierarchical => sheet.columns.iter().map(|column| column.depth()).max().unwrap_or(0)
+    };
+    let dated = options.iso_date_column && K::DATED;
+    let mut header = Vec::with_capacity(depth + 3);
+    if dated {
+        header.push(String::from(DATE_HEADER));
+    }
+    header.push(String::from(K::HEADER));
+    if options.provisional_column {
+        header.push(String::from(PROVISIONAL_HEADER));
+    }
+    if options.revised_column {
+        header.push(String::from(REVISED_HEADER));
+    }
+    match options.labeling {
+        ColumnLabeling::Dotted => header.push(String::from("column")),
+        ColumnLabeling::Hierarchical => header.extend((0..depth).map(|level| format!("level{}", level + 1)))
+    }
+    header.push(String::from("value"));
+    if options.filled_column {
+        header.push(String::from(FILLED_HEADER));
+    }
+    writer.write_record(&header).await?;
+
+    for key in sheet.keys_in_order() {
+        let Some(data) = sheet.rows.get(&key) else {
+            continue;
+        };
+        let date = key.iso_start_date().filter(|_| dated);
+        let provisional = Some(sheet.is_provisional(&key).to_string()).filter(|_| options.provisional_column);
+        let revised = Some(data.is_revised().to_string()).filter(|_| options.revised_column);
+        let key = key.output_value(options);
+        for (column, value) in data.iter().filter(|(_, value)| !value.is_empty()) {
+            let mut record = Vec::with_capacity(depth + 3);
+            record.extend(date.clone());
+            record.push(key.clone());
+            record.extend(provisional.clone());
+            record.extend(revised.clone());
+            match options.labeling {
+                ColumnLabeling::Dotted => record.push(options.display_column(column)),
+                ColumnLabeling::Hierarchical => record.extend((0..depth).map(|level| {
+                    column.label_at(level).unwrap_or_default().to_string()
+                }))
+            }
+            record.push(value.to_string());
+            if options.filled_column {
+                record.push(data.is_filled(column).to_string());
+            }
+            writer.write_record(&record).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes the rows of every sheet into one file, from the longest granularity to the shortest.
+/// Each row is tagged with its granularity and the ISO 8601 date its period starts on. The
+/// columns are the union of every sheet's, in order of their labels, and cells which a sheet
+/// lacks are missing
+pub(crate) async fn write_combined<W>(writer: W, sheets: &[(Granularity, &Sheet)], options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    if options.format == OutputFormat::Jsonl {
+        return write_combined_jsonl(writer, sheets, options).await;
+    }
+    let mut writer = options.csv_writer(writer);
+    let mut columns = sheets.iter()
+        .flat_map(|(_, sheet)| sheet.columns())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    columns.sort_by_cached_key(Column::display_full_labeling);
+
+    // The fields leading every row, and their headers
+    let mut leading_headers = vec![GRANULARITY_HEADER, DATE_HEADER, Timestamp::HEADER];
+    if options.provisional_column {
+        leading_headers.push(PROVISIONAL_HEADER);
+    }
+    if options.revised_column {
+        leading_headers.push(REVISED_HEADER);
+    }
+    let leading_fields = |granularity: Granularity, sheet: &Sheet, key: &Timestamp, data: &RowData| {
+        let mut fields = vec![granularity.to_string(), key.iso_start_date().unwrap_or_default(), key.output_value(options)];
+        if options.provisional_column {
+            fields.push(sheet.is_provisional(key).to_string());
+        }
+        if options.revised_column {
+            fields.push(data.is_revised().to_string());
+        }
+        fields
+    };
+    let depth = match options.labeling {
+        ColumnLabeling::Dotted => 1,
+        ColumnLabeling::Hierarchical => columns.iter().map(Column::depth).max().unwrap_or(0)
+    };
+
+    match options.format {
+        OutputFormat::Wide | OutputFormat::Tsv => {
+            match options.labeling {
+                ColumnLabeling::Dotted => {
+                    let mut header = leading_headers.iter().map(|header| header.to_string()).collect::<Vec<_>>();
+                    header.extend(columns.iter().map(|column| options.display_column(column)));
+                    writer.write_record(&header).await?;
+                }
+                ColumnLabeling::Hierarchical => {
+                    // The leading fields are named on the level nearest the data
+                    for level in 0..depth {
+                        let mut header = leading_headers.iter()
+                            .map(|header| if level + 1 == depth { header.to_string() } else { String::new() })
+                            .collect::<Vec<_>>();
+                        header.extend(columns.iter().map(|column| column.label_at(level).unwrap_or_default().to_string()));
+                        writer.write_record(&header).await?;
+                    }
+                }
+            }
+            for (granularity, sheet) in sheets {
+                for key in sheet.keys_in_order() {
+                    let Some(data) = sheet.rows.get(&key) else {
+                        continue;
+                    };
+                    let mut record = leading_fields(*granularity, sheet, &key, &data);
+                    record.extend(columns.iter().map(|column| match data.get(column) {
+                        Some(value) if !value.is_empty() => value.to_string(),
+                        _ => String::from(MISSING_VALUE)
+                    }));
+                    writer.write_record(record).await?;
+                }
+            }
+        }
+        OutputFormat::Long => {
+            let mut header = leading_headers.iter().map(|header| header.to_string()).collect::<Vec<_>>();
+            match options.labeling {
+                ColumnLabeling::Dotted => header.push(String::from("column")),
+                ColumnLabeling::Hierarchical => header.extend((0..depth).map(|level| format!("level{}", level + 1)))
+            }
+            header.push(String::from("value"));
+            writer.write_record(&header).await?;
+
+            for (granularity, sheet) in sheets {
+                for key in sheet.keys_in_order() {
+                    let Some(data) = sheet.rows.get(&key) else {
+                        continue;
+                    };
+                    let leading = leading_fields(*granularity, sheet, &key, &data);
+                    for (column, value) in data.iter().filter(|(_, value)| !value.is_empty()) {
+                        let mut record = leading.clone();
+                        match options.labeling {
+                            ColumnLabeling::Dotted => record.push(options.display_column(column)),
+                            ColumnLabeling::Hierarchical => record.extend((0..depth).map(|level| {
+                                column.label_at(level).unwrap_or_default().to_string()
+                            }))
+                        }
+                        record.push(value.to_string());
+                        writer.write_record(&record).await?;
+                    }
+                }
+            }
+        }
+        OutputFormat::Jsonl => unreachable!("Written above")
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes the rows of every sheet as JSON objects, tagged like the rows of combined CSV
+async fn write_combined_jsonl<W>(mut writer: W, sheets: &[(Granularity, &Sheet)], options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    for (granularity, sheet) in sheets {
+        for key in sheet.keys_in_order() {
+            let Some(data) = sheet.rows.get(&key) else {
+                continue;
+            };
+            let mut object = row_object(sheet, &key, &data, options);
+            object.insert(String::from(GRANULARITY_HEADER), granularity.to_string().into());
+            object.insert(String::from(DATE_HEADER), key.iso_start_date().into());
+            write_json_line(&mut writer, object).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes a data dictionary listing each sheet's columns, their full label categorization,
+/// how many rows have a value for the column, whether its values are numbers or text, and the
+/// units its figures were converted from
+pub(crate) async fn write_manifest<W>(writer: W, sheets: &[(String, &Sheet)],
+                                      options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    let mut writer = options.csv_writer(writer);
+    let depth = sheets
+        .iter()
+        .flat_map(|(_, sheet)| sheet.columns.iter().map(|column| column.depth()).collect::<Vec<_>>())
+        .max()
+        .unwrap_or(0);
+
+    let mut header = vec![String::from("sheet"), String::from("column")];
+    header.extend((0..depth).map(|level| format!("level{}", level + 1)));
+    header.push(String::from("filled-rows"));
+    header.push(String::from("percentage"));
+    header.push(String::from("type"));
+    header.push(String::from("unit"));
+    writer.write_record(&header).await?;
+
+    for (sheet_name, sheet) in sheets {
+        let mut fill_counts = HashMap::new();
+        for row in sheet.rows.iter() {
+            for (column, _) in row.value().iter().filter(|(_, value)| !value.is_empty()) {
+                *fill_counts.entry(column.clone()).or_insert(0usize) += 1;
+            }
+        }
+        let types = quality::column_types(sheet);
+        for column in sheet.columns.iter() {
+            let column = column.key();
+            let mut record = Vec::with_capacity(header.len());
+            record.push(sheet_name.clone());
+            record.push(options.display_column(column));
+            record.extend((0..depth).map(|level| column.label_at(level).unwrap_or_default().to_string()));
+            record.push(fill_counts.get(column).copied().unwrap_or(0).to_string());
+            record.push(sheet.is_percentage(column).to_string());
+            record.push(types.get(column).copied().unwrap_or(ColumnType::Numeric).to_string());
+            record.push(sheet.units(column).iter().map(Unit::to_string).collect::<Vec<_>>().join("/"));
+            writer.write_record(&record).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes a report of the years whose quarters do not sum to them
+pub(crate) async fn write_sum_discrepancies<W>(writer: W, quarterly: &Sheet, yearly_sheets: &[(String, &Sheet)],
+                                               options: &OutputOptions, tolerance: f64) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    let mut writer = options.csv_writer(writer);
+    writer.write_record(["sheet", "year", "column", "yearly", "sum-of-quarters", "difference"]).await?;
+    for (sheet_name, yearly) in yearly_sheets {
+        let discrepancies = quality::check_quarterly_sums(quarterly, yearly, tolerance);
+        log::info!("{} years in {} differ from the sum of their quarters", discrepancies.len(), sheet_name);
+
+        for discrepancy in discrepancies {
+            writer.write_record([
+                sheet_name.clone(),
+                discrepancy.year.to_string(),
+                options.display_column(&discrepancy.column),
+                discrepancy.yearly.to_string(),
+                discrepancy.sum_of_quarters.to_string(),
+                (discrepancy.yearly - discrepancy.sum_of_quarters).to_string()
+            ]).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes a report on the time span covered by each column and the periods missing within it
+pub(crate) async fn write_coverage_report<W>(writer: W, sheets: &[(String, &Sheet)],
+                                             options: &OutputOptions) -> Result<()>
+    where W: AsyncWrite + Unpin {
+
+    let mut writer = options.csv_writer(writer);
+    writer.write_record([
+        "sheet", "column", "first", "last", "span-months", "present", "missing-count", "missing"
+    ]).await?;
+    for (sheet_name, sheet) in sheets {
+        let mut coverages = quality::coverage(sheet);
+        coverages.sort_by_key(|coverage| coverage.column.display_full_labeling());
+        let with_gaps = coverages.iter().filter(|coverage| !coverage.missing.is_empty()).count();
+        log::info!("{} of {} columns in {} have gaps", with_gaps, coverages.len(), sheet_name);
+
+        for coverage in coverages {
+            let missing = coverage.missing
+                .iter()
+                .map(|timestamp| options.display_timestamp(timestamp))
+                .collect::<Vec<_>>()
+                .join(" ");
+            writer.write_record([
+                sheet_name.clone(),
+                options.display_column(&coverage.column),
+                options.display_timestamp(&coverage.first),
+                options.display_timestamp(&coverage.last),
+                coverage.span_in_months().to_string(),
+                coverage.present.to_string(),
+                coverage.missing.len().to_string(),
+                missing
+            ]).await?;
+        }
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use async_std::task;
+    use crate::common::*;
+    use crate::merge::{ColumnLabel, RowData};
+    use crate::value::ValueOptions;
+    use super::*;
+
+    fn column(labels: &[&str]) -> Column {
+        Column::new(labels.iter().map(|label| ColumnLabel::create(label).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn hierarchical_header() {
+        let sheet = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&column(&["Money", "Broad Money"]), "12.5");
+        sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap())), row);
+
+        let options = OutputOptions { labeling: ColumnLabeling::Hierarchical, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        assert_eq!(
+            "level1,Money\nlevel2,Broad Money\n2009,12.5\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn long_format_skips_missing() {
+        let sheet = Sheet::default();
+        let year_2009 = Year(NonZeroU16::new(2009).unwrap());
+        let mut row = RowData::default();
+        row.populate(&column(&["Money"]), "12.5");
+        sheet.add_row(Timestamp::CalendarYear(year_2009), row);
+        let mut row = RowData::default();
+        row.populate(&column(&["Prices"]), "104");
+        sheet.add_row(Timestamp::FiscalYear(year_2009), row);
+
+        let options = OutputOptions { format: OutputFormat::Long, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines().collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(vec!["2009,Money,12.5", "2009-10,Prices,104", "timestamp-primary-key,column,value"], lines);
+    }
+
+    #[test]
+    fn jsonl_lines_stand_alone() {
+        let sheet = Sheet::default();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let mut row = RowData::default();
+        row.populate(&column(&["Money", "Broad Money"]), "12.5");
+        row.populate(&column(&["Note"]), "Estimated, \"provisional\"");
+        sheet.add_row(year(2010), row);
+        let mut row = RowData::default();
+        row.populate(&column(&["Money", "Broad Money"]), "11");
+        sheet.add_row(year(2009), row);
+
+        let options = OutputOptions { format: OutputFormat::Jsonl, iso_date_column: true, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![
+            serde_json::json!({"date": "2009-01-01", "timestamp-primary-key": "2009", "Money.Broad Money": 11.0}),
+            serde_json::json!({
+                "date": "2010-01-01", "timestamp-primary-key": "2010",
+                "Money.Broad Money": 12.5, "Note": "Estimated, \"provisional\""
+            })
+        ], lines);
+        assert_eq!("jsonl", options.extension());
+    }
+
+    #[test]
+    fn fiscal_year_formats() {
+        let sheet = Sheet::default();
+        for year in [2009, 2099] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Prices"]), "104");
+            sheet.add_row(Timestamp::FiscalYear(Year(NonZeroU16::new(year).unwrap())), row);
+        }
+        let write = |fiscal_years| {
+            let options = OutputOptions { fiscal_years, ..Default::default() };
+            let mut output = Vec::new();
+            task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let header = "timestamp-primary-key,Prices\n";
+        assert_eq!(format!("{}2009-10,104\n2099-00,104\n", header), write(FiscalYearFormat::Hyphenated));
+        assert_eq!(format!("{}2009/10,104\n2099/00,104\n", header), write(FiscalYearFormat::Slashed));
+        assert_eq!(format!("{}FY2010,104\nFY2100,104\n", header), write(FiscalYearFormat::EndingYear));
+        assert_eq!(format!("{}2009,104\n2099,104\n", header), write(FiscalYearFormat::StartingYear));
+        assert_eq!(FiscalYearFormat::EndingYear, "ending-year".parse().unwrap());
+        assert!("fy".parse::<FiscalYearFormat>().is_err());
+    }
+
+    #[test]
+    fn monthly_formats() {
+        let report = MonthlyReport::new(Year(NonZeroU16::new(2009).unwrap()), Month::January);
+        let formats = [
+            (MonthlyFormat::Hyphenated, "2009-01"),
+            (MonthlyFormat::IsoDate, "2009-01-01"),
+            (MonthlyFormat::Named, "Jan 2009"),
+            (MonthlyFormat::Compact, "2009M01")
+        ];
+        for (format, expected) in formats {
+            let displayed = format.display(&report);
+            assert_eq!(expected, displayed);
+            // Reloading condensed files understands every format
+            assert_eq!(
+                Timestamp::Monthly(report),
+                Timestamp::from_displayed_value(&displayed, Granularity::Monthly).unwrap()
+            );
+        }
+        assert_eq!(MonthlyFormat::IsoDate, "iso-date".parse().unwrap());
+    }
+
+    #[test]
+    fn period_formats() {
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let periods = [
+            (Timestamp::BiAnnually(year, HalfYear::JanThruJun), "2014 Jan-Jun", "2014-H1"),
+            (Timestamp::BiAnnually(year, HalfYear::JulThruDec), "2014 Jul-Dec", "2014-H2"),
+            (Timestamp::Quarterly(year, Quarter::JanFebMar), "2014 Jan-Mar", "2014-Q1"),
+            (Timestamp::Quarterly(year, Quarter::JulAugSep), "2014 Jul-Sep", "2014-Q3"),
+            (Timestamp::Quarterly(year, Quarter::OctNovDec), "2014 Oct-Dec", "2014-Q4")
+        ];
+        for (timestamp, verbose, compact) in periods {
+            for (periods, expected) in [(PeriodFormat::Verbose, verbose), (PeriodFormat::Compact, compact)] {
+                let options = OutputOptions { periods, ..Default::default() };
+                let displayed = options.display_timestamp(&timestamp);
+                assert_eq!(expected, displayed);
+                assert_eq!(timestamp, Timestamp::from_displayed_value(&displayed, timestamp.granularity()).unwrap());
+            }
+        }
+        // Quarters are not half-years
+        assert!(Timestamp::from_displayed_value("2014-Q1", Granularity::BiAnnual).is_err());
+        assert!(Timestamp::from_displayed_value("2014-Q5", Granularity::Quarterly).is_err());
+    }
+
+    #[test]
+    fn iso_date_column() {
+        let sheet = Sheet::default();
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        for timestamp in [Timestamp::Quarterly(year, Quarter::JulAugSep), Timestamp::Quarterly(year, Quarter::JanFebMar)] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Exports"]), "10");
+            sheet.add_row(timestamp, row);
+        }
+        let write = |format| {
+            let options = OutputOptions { format, iso_date_column: true, ..Default::default() };
+            let mut output = Vec::new();
+            task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            "date,timestamp-primary-key,Exports\n2014-01-01,2014 Jan-Mar,10\n2014-07-01,2014 Jul-Sep,10\n",
+            write(OutputFormat::Wide)
+        );
+        assert_eq!(
+            "date,timestamp-primary-key,column,value\n2014-01-01,2014 Jan-Mar,Exports,10\n2014-07-01,2014 Jul-Sep,Exports,10\n",
+            write(OutputFormat::Long)
+        );
+    }
+
+    #[test]
+    fn provisional_column() {
+        let sheet = Sheet::default();
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        for month in [Month::May, Month::June] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Exports"]), "10");
+            sheet.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        sheet.mark_provisional(Timestamp::Monthly(MonthlyReport::new(year, Month::June)));
+        let write = |format| {
+            let options = OutputOptions { format, provisional_column: true, ..Default::default() };
+            let mut output = Vec::new();
+            task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            "timestamp-primary-key,provisional,Exports\n2022-05,false,10\n2022-06,true,10\n",
+            write(OutputFormat::Wide)
+        );
+        assert_eq!(
+            "timestamp-primary-key,provisional,column,value\n2022-05,false,Exports,10\n2022-06,true,Exports,10\n",
+            write(OutputFormat::Long)
+        );
+    }
+
+    #[test]
+    fn revised_column() {
+        let sheet = Sheet::default();
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        for (month, revised) in [(Month::May, true), (Month::June, false)] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Exports"]), "10");
+            if revised {
+                row.mark_revised();
+            }
+            sheet.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        let options = OutputOptions { revised_column: true, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        assert_eq!(
+            "timestamp-primary-key,revised,Exports\n2022-05,true,10\n2022-06,false,10\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn combined_granularities() {
+        let year = Year(NonZeroU16::new(2014).unwrap());
+        let yearly = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&column(&["Imports"]), "40");
+        yearly.add_row(Timestamp::CalendarYear(year), row);
+        let monthly = Sheet::default();
+        for month in [Month::February, Month::January] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Exports"]), "10");
+            monthly.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        let sheets = [(Granularity::CalendarYear, &yearly), (Granularity::Monthly, &monthly)];
+        let write = |format| {
+            let options = OutputOptions { format, ..Default::default() };
+            let mut output = Vec::new();
+            task::block_on(write_combined(&mut output, &sheets, &options)).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(
+            "granularity,date,timestamp-primary-key,Exports,Imports\n\
+             calendar-yearly,2014-01-01,2014,NA,40\n\
+             monthly,2014-01-01,2014-01,10,NA\n\
+             monthly,2014-02-01,2014-02,10,NA\n",
+            write(OutputFormat::Wide)
+        );
+        assert_eq!(
+            "granularity,date,timestamp-primary-key,column,value\n\
+             calendar-yearly,2014-01-01,2014,Imports,40\n\
+             monthly,2014-01-01,2014-01,Exports,10\n\
+             monthly,2014-02-01,2014-02,Exports,10\n",
+            write(OutputFormat::Long)
+        );
+    }
+
+    #[test]
+    fn filled_column() {
+        let sheet = Sheet::default();
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        for (month, filled) in [(Month::May, false), (Month::June, true)] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Exports"]), "10");
+            row.populate(&column(&["Imports"]), "20");
+            if filled {
+                row.mark_filled(&column(&["Exports"]));
+                row.mark_filled(&column(&["Imports"]));
+            }
+            sheet.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        let options = OutputOptions { filled_column: true, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert!(lines.next().unwrap().starts_with("timestamp-primary-key,filled,"));
+        assert!(lines.next().unwrap().starts_with("2022-05,,"));
+        let june = lines.next().unwrap();
+        assert!(june.starts_with("2022-06,Exports; Imports,") || june.starts_with("2022-06,Imports; Exports,"));
+
+        let options = OutputOptions { format: OutputFormat::Long, filled_column: true, ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("timestamp-primary-key,column,value,filled\n"));
+        assert!(output.contains("2022-05,Exports,10,false\n"));
+        assert!(output.contains("2022-06,Exports,10,true\n"));
+    }
+
+    #[test]
+    fn manifest_fill_counts() {
+        let sheet = Sheet::default();
+        for year in [2009, 2010] {
+            let mut row = RowData::default();
+            row.populate(&column(&["Money", "Broad Money"]), "12.5");
+            sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap())), row);
+        }
+        let mut output = Vec::new();
+        task::block_on(write_manifest(
+            &mut output, &[(String::from("yearly"), &sheet)], &OutputOptions::default()
+        )).unwrap();
+        assert_eq!(
+            "sheet,column,level1,level2,filled-rows,percentage,type,unit\nyearly,Money.Broad Money,Money,Broad Money,2,false,numeric,\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn manifest_converted_units() {
+        let sheet = Sheet::default();
+        let options = ValueOptions { normalize_units: true, ..Default::default() };
+        for (year, figure) in [(2009, "12.5 crore"), (2010, "3 lakh")] {
+            let mut row = RowData::default();
+            let parsed = Value::parse_with(figure, &options);
+            row.populate(&column(&["Remittances"]), parsed.value);
+            row.mark_unit(&column(&["Remittances"]), parsed.unit.unwrap());
+            row.populate(&column(&["Reserves"]), 7.0);
+            sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap())), row);
+        }
+        let mut output = Vec::new();
+        task::block_on(write_manifest(
+            &mut output, &[(String::from("yearly"), &sheet)], &OutputOptions::default()
+        )).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("yearly,Remittances,Remittances,2,false,numeric,lakh/crore\n"));
+        assert!(output.contains("yearly,Reserves,Reserves,2,false,numeric,\n"));
+    }
+
+    #[test]
+    fn semicolon_delimited() {
+        let sheet = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&column(&["Deposits, total"]), "12.5");
+        sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap())), row);
+
+        let options = OutputOptions { delimiter: b';', ..Default::default() };
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        assert_eq!("timestamp-primary-key;Deposits, total\n2009;12.5\n", String::from_utf8(output.clone()).unwrap());
+
+        let records = task::block_on(async {
+            use futures::StreamExt;
+            let mut reader = csv_async::AsyncReaderBuilder::new()
+                .delimiter(b';')
+                .create_reader(output.as_slice());
+            let header = reader.headers().await.unwrap().clone();
+            let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>().await;
+            (header, rows)
+        });
+        assert_eq!(vec!["timestamp-primary-key", "Deposits, total"], records.0.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["2009", "12.5"], records.1[0].iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tab_separated() {
+        let sheet = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&column(&["Notes, remarks"]), "Revised\tprovisionally");
+        sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(2009).unwrap())), row);
+
+        // The delimiter is overridden
+        let options = OutputOptions { format: OutputFormat::Tsv, delimiter: b';', ..Default::default() };
+        assert_eq!("tsv", options.extension());
+        let mut output = Vec::new();
+        task::block_on(write_sheet(&mut output, &sheet, &options)).unwrap();
+        assert_eq!(
+            "timestamp-primary-key\tNotes, remarks\n2009\t\"Revised\tprovisionally\"\n",
+            String::from_utf8(output.clone()).unwrap()
+        );
+
+        let records = task::block_on(async {
+            use futures::StreamExt;
+            let mut reader = csv_async::AsyncReaderBuilder::new()
+                .delimiter(b'\t')
+                .create_reader(output.as_slice());
+            reader.records().map(Result::unwrap).collect::<Vec<_>>().await
+        });
+        assert_eq!(vec!["2009", "Revised\tprovisionally"], records[0].iter().collect::<Vec<_>>());
+    }
+}
@@ -20,30 +20,83 @@
 
 // Parsing
 
-use std::num::NonZeroU16;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::num::{NonZeroU16, ParseIntError, TryFromIntError};
 use std::str::FromStr;
 use std::sync::OnceLock;
 use regex::Regex;
 use crate::common::*;
 
-#[derive(Debug, PartialEq, Eq)]
-pub struct CannotParse(());
+/// Why a value could not be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CannotParse {
+    /// The rejected value, and what it was parsed as, once known
+    value: Option<(String, &'static str)>,
+    reason: Cow<'static, str>
+}
 
 impl CannotParse {
-    /// Legacy method I keep here in case I need to refactor CannotParse
+    /// The value is not of the expected form
     fn simply() -> Self {
-        Self(())
+        Self::because("unrecognized form")
+    }
+
+    fn because<R>(reason: R) -> Self where R: Into<Cow<'static, str>> {
+        Self { value: None, reason: reason.into() }
+    }
+
+    /// Records the rejected value and what it was parsed as. The innermost value is kept, as
+    /// it pinpoints the failure
+    fn of(mut self, value: &str, parsed_as: &'static str) -> Self {
+        if self.value.is_none() {
+            self.value = Some((value.to_string(), parsed_as));
+        }
+        self
+    }
+
+    /// The rejected value, if known
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_ref().map(|(value, _)| value.as_str())
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl Display for CannotParse {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some((value, parsed_as)) => write!(f, "cannot parse {:?} as {}: {}", value, parsed_as, self.reason),
+            None => f.write_str(&self.reason)
+        }
+    }
+}
+
+impl Error for CannotParse {}
+
+impl From<ParseIntError> for CannotParse {
+    fn from(error: ParseIntError) -> Self {
+        Self::because(format!("invalid number ({})", error))
     }
 }
 
-impl<E> From<E> for CannotParse where E: std::error::Error + Send + Sync + 'static {
-    fn from(_error: E) -> Self {
-        Self(())
+impl From<TryFromIntError> for CannotParse {
+    fn from(_error: TryFromIntError) -> Self {
+        Self::because("there is no year zero")
+    }
+}
+
+impl From<chrono::OutOfRange> for CannotParse {
+    fn from(_error: chrono::OutOfRange) -> Self {
+        Self::because("months are numbered 1 through 12")
     }
 }
 
 macro_rules! impl_from_str_with_pat {
-    ($strct:ty, $pat_constant:ident, $pat:literal, $unchecked_create:ident, $tests_module:ident) => {
+    ($strct:ty, $name:literal, $pat_constant:ident, $pat:literal, $unchecked_create:ident, $tests_module:ident) => {
 
         static $pat_constant: OnceLock<Regex> = OnceLock::new();
 
@@ -55,9 +108,9 @@ macro_rules! impl_from_str_with_pat {
                     Regex::new($pat).expect("Regex compilation failure")
                 );
                 if pattern.shortest_match(value) != Some(value.len()) {
-                    return Err(CannotParse::simply());
+                    return Err(CannotParse::simply().of(value, $name));
                 }
-                $unchecked_create(value)
+                $unchecked_create(value).map_err(|error| error.of(value, $name))
             }
         }
 
@@ -78,7 +131,7 @@ fn impl_parse_year(value: &str) -> Result<Year, CannotParse> {
     Ok(Year(NonZeroU16::try_from(year)?))
 }
 
-impl_from_str_with_pat!(Year, YEAR_PATTERN, "^[0-9]{4}$", impl_parse_year, test_year_from_str);
+impl_from_str_with_pat!(Year, "a year", YEAR_PATTERN, "^[0-9]{4}$", impl_parse_year, test_year_from_str);
 
 fn impl_parse_report(value: &str) -> Result<MonthlyReport, CannotParse> {
     let year: u16 = value[0..4].parse()?;
@@ -90,51 +143,51 @@ fn impl_parse_report(value: &str) -> Result<MonthlyReport, CannotParse> {
 }
 
 impl_from_str_with_pat!(
-    MonthlyReport, MONTHLY_REPORT_PATTERN, "^[0-9]{4}-([0-9]{2}|[0-9])$", impl_parse_report, test_report_from_str
+    MonthlyReport, "a month", MONTHLY_REPORT_PATTERN, "^[0-9]{4}-([0-9]{2}|[0-9])$", impl_parse_report, test_report_from_str
 );
 
 impl FromStr for YearlyTimestamp {
     type Err = CannotParse;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
+        impl_parse_yearly_timestamp(value).map_err(|error| error.of(value, "a calendar or fiscal year"))
+    }
+}
 
-        // Strip trailing whitespace
-        let value = value.trim_end_matches(char::is_whitespace);
-
-        // Goal is to allow for whitespace inside fiscal years, e.g. "2009 - 10" is also valid
-        const FISCAL_YEAR_LEN: usize = "2009-10".len();
-        // However, calendar years are parsed rather simply and more strictly
-        const CALENDAR_YEAR_LEN: usize = "2009".len();
+fn impl_parse_yearly_timestamp(value: &str) -> Result<YearlyTimestamp, CannotParse> {
+    // Strip trailing whitespace
+    let value = value.trim_end_matches(char::is_whitespace);
 
-        if value.len() == CALENDAR_YEAR_LEN {
-            return Ok(YearlyTimestamp::Calendar(Year::from_str(value)?));
-        }
-        if value.len() >= FISCAL_YEAR_LEN {
-            let year: Year = value[0..4].parse()?;
-            // Need to validate rest of the string
-            let suffix = &value[4..];
-            if suffix.len() >= 2 {
-                // Break apart the last two characters
-                let last_two_chars = &suffix[suffix.len() - 2..];
-                let interior = &suffix[..suffix.len() - 2];
-
-                // The last two characters should be the next year
-                let next_year: u16 = last_two_chars.parse()?;
-
-                // The interior, excluding whitespace, should be '-'
-                if interior.trim() == "-" {
-                    assert_eq!(
-                        (year.0.get() + 1) % 100,
-                        next_year,
-                        "Invalid fiscal year"
-                    );
-                    return Ok(YearlyTimestamp::Fiscal(year));
-                }
+    // Goal is to allow for whitespace inside fiscal years, e.g. "2009 - 10" is also valid
+    const FISCAL_YEAR_LEN: usize = "2009-10".len();
+    // However, calendar years are parsed rather simply and more strictly
+    const CALENDAR_YEAR_LEN: usize = "2009".len();
 
+    if value.len() == CALENDAR_YEAR_LEN {
+        return Ok(YearlyTimestamp::Calendar(Year::from_str(value)?));
+    }
+    // Spreadsheet content is untrusted; slicing must not split a multibyte character
+    if value.len() >= FISCAL_YEAR_LEN && value.is_ascii() {
+        let year: Year = value[0..4].parse()?;
+        // Need to validate rest of the string
+        let suffix = &value[4..];
+        if suffix.len() >= 2 {
+            // Break apart the last two characters
+            let last_two_chars = &suffix[suffix.len() - 2..];
+            let interior = &suffix[..suffix.len() - 2];
+
+            // The last two characters should be the next year
+            let next_year: u16 = last_two_chars.parse()?;
+
+            // The interior, excluding whitespace, should be '-'. Otherwise, e.g. 2009-05,
+            // the value may well be a month
+            if interior.trim() == "-" && (year.0.get() + 1) % 100 == next_year {
+                return Ok(YearlyTimestamp::Fiscal(year));
             }
+
         }
-        Err(CannotParse::simply())
     }
+    Err(CannotParse::simply())
 }
 
 impl TryFrom<(Year, &str)> for Timestamp {
@@ -147,14 +200,131 @@ impl TryFrom<(Year, &str)> for Timestamp {
             Ok(Timestamp::Quarterly(year, quarter))
         } else if let Ok(halfyear) = HalfYear::from_str(remainder) {
             Ok(Timestamp::BiAnnually(year, halfyear))
+        } else if let Some((year, period)) = split_embedded_year(remainder) {
+            // The year given in the cell takes precedence over the year of the rows before
+            if let Ok(quarter) = Quarter::from_str(&period) {
+                Ok(Timestamp::Quarterly(year, quarter))
+            } else {
+                HalfYear::from_str(&period).map(|halfyear| Timestamp::BiAnnually(year, halfyear))
+            }
         } else {
-            Err(CannotParse::simply())
+            Err(CannotParse::because("not a month, quarter, or half-year").of(remainder, "a period within a year"))
+        }
+    }
+}
+
+/// Removes the year repeated in a period, as in "July-September 2014" or "Jul 2014-Sep 2014".
+/// Yields the year and the period without it, unless there is no year or the years differ
+fn split_embedded_year(value: &str) -> Option<(Year, String)> {
+    let mut year = None;
+    let mut period = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_digit()) {
+        let end = rest[start..].find(|c: char| !c.is_ascii_digit()).map_or(rest.len(), |length| start + length);
+        let found = Year::from_str(&rest[start..end]).ok()?;
+        if year.is_some_and(|year| year != found) {
+            return None;
         }
+        year = Some(found);
+        period.push_str(&rest[..start]);
+        rest = &rest[end..];
+    }
+    period.push_str(rest);
+    Some((year?, period))
+}
+
+impl FromStr for Granularity {
+    type Err = CannotParse;
+
+    /// Parses the name of the granularity, as used in file names
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Self::values()
+            .into_iter()
+            .find(|granularity| granularity.name() == value)
+            .ok_or_else(|| CannotParse::simply().of(value, "a granularity"))
+    }
+}
+
+impl Timestamp {
+    /// Parses a timestamp in any displayed form, trying each granularity from the longest to
+    /// the shortest. Thus 2009-10 is the 2009-10 fiscal year, never October 2009; use
+    /// [Self::from_displayed_value] where the granularity is known
+    pub fn parse(value: &str) -> Result<Self, CannotParse> {
+        Granularity::values()
+            .into_iter()
+            .find_map(|granularity| Self::from_displayed_value(value, granularity).ok())
+            .ok_or_else(|| CannotParse::because("not a timestamp of any granularity").of(value, "a timestamp"))
+    }
+
+    /// Parses a timestamp as it is displayed in output. The granularity must be known in
+    /// advance, because "2009-10" is both the 2009-10 fiscal year and October 2009
+    pub fn from_displayed_value(value: &str, granularity: Granularity) -> Result<Self, CannotParse> {
+        match granularity {
+            Granularity::CalendarYear => Ok(Self::CalendarYear(Year::from_str(value)?)),
+            Granularity::FiscalYear => match YearlyTimestamp::from_str(value)? {
+                YearlyTimestamp::Fiscal(year) => Ok(Self::FiscalYear(year)),
+                YearlyTimestamp::Calendar(_) => Err(CannotParse::because("a calendar year").of(value, "a fiscal year"))
+            },
+            Granularity::BiAnnual | Granularity::Quarterly => {
+                // e.g. 2014 Jul-Sep, or compactly 2014-Q3
+                let timestamp = match value.split_once(' ') {
+                    Some((year, remainder)) => Self::try_from((Year::from_str(year)?, remainder))?,
+                    None => Self::from_compact_period(value)?
+                };
+                if timestamp.granularity() == granularity {
+                    Ok(timestamp)
+                } else {
+                    Err(CannotParse::because(format!("a {} timestamp", timestamp.granularity()))
+                        .of(value, granularity.name()))
+                }
+            },
+            Granularity::Monthly => Ok(Self::Monthly(MonthlyReport::from_displayed_value(value)?))
+        }
+    }
+
+    /// Parses a half-year or quarter as written compactly, e.g. 2009-H1 or 2014-Q3
+    fn from_compact_period(value: &str) -> Result<Self, CannotParse> {
+        let (year, period) = value.split_once('-').ok_or_else(CannotParse::simply)
+            .map_err(|error| error.of(value, "a half-year or quarter"))?;
+        let year = Year::from_str(year)?;
+        let number = |prefix| -> Result<usize, CannotParse> {
+            let number = period.strip_prefix(prefix).ok_or_else(CannotParse::simply)?.parse::<usize>()?;
+            number.checked_sub(1).ok_or_else(|| CannotParse::because("periods are numbered from 1"))
+        };
+        let period = if let Ok(index) = number('H') {
+            HalfYear::values().into_iter().nth(index).map(|half_year| Self::BiAnnually(year, half_year))
+                .ok_or_else(|| CannotParse::because("there are two half-years"))
+        } else {
+            number('Q').and_then(|index| {
+                Quarter::values().into_iter().nth(index).map(|quarter| Self::Quarterly(year, quarter))
+                    .ok_or_else(|| CannotParse::because("there are four quarters"))
+            })
+        };
+        period.map_err(|error| error.of(value, "a half-year or quarter"))
+    }
+}
+
+impl MonthlyReport {
+    /// Parses a month in any of the output formats: 2009-01, 2009-01-01, Jan 2009, or 2009M01
+    fn from_displayed_value(value: &str) -> Result<Self, CannotParse> {
+        if let Ok(report) = Self::from_str(value) {
+            return Ok(report);
+        }
+        if let Some(year_and_month) = value.strip_suffix("-01").filter(|_| value.len() == "2009-01-01".len()) {
+            return Self::from_str(year_and_month);
+        }
+        if let Some((month, year)) = value.split_once(' ') {
+            return Ok(Self::new(Year::from_str(year)?, Month::from_str(month)?));
+        }
+        if let Some((year, month)) = value.split_once('M').filter(|(_, month)| month.len() == 2) {
+            return Ok(Self::new(Year::from_str(year)?, Month::from_str(month)?));
+        }
+        Err(CannotParse::simply().of(value, "a month"))
     }
 }
 
 macro_rules! impl_from_str_using_start_end_months {
-    ($strct:ident, $values:expr) => {
+    ($strct:ident, $name:literal, $values:expr) => {
         impl $strct {
             pub fn values() -> impl IntoIterator<Item=Self> {
                 $values
@@ -189,14 +359,14 @@ macro_rules! impl_from_str_using_start_end_months {
                         return Ok(try_me);
                     }
                 }
-                Err(CannotParse::simply())
+                Err(CannotParse::because("no span of months matches").of(value, $name))
             }
         }
     }
 }
 
-impl_from_str_using_start_end_months!(HalfYear, [HalfYear::JanThruJun, HalfYear::JulThruDec]);
-impl_from_str_using_start_end_months!(Quarter, [Quarter::JanFebMar, Quarter::AprMayJun, Quarter::JulAugSep, Quarter::OctNovDec]);
+impl_from_str_using_start_end_months!(HalfYear, "a half-year", [HalfYear::JanThruJun, HalfYear::JulThruDec]);
+impl_from_str_using_start_end_months!(Quarter, "a quarter", [Quarter::JanFebMar, Quarter::AprMayJun, Quarter::JulAugSep, Quarter::OctNovDec]);
 
 impl TryFrom<u8> for Month {
     type Error = CannotParse;
@@ -225,7 +395,12 @@ impl FromStr for Month {
                     // Hooray for spelling
                     return Ok(Self::February);
                 }
-                return Err(CannotParse::simply())
+                // Some sheets number their months, e.g. "7" or "07". Anything longer than
+                // two digits is left alone, so that years are never mistaken for months
+                if (1..=2).contains(&value.len()) && value.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return Self::try_from(value.parse::<u8>()?).map_err(|error| error.of(value, "a month"));
+                }
+                return Err(CannotParse::because("unknown month name").of(value, "a month"))
             }
         };
         Ok(Self::from_chrono(chrono))
@@ -248,11 +423,7 @@ mod tests {
     #[test]
     fn parse_fiscal_year() {
         fn is_fiscal_year(value: &str) -> bool {
-            if let Ok(YearlyTimestamp::Fiscal(_)) = YearlyTimestamp::from_str(value) {
-                true
-            } else {
-                false
-            }
+            matches!(YearlyTimestamp::from_str(value), Ok(YearlyTimestamp::Fiscal(_)))
         }
         assert!(is_fiscal_year("2022-23"));
         assert!(is_fiscal_year("2009-10"));
@@ -262,14 +433,34 @@ mod tests {
         assert!(!is_fiscal_year("hello"));
     }
 
+    #[test]
+    fn month_display_round_trip() {
+        for month in Month::values() {
+            assert_eq!(month, Month::from_str(&month.to_string()).unwrap(), "{}", month);
+        }
+    }
+
+    #[test]
+    fn month_numeric_round_trip() {
+        for month in Month::values() {
+            assert_eq!(month, Month::try_from(month.as_numeric()).unwrap());
+        }
+        assert_matches!(Month::try_from(0), Err(_));
+        assert_matches!(Month::try_from(13), Err(_));
+    }
+
+    #[test]
+    fn reject_malformed_fiscal_year() {
+        // The second year must follow the first
+        assert!(YearlyTimestamp::from_str("2009-15").is_err());
+        assert!(YearlyTimestamp::from_str("2009-09").is_err());
+        assert!(YearlyTimestamp::from_str("২০০৯-১০").is_err());
+    }
+
     #[test]
     fn parse_calendar_year() {
         fn is_calendar_year(value: &str) -> bool {
-            if let Ok(YearlyTimestamp::Calendar(_)) = YearlyTimestamp::from_str(value) {
-                true
-            } else {
-                false
-            }
+            matches!(YearlyTimestamp::from_str(value), Ok(YearlyTimestamp::Calendar(_)))
         }
         assert!(is_calendar_year("2022"));
         assert!(is_calendar_year("2009"));
@@ -279,6 +470,28 @@ mod tests {
         assert!(!is_calendar_year("hello"));
     }
 
+    #[test]
+    fn parse_numeric_month() {
+        assert_eq!(Ok(Month::January), Month::from_str("01"));
+        assert_eq!(Ok(Month::January), Month::from_str("1"));
+        assert_eq!(Ok(Month::July), Month::from_str("7"));
+        assert_eq!(Ok(Month::December), Month::from_str("12"));
+        assert_eq!(Ok(Month::October), Month::from_str("10."));
+        assert_matches!(Month::from_str("0"), Err(_));
+        assert_matches!(Month::from_str("13"), Err(_));
+        assert_matches!(Month::from_str("007"), Err(_));
+        assert_matches!(Month::from_str("2009"), Err(_));
+        assert_matches!(Month::from_str("-1"), Err(_));
+
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        assert_eq!(
+            Ok(Timestamp::Monthly(MonthlyReport::new(year, Month::March))),
+            Timestamp::try_from((year, "03"))
+        );
+        // Four digits remain a year
+        assert_matches!(YearlyTimestamp::from_str("2010"), Ok(YearlyTimestamp::Calendar(_)));
+    }
+
     #[test]
     fn parse_quarter() {
         fn assert_parse_quarter(expected: Quarter, from_what: &str) {
@@ -297,5 +510,102 @@ mod tests {
         assert_parse_quarter(Quarter::JulAugSep, "Jul-Sep");
         assert_parse_quarter(Quarter::JulAugSep, "Jul- Sep");
         assert_parse_quarter(Quarter::JulAugSep, "July- Sep");
+        assert_parse_quarter(Quarter::JulAugSep, "July-September");
+        assert_parse_quarter(Quarter::OctNovDec, "October - December");
+    }
+
+    #[test]
+    fn parse_period_with_year() {
+        let year = |year| Year(NonZeroU16::new(year).unwrap());
+        let parse = |value: &str| Timestamp::try_from((year(2009), value));
+        assert_eq!(Ok(Timestamp::Quarterly(year(2014), Quarter::JulAugSep)), parse("July-September 2014"));
+        assert_eq!(Ok(Timestamp::Quarterly(year(2014), Quarter::JulAugSep)), parse("Jul 2014-Sep 2014"));
+        assert_eq!(Ok(Timestamp::Quarterly(year(2014), Quarter::JulAugSep)), parse("Jul 2014 - Sep 2014"));
+        assert_eq!(Ok(Timestamp::BiAnnually(year(2015), HalfYear::JanThruJun)), parse("Jan-Jun 2015"));
+        // The compact forms keep the year of the rows before
+        assert_eq!(Ok(Timestamp::Quarterly(year(2009), Quarter::JulAugSep)), parse("Jul-Sep"));
+        // Differing or malformed years are no period
+        assert_matches!(parse("Jul 2014-Sep 2015"), Err(_));
+        assert_matches!(parse("Jul-Sep 20140"), Err(_));
+        assert_matches!(parse("Nothing 2014"), Err(_));
+    }
+
+    #[test]
+    fn parse_displayed_value() {
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        for timestamp in [
+            Timestamp::CalendarYear(year),
+            Timestamp::FiscalYear(year),
+            Timestamp::FiscalYear(Year(NonZeroU16::new(2008).unwrap())),
+            Timestamp::FiscalYear(Year(NonZeroU16::new(2099).unwrap())),
+            Timestamp::BiAnnually(year, HalfYear::JulThruDec),
+            Timestamp::Quarterly(year, Quarter::AprMayJun),
+            Timestamp::Monthly(MonthlyReport::new(year, Month::October))
+        ] {
+            let displayed = timestamp.to_string();
+            assert_eq!(Ok(timestamp), Timestamp::from_displayed_value(&displayed, timestamp.granularity()), "{}", displayed);
+        }
+        // The granularity tells apart the fiscal year from the month
+        assert_eq!(Ok(Timestamp::FiscalYear(year)), Timestamp::from_displayed_value("2009-10", Granularity::FiscalYear));
+        assert_eq!(
+            Ok(Timestamp::Monthly(MonthlyReport::new(year, Month::October))),
+            Timestamp::from_displayed_value("2009-10", Granularity::Monthly)
+        );
+        assert_matches!(Timestamp::from_displayed_value("2009 Jul-Sep", Granularity::BiAnnual), Err(_));
+        assert_matches!(Timestamp::from_displayed_value("2009", Granularity::FiscalYear), Err(_));
+        assert_matches!(Timestamp::from_displayed_value("hello", Granularity::Quarterly), Err(_));
+    }
+
+    #[test]
+    fn describe_failures() {
+        let error = "20x9".parse::<Year>().unwrap_err();
+        assert_eq!(Some("20x9"), error.value());
+        assert_eq!("cannot parse \"20x9\" as a year: unrecognized form", error.to_string());
+        // The innermost value pinpoints the failure
+        let error = "20x9-10".parse::<YearlyTimestamp>().unwrap_err();
+        assert_eq!(Some("20x9"), error.value());
+        assert_eq!(
+            "cannot parse \"Foo\" as a month: unknown month name",
+            "Foo".parse::<Month>().unwrap_err().to_string()
+        );
+        assert_eq!(
+            "cannot parse \"13\" as a month: months are numbered 1 through 12",
+            "13".parse::<Month>().unwrap_err().to_string()
+        );
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        assert_eq!(
+            "cannot parse \"Total\" as a period within a year: not a month, quarter, or half-year",
+            Timestamp::try_from((year, "Total")).unwrap_err().to_string()
+        );
+        assert_eq!(
+            "cannot parse \"2009-Q5\" as a half-year or quarter: there are four quarters",
+            Timestamp::from_displayed_value("2009-Q5", Granularity::Quarterly).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn parse_any_granularity() {
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        for timestamp in [
+            Timestamp::CalendarYear(year),
+            Timestamp::FiscalYear(year),
+            Timestamp::FiscalYear(Year(NonZeroU16::new(2099).unwrap())),
+            Timestamp::BiAnnually(year, HalfYear::JanThruJun),
+            Timestamp::BiAnnually(year, HalfYear::JulThruDec),
+            Timestamp::Quarterly(year, Quarter::JanFebMar),
+            Timestamp::Quarterly(year, Quarter::OctNovDec),
+            Timestamp::Monthly(MonthlyReport::new(year, Month::January)),
+            Timestamp::Monthly(MonthlyReport::new(year, Month::December))
+        ] {
+            let displayed = timestamp.to_string();
+            assert_eq!(Ok(timestamp), Timestamp::parse(&displayed), "{}", displayed);
+        }
+        // Compact forms are understood too
+        assert_eq!(Ok(Timestamp::Quarterly(year, Quarter::JulAugSep)), Timestamp::parse("2009-Q3"));
+        assert_eq!(Ok(Timestamp::Monthly(MonthlyReport::new(year, Month::March))), Timestamp::parse("Mar 2009"));
+        // Ambiguous values are fiscal years
+        assert_eq!(Ok(Timestamp::FiscalYear(year)), Timestamp::parse("2009-10"));
+        assert_matches!(Timestamp::parse("2009 Feb-Apr"), Err(_));
+        assert_matches!(Timestamp::parse(""), Err(_));
     }
 }
@@ -0,0 +1,276 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Data quality checks over merged sheets
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use crate::common::*;
+use crate::merge::{Column, RowKey, Sheet};
+use crate::value::Value;
+
+/// Which periods a column has data for, and which periods it lacks in between
+#[derive(Clone, Debug)]
+pub struct ColumnCoverage {
+    pub column: Column,
+    pub first: Timestamp,
+    pub last: Timestamp,
+    pub present: usize,
+    /// Periods between the first and last which have no value
+    pub missing: Vec<Timestamp>
+}
+
+impl ColumnCoverage {
+    /// The number of months from the start of the first period to the end of the last
+    pub fn span_in_months(&self) -> u32 {
+        let (start, _) = self.first.date_range();
+        let (_, end) = self.last.date_range();
+        let month_index = |report: MonthlyReport| report.year.0.get() as u32 * 12 + report.month.as_numeric() as u32;
+        month_index(end) + 1 - month_index(start)
+    }
+}
+
+/// Computes the coverage of every column in the sheet. A sheet holds timestamps of a single
+/// kind, so the expected periods are found by stepping from the first timestamp to the last.
+pub fn coverage(sheet: &Sheet) -> Vec<ColumnCoverage> {
+    let mut present = HashMap::<Column, BTreeSet<Timestamp>>::new();
+    for row in sheet.rows.iter() {
+        for (column, _) in row.value().iter().filter(|(_, value)| !value.is_empty()) {
+            present.entry(column.clone()).or_default().insert(*row.key());
+        }
+    }
+    let mut coverages = Vec::with_capacity(present.len());
+    for (column, timestamps) in present {
+        let (Some(&first), Some(&last)) = (timestamps.first(), timestamps.last()) else {
+            continue;
+        };
+        let mut missing = Vec::new();
+        let mut cursor = first;
+        while cursor < last {
+            cursor = match cursor.next() {
+                Some(next) => next,
+                None => break
+            };
+            if !timestamps.contains(&cursor) {
+                missing.push(cursor);
+            }
+        }
+        coverages.push(ColumnCoverage {
+            column, first, last, present: timestamps.len(), missing
+        });
+    }
+    coverages
+}
+
+/// A yearly figure which differs from the sum of its four quarters
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumDiscrepancy {
+    pub column: Column,
+    pub year: Timestamp,
+    pub yearly: f64,
+    pub sum_of_quarters: f64
+}
+
+/// Checks that the four quarters of each year sum to the yearly figure, within the tolerance
+/// relative to the yearly figure. Only years with all four quarters are checked, in columns
+/// present in both sheets. The yearly sheet may hold calendar or fiscal years.
+///
+/// Stocks such as balances are not expected to sum, and are reported alike. The discrepancies
+/// are in chronological order
+pub fn check_quarterly_sums(quarterly: &Sheet, yearly: &Sheet, tolerance: f64) -> Vec<SumDiscrepancy> {
+    let Some(yearly_granularity) = yearly.rows.iter().next().map(|row| row.key().granularity()) else {
+        return Vec::new();
+    };
+    let mut sums = HashMap::<(Column, Timestamp), (f64, usize)>::new();
+    for row in quarterly.rows.iter() {
+        let (first_month, _) = row.key().date_range();
+        let Some(year) = yearly_granularity.period_containing(first_month) else {
+            continue;
+        };
+        for (column, value) in row.value().iter() {
+            if let Value::Number(number) = value {
+                let (sum, count) = sums.entry((column.clone(), year)).or_default();
+                *sum += number;
+                *count += 1;
+            }
+        }
+    }
+    let mut discrepancies = Vec::new();
+    for ((column, year), (sum_of_quarters, count)) in sums {
+        if count != 4 {
+            continue;
+        }
+        let Some(row) = yearly.rows.get(&year) else {
+            continue;
+        };
+        let Some(&Value::Number(yearly)) = row.get(&column) else {
+            continue;
+        };
+        if (sum_of_quarters - yearly).abs() > tolerance * yearly.abs() {
+            discrepancies.push(SumDiscrepancy { column, year, yearly, sum_of_quarters });
+        }
+    }
+    discrepancies.sort_by(|first, second| {
+        first.year.cmp_chronologically(&second.year)
+            .then_with(|| first.column.display_full_labeling().cmp(&second.column.display_full_labeling()))
+    });
+    discrepancies
+}
+
+/// The kind of values a column holds, for choosing its type in typed outputs
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColumnType {
+    /// Every value is a number. Columns without any values are considered numeric
+    Numeric,
+    /// Every value is text, such as remarks or ratings
+    Text,
+    /// Both numbers and text. Typed outputs fall back to text
+    Mixed
+}
+
+impl ColumnType {
+    /// The type of a single value. Placeholders such as NA stand for missing values, so
+    /// they have none
+    fn of(value: &Value) -> Option<Self> {
+        match value {
+            _ if value.is_placeholder() => None,
+            Value::Number(_) => Some(Self::Numeric),
+            _ => Some(Self::Text)
+        }
+    }
+
+    fn and(self, other: Self) -> Self {
+        if self == other { self } else { Self::Mixed }
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Numeric => "numeric",
+            Self::Text => "text",
+            Self::Mixed => "mixed"
+        })
+    }
+}
+
+/// Classifies every column of the sheet by the values it holds
+pub fn column_types<K>(sheet: &Sheet<K>) -> HashMap<Column, ColumnType> where K: RowKey {
+    let mut types = HashMap::<Column, Option<ColumnType>>::new();
+    for column in sheet.columns() {
+        types.insert(column, None);
+    }
+    for row in sheet.rows.iter() {
+        for (column, value) in row.value().iter() {
+            let Some(value_type) = ColumnType::of(value) else {
+                continue;
+            };
+            let column_type = types.entry(column.clone()).or_default();
+            *column_type = Some(column_type.map_or(value_type, |column_type| column_type.and(value_type)));
+        }
+    }
+    types
+        .into_iter()
+        .map(|(column, column_type)| (column, column_type.unwrap_or(ColumnType::Numeric)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use crate::merge::{ColumnLabel, RowData};
+    use super::*;
+
+    #[test]
+    fn find_gaps() {
+        let sheet = Sheet::default();
+        let column = Column::new([ColumnLabel::create("Broad Money").unwrap()]).unwrap();
+        let year = Year(NonZeroU16::new(2009).unwrap());
+        for month in [Month::January, Month::February, Month::May] {
+            let mut row = RowData::default();
+            row.populate(&column, 1.0);
+            sheet.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        let coverage = coverage(&sheet);
+        assert_eq!(1, coverage.len());
+        assert_eq!(3, coverage[0].present);
+        assert_eq!(5, coverage[0].span_in_months());
+        assert_eq!(
+            vec![
+                Timestamp::Monthly(MonthlyReport::new(year, Month::March)),
+                Timestamp::Monthly(MonthlyReport::new(year, Month::April))
+            ],
+            coverage[0].missing
+        );
+    }
+
+    #[test]
+    fn quarters_sum_to_years() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let (exports, imports) = (column("Exports"), column("Imports"));
+        let year = |year| Year(NonZeroU16::new(year).unwrap());
+        let quarterly = Sheet::default();
+        // The fiscal year 2021-22 spans the last two quarters of 2021 and the first two of 2022
+        for (calendar_year, quarter) in [
+            (2021, Quarter::JulAugSep), (2021, Quarter::OctNovDec), (2022, Quarter::JanFebMar), (2022, Quarter::AprMayJun)
+        ] {
+            let mut row = RowData::default();
+            row.populate(&exports, 25.0);
+            row.populate(&imports, 10.0);
+            quarterly.add_row(Timestamp::Quarterly(year(calendar_year), quarter), row);
+        }
+        let fiscal = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&exports, 100.2);
+        row.populate(&imports, 45.0);
+        fiscal.add_row(Timestamp::FiscalYear(year(2021)), row);
+
+        assert_eq!(
+            vec![SumDiscrepancy { column: imports, year: Timestamp::FiscalYear(year(2021)), yearly: 45.0, sum_of_quarters: 40.0 }],
+            check_quarterly_sums(&quarterly, &fiscal, 0.01)
+        );
+        assert_eq!(2, check_quarterly_sums(&quarterly, &fiscal, 0.0).len());
+
+        // Neither calendar year has all four quarters
+        let calendar = Sheet::default();
+        let mut row = RowData::default();
+        row.populate(&exports, 1.0);
+        calendar.add_row(Timestamp::CalendarYear(year(2021)), row);
+        assert!(check_quarterly_sums(&quarterly, &calendar, 0.01).is_empty());
+    }
+
+    #[test]
+    fn classify_columns() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let (exports, remarks, ratio) = (column("Exports"), column("Remarks"), column("Ratio"));
+        let sheet = Sheet::default();
+        for (year, exports_value, ratio_value) in [(2020, "12", "1.5"), (2021, "n.a.", "see note"), (2022, "", "")] {
+            let mut row = RowData::default();
+            row.populate(&exports, exports_value);
+            row.populate(&remarks, "Estimated");
+            row.populate(&ratio, ratio_value);
+            sheet.add_row(Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap())), row);
+        }
+        let types = column_types(&sheet);
+        assert_eq!(3, types.len());
+        assert_eq!(ColumnType::Numeric, types[&exports]);
+        assert_eq!(ColumnType::Text, types[&remarks]);
+        assert_eq!(ColumnType::Mixed, types[&ratio]);
+    }
+}
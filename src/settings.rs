@@ -0,0 +1,123 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Settings from the command line and environment
+
+use std::env;
+use std::str::FromStr;
+use eyre::{Result, WrapErr};
+use log::LevelFilter;
+
+/// Reads a setting, first from the command line as --name=value or --name value,
+/// then from the environment as NAME (upper case, with underscores instead of hyphens)
+pub fn setting(name: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            return args.next();
+        }
+        if let Some(value) = arg.strip_prefix(&flag).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.to_string());
+        }
+    }
+    env::var(env_var_name(name)).ok()
+}
+
+/// Parses a setting, if present
+pub fn parsed_setting<T>(name: &str) -> Result<Option<T>>
+    where T: FromStr, T::Err: Into<eyre::Report> {
+
+    setting(name)
+        .map(|value| {
+            value.parse::<T>()
+                .map_err(Into::into)
+                .wrap_err_with(|| format!("Invalid value {} for {}", value, name))
+        })
+        .transpose()
+}
+
+/// Whether a switch is enabled, either by passing --name or by setting NAME to true/1/yes
+pub fn flag(name: &str) -> bool {
+    let flag = format!("--{}", name);
+    if env::args().skip(1).any(|arg| arg == flag) {
+        return true;
+    }
+    match env::var(env_var_name(name)) {
+        Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes"),
+        Err(_) => false
+    }
+}
+
+/// The verbosity of logging. --quiet shows only warnings and errors, while -v shows debug
+/// messages and -vv everything. Otherwise, RUST_LOG may name a level. Defaults to info
+pub fn log_level() -> Result<LevelFilter> {
+    let rust_log = env::var("RUST_LOG").ok();
+    log_level_from(env::args().skip(1), flag("quiet"), rust_log.as_deref())
+}
+
+fn log_level_from(args: impl Iterator<Item=String>, quiet: bool, rust_log: Option<&str>) -> Result<LevelFilter> {
+    let verbosity = args
+        .filter_map(|arg| {
+            let v_count = arg.strip_prefix('-')?;
+            (!v_count.is_empty() && v_count.bytes().all(|byte| byte == b'v')).then_some(v_count.len())
+        })
+        .sum::<usize>();
+    if quiet && verbosity > 0 {
+        return Err(eyre::eyre!("Cannot be both quiet and verbose"));
+    }
+    Ok(match (quiet, verbosity) {
+        (true, _) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Debug,
+        (false, 2..) => LevelFilter::Trace,
+        (false, 0) => match rust_log {
+            Some(level) => LevelFilter::from_str(level)
+                .map_err(|_| eyre::eyre!("Unknown log level {} in RUST_LOG. Use off, error, warn, info, debug, or trace", level))?,
+            None => LevelFilter::Info
+        }
+    })
+}
+
+fn env_var_name(name: &str) -> String {
+    name.to_uppercase().replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choose_log_level() {
+        let level = |args: &[&str], quiet, rust_log| {
+            log_level_from(args.iter().map(|arg| arg.to_string()), quiet, rust_log).unwrap()
+        };
+        assert_eq!(LevelFilter::Info, level(&[], false, None));
+        assert_eq!(LevelFilter::Warn, level(&["--quiet"], true, None));
+        assert_eq!(LevelFilter::Debug, level(&["-v"], false, None));
+        assert_eq!(LevelFilter::Trace, level(&["-vv"], false, None));
+        assert_eq!(LevelFilter::Trace, level(&["-v", "-v"], false, None));
+        assert_eq!(LevelFilter::Error, level(&[], false, Some("error")));
+        // Flags take precedence over the environment
+        assert_eq!(LevelFilter::Debug, level(&["-v"], false, Some("error")));
+        // Other arguments with a leading hyphen are not verbosity
+        assert_eq!(LevelFilter::Info, level(&["--verbose", "-"], false, None));
+        assert!(log_level_from(std::iter::empty(), false, Some("loud")).is_err());
+        assert!(log_level_from(["-v".to_string()].into_iter(), true, None).is_err());
+    }
+}
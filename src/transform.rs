@@ -0,0 +1,500 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Transformations of merged sheets
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+use crate::common::*;
+use crate::merge::{Column, ColumnLabel, RowData, RowKey, Sheet};
+use crate::value::Value;
+
+/// Combines the monthly values of a column within a period into one
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Reducer {
+    Sum,
+    #[default]
+    Mean,
+    /// The value of the final month, for stocks such as end-of-period balances
+    Last
+}
+
+/// How gaps between known monthly values are filled
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum FillMethod {
+    /// Repeats the last known value
+    #[default]
+    Forward,
+    /// Draws a straight line between the known values on either side
+    Linear
+}
+
+/// Aggregates monthly data into longer periods
+#[derive(Clone, Debug)]
+pub struct Aggregation {
+    pub target: Granularity,
+    /// Applies to columns lacking their own reducer
+    pub default_reducer: Reducer,
+    pub reducers: HashMap<Column, Reducer>
+}
+
+impl Reducer {
+    /// Reduces the values of consecutive months, given in order
+    fn reduce(&self, values: &[f64]) -> f64 {
+        match self {
+            Self::Sum => values.iter().sum(),
+            Self::Mean => values.iter().sum::<f64>() / values.len() as f64,
+            Self::Last => values.last().copied().unwrap_or(f64::NAN)
+        }
+    }
+}
+
+impl FromStr for Reducer {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "sum" => Ok(Self::Sum),
+            "mean" => Ok(Self::Mean),
+            "last" => Ok(Self::Last),
+            other => Err(eyre::eyre!("Unknown reducer {}. Use sum, mean, or last", other))
+        }
+    }
+}
+
+impl FromStr for FillMethod {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "forward" => Ok(Self::Forward),
+            "linear" => Ok(Self::Linear),
+            other => Err(eyre::eyre!("Unknown fill method {}. Use forward or linear", other))
+        }
+    }
+}
+
+impl Aggregation {
+    pub fn new(target: Granularity, default_reducer: Reducer) -> Self {
+        Self {
+            target,
+            default_reducer,
+            reducers: HashMap::new()
+        }
+    }
+
+    fn reducer_for(&self, column: &Column) -> Reducer {
+        self.reducers.get(column).copied().unwrap_or(self.default_reducer)
+    }
+}
+
+/// Aggregates the monthly rows of the sheet into periods of the target granularity.
+///
+/// Only complete periods are aggregated, because a sum or mean over some of the months would
+/// misrepresent the period. Columns holding text cannot be reduced, and are left out
+pub fn aggregate(monthly: &Sheet, aggregation: &Aggregation) -> Sheet {
+    let mut periods = HashMap::<Column, HashMap<Timestamp, Vec<(MonthlyReport, f64)>>>::new();
+    let mut non_numeric = HashSet::new();
+    for row in monthly.rows.iter() {
+        let Timestamp::Monthly(report) = *row.key() else {
+            continue;
+        };
+        let Some(period) = aggregation.target.period_containing(report) else {
+            continue;
+        };
+        for (column, value) in row.value().iter() {
+            match value {
+                Value::Number(number) => {
+                    periods.entry(column.clone()).or_default().entry(period).or_default().push((report, *number));
+                }
+                Value::Text(_) => {
+                    non_numeric.insert(column.clone());
+                }
+                Value::Empty => {}
+            }
+        }
+    }
+
+    let aggregated = Sheet::default();
+    for (column, periods) in periods {
+        if non_numeric.contains(&column) {
+            log::debug!("Not aggregating column {} which holds text", column);
+            continue;
+        }
+        let reducer = aggregation.reducer_for(&column);
+        for (period, mut values) in periods {
+            if values.len() != period.month_count() as usize {
+                continue;
+            }
+            values.sort_by_key(|(report, _)| *report);
+            let values = values.into_iter().map(|(_, number)| number).collect::<Vec<_>>();
+            let mut row = RowData::default();
+            row.populate(&column, reducer.reduce(&values));
+            aggregated.add_row(period, row);
+        }
+    }
+    aggregated
+}
+
+/// Copies the monthly sheet, filling the months missing between the first and last known
+/// numbers of each column. Filled cells are marked as such in their rows. Months whose cell
+/// holds text are left alone
+pub fn fill_gaps(monthly: &Sheet, method: FillMethod) -> Sheet {
+    let filled = Sheet::default();
+    let mut series = HashMap::<Column, Vec<(MonthlyReport, f64)>>::new();
+    for row in monthly.rows.iter() {
+        if monthly.is_provisional(row.key()) {
+            filled.mark_provisional(*row.key());
+        }
+        filled.add_row(*row.key(), row.value().clone());
+        let Timestamp::Monthly(report) = *row.key() else {
+            continue;
+        };
+        for (column, value) in row.value().iter() {
+            if let Value::Number(number) = value {
+                series.entry(column.clone()).or_default().push((report, *number));
+            }
+        }
+    }
+
+    for (column, mut known) in series {
+        known.sort_by_key(|(report, _)| *report);
+        for pair in known.windows(2) {
+            let ((start, start_value), (end, end_value)) = (pair[0], pair[1]);
+            let gap = std::iter::successors(start.next(), MonthlyReport::next)
+                .take_while(|report| *report < end)
+                .collect::<Vec<_>>();
+            let span = (gap.len() + 1) as f64;
+            for (step, report) in (1..).zip(gap) {
+                let timestamp = Timestamp::Monthly(report);
+                let has_text = monthly.get(&timestamp).is_some_and(|row| row.get(&column).is_some_and(|value| !value.is_empty()));
+                if has_text {
+                    continue;
+                }
+                let value = match method {
+                    FillMethod::Forward => start_value,
+                    FillMethod::Linear => start_value + (end_value - start_value) * f64::from(step) / span
+                };
+                let mut row = RowData::default();
+                row.populate(&column, value);
+                row.mark_filled(&column);
+                filled.add_row(timestamp, row);
+            }
+        }
+    }
+    filled
+}
+
+/// Splits the sheet by top-level category, the first label of each column, into one sheet per
+/// category. Each row appears in the sheets of the categories it has values for, with its flags.
+/// Categories are in order of their labels
+pub fn split_by_category<K>(sheet: &Sheet<K>) -> Vec<(String, Sheet<K>)> where K: RowKey {
+    let mut categories = BTreeMap::<String, Sheet<K>>::new();
+    for row in sheet.rows.iter() {
+        let mut split = HashMap::<&str, RowData>::new();
+        for (column, value) in row.value().iter().filter(|(_, value)| !value.is_empty()) {
+            let category_row = split.entry(column.label_at(0).unwrap_or_default()).or_default();
+            category_row.populate(column, value.clone());
+            if sheet.is_percentage(column) {
+                category_row.mark_percentage(column);
+            }
+            for unit in sheet.units(column) {
+                category_row.mark_unit(column, unit);
+            }
+        }
+        for (category, mut category_row) in split {
+            if row.value().is_revised() {
+                category_row.mark_revised();
+            }
+            let category_sheet = categories.entry(category.to_string()).or_default();
+            if sheet.is_provisional(row.key()) {
+                category_sheet.mark_provisional(row.key().clone());
+            }
+            category_sheet.add_row(row.key().clone(), category_row);
+        }
+    }
+    categories.into_iter().collect()
+}
+
+/// Marks which the central bank appends to labels to refer to footnotes
+const FOOTNOTE_MARKERS: [char; 14] = ['*', '†', '‡', '#', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹', '⁰'];
+
+/// Merges columns whose labels differ only by trailing footnote markers, such as Deposits 1 and
+/// Deposits* into Deposits. A column is merged only into the unmarked column, which must exist,
+/// so that series like Tier 1 and Tier 2 stay apart. Yields each merged column and the column
+/// it was merged into
+pub fn consolidate_footnotes<K>(sheet: &Sheet<K>) -> Vec<(Column, Column)> where K: RowKey {
+    let columns = sheet.columns().collect::<HashSet<_>>();
+    let mut merged = columns
+        .iter()
+        .filter_map(|column| {
+            let unmarked = without_footnotes(column)?;
+            columns.contains(&unmarked).then(|| (column.clone(), unmarked))
+        })
+        .collect::<Vec<_>>();
+    // Where several marked columns fill the same cell, the first by label wins
+    merged.sort_by_key(|(column, _)| column.display_full_labeling());
+    for (column, unmarked) in &merged {
+        sheet.merge_column(column, unmarked);
+    }
+    merged
+}
+
+/// The column with footnote markers removed from its labels, if it has any
+fn without_footnotes(column: &Column) -> Option<Column> {
+    let labels = (0..column.depth())
+        .filter_map(|level| column.label_at(level))
+        .map(|label| {
+            let unmarked = strip_footnote(label);
+            ColumnLabel::create(unmarked).filter(|_| !unmarked.is_empty()).map(|unmarked| (label, unmarked))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if labels.iter().all(|(label, unmarked)| *label == unmarked.as_ref()) {
+        return None;
+    }
+    Column::new(labels.into_iter().map(|(_, unmarked)| unmarked)).ok()
+}
+
+/// Removes trailing footnote symbols, and footnote numbers of one or two digits set apart by a
+/// space. Numbers within labels, as in M2 or Column 100, are kept
+fn strip_footnote(label: &str) -> &str {
+    let label = label.trim_end_matches(FOOTNOTE_MARKERS).trim_end();
+    match label.rsplit_once(' ') {
+        Some((rest, digits)) if (1..=2).contains(&digits.len()) && digits.bytes().all(|byte| byte.is_ascii_digit()) => {
+            rest.trim_end()
+        },
+        _ => label
+    }
+}
+
+/// Merges columns whose labels differ only by case, such as TOTAL DEPOSITS and Total deposits.
+/// The column filling the most cells gives the canonical casing. On a tie, a label not written
+/// in capitals is preferred. Yields each merged column and the column it was merged into
+pub fn consolidate_case<K>(sheet: &Sheet<K>) -> Vec<(Column, Column)> where K: RowKey {
+    let mut cell_counts = HashMap::<Column, usize>::new();
+    for column in sheet.columns() {
+        cell_counts.insert(column, 0);
+    }
+    for row in sheet.rows.iter() {
+        for (column, _) in row.value().iter() {
+            *cell_counts.entry(column.clone()).or_default() += 1;
+        }
+    }
+    let mut variants = BTreeMap::<Vec<String>, Vec<Column>>::new();
+    for column in cell_counts.keys() {
+        let folded = (0..column.depth())
+            .filter_map(|level| column.label_at(level))
+            .map(str::to_lowercase)
+            .collect();
+        variants.entry(folded).or_default().push(column.clone());
+    }
+    let mut merged = Vec::new();
+    for mut columns in variants.into_values().filter(|columns| columns.len() > 1) {
+        columns.sort_by_cached_key(|column| {
+            let labeling = column.display_full_labeling();
+            let capitals = !labeling.chars().any(char::is_lowercase);
+            (std::cmp::Reverse(cell_counts[column]), capitals, labeling)
+        });
+        let canonical = columns.remove(0);
+        for column in columns {
+            sheet.merge_column(&column, &canonical);
+            merged.push((column, canonical.clone()));
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU16;
+    use super::*;
+
+    #[test]
+    fn aggregate_quarters() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let (exports, reserves, remarks) = (column("Exports"), column("Reserves"), column("Remarks"));
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        let monthly = Sheet::default();
+        // January through May, so that the second quarter is incomplete
+        for (month, value) in Month::values().into_iter().take(5).zip(1..) {
+            let mut row = RowData::default();
+            row.populate(&exports, f64::from(value));
+            row.populate(&reserves, f64::from(value * 10));
+            row.populate(&remarks, if value == 2 { Value::from("revised") } else { Value::from(0.0) });
+            monthly.add_row(Timestamp::Monthly(MonthlyReport::new(year, month)), row);
+        }
+        let mut aggregation = Aggregation::new(Granularity::Quarterly, Reducer::Sum);
+        aggregation.reducers.insert(reserves.clone(), Reducer::Last);
+
+        let quarterly = aggregate(&monthly, &aggregation);
+        let first_quarter = Timestamp::Quarterly(year, Quarter::JanFebMar);
+        assert_eq!(vec![first_quarter], quarterly.keys_in_order());
+        let row = quarterly.get(&first_quarter).unwrap();
+        assert_eq!(Some(&Value::Number(6.0)), row.get(&exports));
+        assert_eq!(Some(&Value::Number(30.0)), row.get(&reserves));
+        assert_eq!(None, row.get(&remarks));
+
+        let half_year = aggregate(&monthly, &Aggregation::new(Granularity::BiAnnual, Reducer::Mean));
+        assert_eq!(0, half_year.row_count());
+    }
+
+    #[test]
+    fn merge_case_variants() {
+        let column = |labels: &[&str]| Column::new(labels.iter().map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let (title, capitals, sentence) = (column(&["Total Deposits"]), column(&["TOTAL DEPOSITS"]), column(&["Total deposits"]));
+        let (upper, lower) = (column(&["Money", "M2"]), column(&["MONEY", "M2"]));
+        let exports = column(&["Exports"]);
+        let sheet = Sheet::default();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let mut row = RowData::default();
+        row.populate(&title, 10.0);
+        row.populate(&capitals, 11.0);
+        row.populate(&upper, 1.0);
+        row.populate(&exports, 5.0);
+        sheet.add_row(year(2020), row);
+        let mut row = RowData::default();
+        row.populate(&capitals, 21.0);
+        row.populate(&lower, 2.0);
+        sheet.add_row(year(2021), row);
+        let mut row = RowData::default();
+        row.populate(&sentence, 30.0);
+        sheet.add_row(year(2022), row);
+
+        let mut merged = consolidate_case(&sheet);
+        merged.sort_by_key(|(column, _)| column.display_full_labeling());
+        // Capitals fill the most cells. Between the others, the label not in capitals wins
+        assert_eq!(vec![
+            (lower, upper.clone()), (title, capitals.clone()), (sentence, capitals.clone())
+        ], merged);
+        let mut columns = sheet.columns().map(|column| column.display_full_labeling()).collect::<Vec<_>>();
+        columns.sort();
+        assert_eq!(vec!["Exports", "Money.M2", "TOTAL DEPOSITS"], columns);
+        // The canonical column's own values are kept
+        let values = [2020, 2021, 2022].map(|key| sheet.get(&year(key)).unwrap().get(&capitals).cloned());
+        assert_eq!([Some(Value::Number(11.0)), Some(Value::Number(21.0)), Some(Value::Number(30.0))], values);
+    }
+
+    #[test]
+    fn merge_footnoted_columns() {
+        let column = |labels: &[&str]| Column::new(labels.iter().map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let deposits = column(&["Deposits"]);
+        let (numbered, starred) = (column(&["Deposits 1"]), column(&["Deposits*"]));
+        let (tier_1, tier_2) = (column(&["Capital", "Tier 1"]), column(&["Capital", "Tier 2"]));
+        let broad_money = column(&["Money", "M2"]);
+        let sheet = Sheet::default();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let mut row = RowData::default();
+        row.populate(&deposits, 10.0);
+        row.populate(&starred, 11.0);
+        row.populate(&tier_1, 1.0);
+        row.populate(&tier_2, 2.0);
+        row.populate(&broad_money, 5.0);
+        sheet.add_row(year(2020), row);
+        let mut row = RowData::default();
+        row.populate(&numbered, 20.0);
+        sheet.add_row(year(2021), row);
+        let mut row = RowData::default();
+        row.populate(&starred, 30.0);
+        sheet.add_row(year(2022), row);
+
+        let merged = consolidate_footnotes(&sheet);
+        assert_eq!(vec![(numbered, deposits.clone()), (starred, deposits.clone())], merged);
+        let mut columns = sheet.columns().map(|column| column.display_full_labeling()).collect::<Vec<_>>();
+        columns.sort();
+        assert_eq!(vec!["Capital.Tier 1", "Capital.Tier 2", "Deposits", "Money.M2"], columns);
+        // The unmarked column's own values are kept
+        let values = [2020, 2021, 2022].map(|key| sheet.get(&year(key)).unwrap().get(&deposits).cloned());
+        assert_eq!([Some(Value::Number(10.0)), Some(Value::Number(20.0)), Some(Value::Number(30.0))], values);
+    }
+
+    #[test]
+    fn fill_monthly_gaps() {
+        let column = |label: &str| Column::new([ColumnLabel::create(label).unwrap()]).unwrap();
+        let reserves = column("Reserves");
+        let year = Year(NonZeroU16::new(2022).unwrap());
+        let month = |month| Timestamp::Monthly(MonthlyReport::new(year, month));
+        let monthly = Sheet::default();
+        for (report, value) in [(Month::January, Value::from(10.0)), (Month::April, Value::from(40.0)), (Month::March, Value::from("n.a."))] {
+            let mut row = RowData::default();
+            row.populate(&reserves, value);
+            monthly.add_row(month(report), row);
+        }
+
+        let forward = fill_gaps(&monthly, FillMethod::Forward);
+        assert_eq!(vec![month(Month::January), month(Month::February), month(Month::March), month(Month::April)], forward.keys_in_order());
+        let february = forward.get(&month(Month::February)).unwrap();
+        assert_eq!(Some(&Value::Number(10.0)), february.get(&reserves));
+        assert!(february.is_filled(&reserves));
+        // Text is not overwritten, and observed values are not marked
+        let march = forward.get(&month(Month::March)).unwrap();
+        assert_eq!(Some(&Value::from("n.a.")), march.get(&reserves));
+        assert!(!march.is_filled(&reserves));
+        assert!(!forward.get(&month(Month::April)).unwrap().is_filled(&reserves));
+
+        let linear = fill_gaps(&monthly, FillMethod::Linear);
+        assert_eq!(Some(&Value::Number(20.0)), linear.get(&month(Month::February)).unwrap().get(&reserves));
+        // Nothing is filled outside the observed span
+        assert_eq!(4, linear.row_count());
+        assert!(monthly.get(&month(Month::February)).is_none());
+        assert!(FillMethod::from_str("cubic").is_err());
+    }
+
+    #[test]
+    fn split_categories() {
+        let column = |labels: &[&str]| Column::new(labels.iter().map(|label| ColumnLabel::create(label).unwrap())).unwrap();
+        let (broad_money, reserve_money) = (column(&["Money", "M2"]), column(&["Money", "Reserve money"]));
+        let cpi = column(&["Prices", "CPI"]);
+        let sheet = Sheet::default();
+        let year = |year| Timestamp::CalendarYear(Year(NonZeroU16::new(year).unwrap()));
+        let mut row = RowData::default();
+        row.populate(&broad_money, 5.0);
+        row.populate(&reserve_money, 2.0);
+        row.populate(&cpi, Value::Empty);
+        row.mark_revised();
+        sheet.add_row(year(2020), row);
+        let mut row = RowData::default();
+        row.populate(&cpi, 110.0);
+        sheet.add_row(year(2021), row);
+        sheet.mark_provisional(year(2021));
+
+        let split = split_by_category(&sheet);
+        let names = split.iter().map(|(category, _)| category.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["Money", "Prices"], names);
+        let (money, prices) = (&split[0].1, &split[1].1);
+        assert_eq!(vec![year(2020)], money.keys_in_order());
+        assert_eq!(2, money.columns().count());
+        assert!(money.get(&year(2020)).unwrap().is_revised());
+        // Empty cells do not place a row in a category
+        assert_eq!(vec![year(2021)], prices.keys_in_order());
+        assert_eq!(Some(&Value::Number(110.0)), prices.get(&year(2021)).unwrap().get(&cpi));
+        assert!(prices.is_provisional(&year(2021)));
+    }
+
+    #[test]
+    fn fiscal_year_periods() {
+        let year = |year| Year(NonZeroU16::new(year).unwrap());
+        let june = MonthlyReport::new(year(2022), Month::June);
+        let july = MonthlyReport::new(year(2022), Month::July);
+        assert_eq!(Some(Timestamp::FiscalYear(year(2021))), Granularity::FiscalYear.period_containing(june));
+        assert_eq!(Some(Timestamp::FiscalYear(year(2022))), Granularity::FiscalYear.period_containing(july));
+        assert_eq!(Some(Timestamp::BiAnnually(year(2022), HalfYear::JulThruDec)), Granularity::BiAnnual.period_containing(july));
+        assert_eq!(12, Timestamp::FiscalYear(year(2022)).month_count());
+        assert_eq!(3, Timestamp::Quarterly(year(2022), Quarter::JulAugSep).month_count());
+    }
+}
@@ -0,0 +1,393 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Typed cell values
+
+use std::fmt::{Display, Formatter};
+use calamine::DataType;
+use serde::{Deserialize, Serialize};
+
+/// A single data point. Numbers are kept as such so that they need not be re-parsed later
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Number(f64),
+    Text(Box<str>),
+    Empty
+}
+
+/// Text written in place of a figure which is not available
+const PLACEHOLDERS: [&str; 6] = ["NA", "N/A", "n.a.", "-", "--", "..."];
+
+/// Controls how cell values are interpreted
+#[derive(Clone, Debug, Default)]
+pub struct ValueOptions {
+    /// Whether to convert figures such as "12.5 crore" to plain numbers. Off by default,
+    /// because it changes the magnitude of the data
+    pub normalize_units: bool,
+    /// Whether percentages such as "5.25%" become fractions such as 0.0525, rather than 5.25
+    pub percent_as_fraction: bool
+}
+
+/// Units of magnitude which the central bank sometimes appends to figures
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum Unit {
+    Lakh,
+    Million,
+    Crore
+}
+
+/// A value read from a cell, along with the unit that was converted and the footnote
+/// marker that was removed, if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedValue {
+    pub value: Value,
+    pub unit: Option<Unit>,
+    pub footnote: Option<Footnote>,
+    /// Whether the figure was written as a percentage, with its % sign removed
+    pub percent: bool
+}
+
+/// Footnote markers which the central bank attaches to timestamps and figures alike
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Footnote {
+    /// An asterisk, which points to a note at the bottom of the sheet
+    Asterisk,
+    /// The 'R' superscript means revised. ® is also used
+    Revised
+}
+
+impl Footnote {
+    /// Splits a trailing footnote marker from the value
+    pub fn strip_from(value: &str) -> (&str, Option<Self>) {
+        let footnote = match value.chars().next_back() {
+            Some('*') => Self::Asterisk,
+            Some('R') | Some('®') => Self::Revised,
+            _ => return (value, None)
+        };
+        let marker_len = value.chars().next_back().map_or(0, char::len_utf8);
+        (&value[..value.len() - marker_len], Some(footnote))
+    }
+}
+
+impl Unit {
+    pub(crate) const ALL: [Self; 3] = [Self::Lakh, Self::Million, Self::Crore];
+
+    fn names(&self) -> &'static [&'static str] {
+        match self {
+            Self::Lakh => &["lakh", "lac"],
+            Self::Million => &["million", "mn"],
+            Self::Crore => &["crore"]
+        }
+    }
+
+    /// The multiplier taking a figure in this unit to a raw number
+    pub fn factor(&self) -> f64 {
+        match self {
+            Self::Lakh => 1e5,
+            Self::Million => 1e6,
+            Self::Crore => 1e7
+        }
+    }
+
+    /// Splits a trailing unit from the value, ignoring case
+    fn strip_from(value: &str) -> Option<(&str, Self)> {
+        let lowercase = value.to_lowercase();
+        for unit in Self::ALL {
+            for name in unit.names() {
+                if lowercase.ends_with(name) && value.is_char_boundary(value.len() - name.len()) {
+                    return Some((&value[..value.len() - name.len()], unit));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Value {
+    /// Reads the value of a spreadsheet cell. Textual cells are parsed as numbers if possible
+    pub fn from_cell(cell: &DataType, options: &ValueOptions) -> ParsedValue {
+        let value = match cell {
+            DataType::Int(value) => Self::Number(*value as f64),
+            DataType::Float(value) => Self::Number(*value),
+            DataType::String(value) => return Self::parse_with(value, options),
+            DataType::Empty => Self::Empty,
+            other => Self::Text(Box::from(other.to_string()))
+        };
+        ParsedValue { value, unit: None, footnote: None, percent: false }
+    }
+
+    /// Parses a value from text according to the given options.
+    ///
+    /// Footnote markers are removed from figures, e.g. "1234*" is the number 1234. Text which
+    /// is not a figure is kept exactly as it is, markers included.
+    pub fn parse_with(value: &str, options: &ValueOptions) -> ParsedValue {
+        let (figure, footnote) = Footnote::strip_from(value.trim());
+        if footnote.is_some() {
+            let parsed = Self::parse_figure(figure, options);
+            if let Value::Number(_) = parsed.value {
+                return ParsedValue { footnote, ..parsed };
+            }
+        }
+        Self::parse_figure(value, options)
+    }
+
+    fn parse_figure(value: &str, options: &ValueOptions) -> ParsedValue {
+        // Interest rates and inflation are often written as "5.25%"
+        if let Some(number) = value.trim().strip_suffix('%').and_then(parse_number) {
+            return ParsedValue {
+                value: Self::Number(if options.percent_as_fraction { number / 100.0 } else { number }),
+                unit: None,
+                footnote: None,
+                percent: true
+            };
+        }
+        if options.normalize_units {
+            if let Some((figure, unit)) = Unit::strip_from(value.trim()) {
+                if let Some(number) = parse_number(figure) {
+                    return ParsedValue {
+                        value: Self::Number(number * unit.factor()),
+                        unit: Some(unit),
+                        footnote: None,
+                        percent: false
+                    };
+                }
+            }
+        }
+        ParsedValue { value: Self::parse(value), unit: None, footnote: None, percent: false }
+    }
+
+    /// Parses a value from text, falling back to the text itself if it is not a number
+    pub fn parse(value: &str) -> Self {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            return Self::Empty;
+        }
+        match parse_number(trimmed) {
+            Some(number) => Self::Number(number),
+            None => Self::Text(Box::from(value))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, Self::Empty)
+    }
+
+    /// Whether this is no real value, being empty or a placeholder such as NA
+    pub fn is_placeholder(&self) -> bool {
+        match self {
+            Self::Empty => true,
+            Self::Text(text) => PLACEHOLDERS.contains(&text.trim()),
+            Self::Number(_) => false
+        }
+    }
+}
+
+/// Parses a decimal number as the central bank writes it. Unlike f64::from_str, this does not
+/// accept words like "inf" or "NaN", which are legitimate text in a spreadsheet.
+///
+/// Accounting conventions are understood: grouping commas are removed, "1,234.5" and the
+/// South Asian "1,23,456" alike, and a parenthesized figure such as "(123)" is negative.
+fn parse_number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Some(inner) = value.strip_prefix('(').and_then(|rest| rest.strip_suffix(')')) {
+        return parse_number(inner).map(|number| -number);
+    }
+    let first_char = value.chars().next()?;
+    if !(first_char.is_ascii_digit() || ['-', '+', '.'].contains(&first_char)) {
+        return None;
+    }
+    if value.contains(',') {
+        return parse_number_with_grouping(value);
+    }
+    value.parse::<f64>().ok().filter(|number| number.is_finite())
+}
+
+/// Every comma must sit between two digits of the integer part
+fn parse_number_with_grouping(value: &str) -> Option<f64> {
+    let integer_part = value.split('.').next().unwrap_or(value);
+    let bytes = integer_part.as_bytes();
+    for (index, byte) in bytes.iter().enumerate() {
+        if *byte == b',' {
+            let between_digits = index > 0
+                && bytes[index - 1].is_ascii_digit()
+                && bytes.get(index + 1).is_some_and(u8::is_ascii_digit);
+            if !between_digits {
+                return None;
+            }
+        }
+    }
+    if value[integer_part.len()..].contains(',') {
+        // Commas in the fractional part
+        return None;
+    }
+    parse_number(&value.replace(',', ""))
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::parse(value)
+    }
+}
+
+/// Numbers are written in their shortest form which reads back to the same value, e.g. 12.5
+impl Display for Unit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.names()[0])
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            // Plain decimals, never scientific notation, with the fewest digits which read
+            // back as the same number. "1.23E+08" is written as 123000000
+            Self::Number(number) => Display::fmt(number, f),
+            Self::Text(text) => f.write_str(text),
+            Self::Empty => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_numbers() {
+        assert_eq!(Value::Number(12.5), Value::parse("12.5"));
+        assert_eq!(Value::Number(-3.0), Value::parse(" -3 "));
+        assert_eq!(Value::Text(Box::from("inf")), Value::parse("inf"));
+        assert_eq!(Value::Text(Box::from("n.a.")), Value::parse("n.a."));
+        assert_eq!(Value::Empty, Value::parse("  "));
+    }
+
+    #[test]
+    fn parse_accounting_numbers() {
+        assert_eq!(Value::Number(1234.0), Value::parse("1,234"));
+        assert_eq!(Value::Number(123456.0), Value::parse("1,23,456"));
+        assert_eq!(Value::Number(-1234.5), Value::parse("(1,234.5)"));
+        assert_eq!(Value::Number(-123.0), Value::parse(" (123) "));
+        assert_eq!(Value::Number(1234.5), Value::parse(" 1,234.5\t"));
+    }
+
+    #[test]
+    fn keep_non_numeric_text() {
+        for text in ["Dhaka, Chittagong", "1,,234", ",123", "123,", "1.5,0", "(note)", "()"] {
+            assert_eq!(Value::Text(Box::from(text)), Value::parse(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn read_cells() {
+        let options = ValueOptions::default();
+        let read = |cell| Value::from_cell(&cell, &options).value;
+        assert_eq!(Value::Number(7.0), read(DataType::Int(7)));
+        assert_eq!(Value::Number(0.25), read(DataType::Float(0.25)));
+        assert_eq!(Value::Number(4.0), read(DataType::String(String::from("4"))));
+        assert_eq!(Value::Empty, read(DataType::Empty));
+    }
+
+    #[test]
+    fn normalize_units() {
+        let options = ValueOptions { normalize_units: true, ..Default::default() };
+        assert_eq!(
+            ParsedValue { value: Value::Number(125_000_000.0), unit: Some(Unit::Crore), footnote: None, percent: false },
+            Value::parse_with("12.5 crore", &options)
+        );
+        assert_eq!(Some(Unit::Lakh), Value::parse_with("3 Lakh", &options).unit);
+        assert_eq!(Value::Number(2_000_000.0), Value::parse_with("2million", &options).value);
+        assert_eq!(
+            ParsedValue { value: Value::Text(Box::from("Taka in crore")), unit: None, footnote: None, percent: false },
+            Value::parse_with("Taka in crore", &options)
+        );
+        // Opt-in only
+        assert_eq!(Value::Text(Box::from("12.5 crore")), Value::parse_with("12.5 crore", &ValueOptions::default()).value);
+    }
+
+    #[test]
+    fn strip_footnotes() {
+        let options = ValueOptions::default();
+        assert_eq!(
+            ParsedValue { value: Value::Number(1234.0), unit: None, footnote: Some(Footnote::Asterisk), percent: false },
+            Value::parse_with("1234*", &options)
+        );
+        assert_eq!(
+            ParsedValue { value: Value::Number(12.3), unit: None, footnote: Some(Footnote::Revised), percent: false },
+            Value::parse_with("12.3R", &options)
+        );
+        assert_eq!(Some(Footnote::Revised), Value::parse_with("(1,234)®", &options).footnote);
+        // Text is left alone
+        assert_eq!(
+            ParsedValue { value: Value::Text(Box::from("BDR")), unit: None, footnote: None, percent: false },
+            Value::parse_with("BDR", &options)
+        );
+    }
+
+    #[test]
+    fn strip_percent_signs() {
+        let options = ValueOptions::default();
+        assert_eq!(
+            ParsedValue { value: Value::Number(5.25), unit: None, footnote: None, percent: true },
+            Value::parse_with("5.25%", &options)
+        );
+        assert_eq!(
+            ParsedValue { value: Value::Number(100.0), unit: None, footnote: None, percent: true },
+            Value::parse_with("100 %", &options)
+        );
+        assert_eq!(Some(Footnote::Asterisk), Value::parse_with("6%*", &options).footnote);
+        let fractions = ValueOptions { percent_as_fraction: true, ..Default::default() };
+        assert_eq!(Value::Number(0.0525), Value::parse_with("5.25%", &fractions).value);
+        // Neither plain numbers nor text are percentages
+        assert!(!Value::parse_with("5.25", &fractions).percent);
+        assert_eq!(Value::Number(5.25), Value::parse_with("5.25", &fractions).value);
+        assert_eq!(
+            ParsedValue { value: Value::Text(Box::from("Growth %")), unit: None, footnote: None, percent: false },
+            Value::parse_with("Growth %", &options)
+        );
+    }
+
+    #[test]
+    fn scientific_notation() {
+        // Written out in plain decimals, which every CSV reader understands
+        let cases = [("1.23E+08", "123000000"), ("1.5e-3", "0.0015"), ("-2E5", "-200000"), ("4.0E+00", "4")];
+        for (scientific, decimal) in cases {
+            assert_eq!(decimal, Value::parse(scientific).to_string());
+        }
+        let options = ValueOptions::default();
+        assert_eq!(Value::Number(123_000_000.0), Value::from_cell(&DataType::Float(1.23E+08), &options).value);
+        assert_eq!("123000000", Value::Number(1.23E+08).to_string());
+        // Text merely containing an E is kept as it is
+        for text in ["E-commerce", "2E", "1.2E+", "EUR 12", "12 E"] {
+            assert_eq!(Value::Text(Box::from(text)), Value::parse(text));
+        }
+    }
+
+    #[test]
+    fn display_canonical() {
+        assert_eq!("100", Value::Number(100.0).to_string());
+        assert_eq!("12.5", Value::Number(12.5).to_string());
+        assert_eq!("text", Value::Text(Box::from("text")).to_string());
+    }
+}
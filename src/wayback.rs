@@ -0,0 +1,105 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Lookups against the Internet Archive's Wayback Machine, for reports no longer on the live site
+
+use serde::Deserialize;
+use eyre::{Result, WrapErr};
+
+/// Answers whether, and when, a url was archived
+pub const AVAILABILITY_API: &str = "https://archive.org/wayback/available";
+/// Serves archived snapshots
+pub const ARCHIVE_PREFIX: &str = "https://web.archive.org/web";
+
+#[derive(Deserialize)]
+struct Availability {
+    archived_snapshots: ArchivedSnapshots
+}
+
+#[derive(Deserialize)]
+struct ArchivedSnapshots {
+    closest: Option<Snapshot>
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    available: bool,
+    status: String,
+    timestamp: String
+}
+
+/// The availability API request asking for the snapshot of a url
+pub fn availability_url(url: &str) -> String {
+    format!("{}?url={}", AVAILABILITY_API, percent_encode(url))
+}
+
+/// Reads the availability API's response. Yields the url of the archived file as originally
+/// served, without the Wayback Machine's page decorations, if a successful snapshot exists
+pub fn snapshot_url(url: &str, availability: &[u8]) -> Result<Option<String>> {
+    let availability = serde_json::from_slice::<Availability>(availability)
+        .wrap_err_with(|| format!("Unexpected Wayback Machine response for {}", url))?;
+    Ok(availability.archived_snapshots.closest
+        .filter(|snapshot| snapshot.available && snapshot.status == "200")
+        .map(|snapshot| format!("{}/{}id_/{}", ARCHIVE_PREFIX, snapshot.timestamp, url)))
+}
+
+/// Escapes everything except unreserved characters, for use as a query parameter
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_availability() {
+        assert_eq!(
+            "https://archive.org/wayback/available?url=https%3A%2F%2Fwww.bb.org.bd%2Fet%20jan2014.xls",
+            availability_url("https://www.bb.org.bd/et jan2014.xls")
+        );
+    }
+
+    #[test]
+    fn read_snapshots() {
+        let url = "https://www.bb.org.bd/pub/monthly/econtrds/etjan2014.xls";
+        let archived = br#"{"url": "https://www.bb.org.bd/pub/monthly/econtrds/etjan2014.xls", "archived_snapshots": {
+            "closest": {"status": "200", "available": true, "timestamp": "20140312093512",
+                        "url": "http://web.archive.org/web/20140312093512/https://www.bb.org.bd/pub/monthly/econtrds/etjan2014.xls"}
+        }}"#;
+        assert_eq!(
+            Some(format!("https://web.archive.org/web/20140312093512id_/{}", url)),
+            snapshot_url(url, archived).unwrap()
+        );
+        let not_found = br#"{"url": "x", "archived_snapshots": {
+            "closest": {"status": "404", "available": true, "timestamp": "20140312093512", "url": "x"}
+        }}"#;
+        assert_eq!(None, snapshot_url(url, not_found).unwrap());
+        assert_eq!(None, snapshot_url(url, br#"{"url": "x", "archived_snapshots": {}}"#).unwrap());
+        assert!(snapshot_url(url, b"<html>Rate limited</html>").is_err());
+    }
+}
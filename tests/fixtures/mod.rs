@@ -0,0 +1,113 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+// Synthetic workbooks resembling the central bank's sheets. They are generated when the
+// tests run, so that the layouts stay readable and reviewable here
+
+use std::path::Path;
+use rust_xlsxwriter::{Workbook, XlsxError};
+
+#[path = "../../src/testing.rs"]
+mod testing;
+
+pub use testing::TempDir;
+
+pub enum Cell {
+    Text(&'static str),
+    Number(f64),
+    Empty
+}
+
+use Cell::*;
+
+pub type Rows = &'static [&'static [Cell]];
+
+fn write_workbook(path: &Path, sheets: &[(&str, Rows)]) -> Result<(), XlsxError> {
+    let mut workbook = Workbook::new();
+    for (name, rows) in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(*name)?;
+        for (row_index, row) in rows.iter().enumerate() {
+            for (col_index, cell) in row.iter().enumerate() {
+                let (row_index, col_index) = (row_index as u32, col_index as u16);
+                match cell {
+                    Text(text) => { worksheet.write_string(row_index, col_index, *text)?; }
+                    Number(number) => { worksheet.write_number(row_index, col_index, *number)?; }
+                    Empty => {}
+                }
+            }
+        }
+    }
+    workbook.save(path)
+}
+
+/// A yearly row followed by months, which take their year from it. Also includes a
+/// contents sheet, which is skipped
+const MONTHLY: Rows = &[
+    &[Text("Table I: Deposits of scheduled banks")],
+    &[Text("Period"), Text("Demand deposits"), Text("Time deposits")],
+    &[Number(2022.0), Number(500.0), Number(700.0)],
+    &[Text("July"), Number(40.0), Number(60.0)],
+    &[Text("August"), Number(42.5), Number(61.0)],
+    &[Text("September"), Number(43.0), Empty],
+    &[Text("Source: Statistics Department, Bangladesh Bank")]
+];
+
+const CONTENTS: Rows = &[
+    &[Text("Contents")],
+    &[Text("Period"), Text("Nothing to see")],
+    &[Number(2022.0), Number(1.0)]
+];
+
+/// Fiscal years, ending at the first provisional year
+const FISCAL_YEARLY: Rows = &[
+    &[Text("Period"), Text("GDP growth")],
+    &[Text("2019-20"), Number(3.45)],
+    &[Text("2020-21"), Number(6.94)],
+    &[Text("2021-22P"), Number(7.1)]
+];
+
+/// Columns sharing a category label, written once over the first of them. The second
+/// column borrows the label from its neighbour to the left
+const LOOK_BEHIND: Rows = &[
+    &[Text("Period"), Text("Exports"), Empty, Text("Imports")],
+    &[Empty, Text("Goods"), Text("Services"), Text("Goods")],
+    &[Number(2021.0), Number(10.0), Number(5.0), Number(12.0)]
+];
+
+/// Two groups of columns, with spacers setting them apart from the periods and each other.
+/// The first spacer has no neighbour to borrow a label from
+const GROUPED: Rows = &[
+    &[Text("Period"), Empty, Text("Reserves"), Empty, Text("Remittances")],
+    &[Number(2020.0), Empty, Number(32.0), Empty, Number(18.2)]
+];
+
+/// Daily bond data, which cannot be read
+const UNSUPPORTED: Rows = &[
+    &[Text("BD(Govt) Treasury Bond")],
+    &[Text("Period"), Text("Yield")],
+    &[Number(2021.0), Number(8.0)]
+];
+
+/// Writes every fixture workbook to the directory
+pub fn write_all(data_dir: &Path) -> Result<(), XlsxError> {
+    write_workbook(&data_dir.join("2023-1.xlsx"), &[("Contents", CONTENTS), ("Deposits", MONTHLY)])?;
+    write_workbook(&data_dir.join("2023-2.xlsx"), &[("GDP", FISCAL_YEARLY), ("Trade", LOOK_BEHIND), ("Foreign exchange", GROUPED)])?;
+    write_workbook(&data_dir.join("2023-3.xlsx"), &[("Treasury", UNSUPPORTED)])
+}
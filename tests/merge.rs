@@ -0,0 +1,179 @@
+/*
+ * bank-data
+ * Copyright © 2023 Centre for Policy Dialogue
+ *
+ * bank-data is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * bank-data is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with bank-data. If not, see <https://www.gnu.org/licenses/>
+ * and navigate to version 3 of the GNU General Public License.
+ */
+
+mod fixtures;
+
+use std::collections::HashMap;
+use async_std::path::Path;
+use async_std::task;
+use bank_data::analysis::{AnalysisOptions, SheetExclusions};
+use bank_data::cache::MergeCache;
+use bank_data::merge::{self, MergeXL};
+use bank_data::output::OutputOptions;
+use fixtures::TempDir;
+
+/// Reads a written CSV into a map of (timestamp, column) to value, so that the order of
+/// rows and columns does not matter
+fn read_output(path: &std::path::Path) -> HashMap<(String, String), String> {
+    let content = std::fs::read_to_string(path).unwrap();
+    let mut lines = content.lines();
+    let header = lines.next().unwrap().split(',').collect::<Vec<_>>();
+    assert_eq!("timestamp-primary-key", header[0]);
+    let mut cells = HashMap::new();
+    for line in lines {
+        let fields = line.split(',').collect::<Vec<_>>();
+        assert_eq!(header.len(), fields.len(), "Malformed row {}", line);
+        for (column, value) in header.iter().zip(&fields).skip(1) {
+            cells.insert((fields[0].to_string(), column.to_string()), value.to_string());
+        }
+    }
+    cells
+}
+
+fn cells(expected: &[(&str, &str, &str)]) -> HashMap<(String, String), String> {
+    expected
+        .iter()
+        .map(|(timestamp, column, value)| ((timestamp.to_string(), column.to_string()), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn merge_fixtures() {
+    let root = TempDir::new("fixtures");
+    let data_dir = root.join("data");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    fixtures::write_all(&data_dir).unwrap();
+
+    let report = task::block_on(async {
+        let merge_xl = MergeXL::default();
+        let report = merge_xl.load_all_from(Path::new(&data_dir)).await.unwrap();
+        merge_xl.write_to(root.join("output").as_os_str(), &OutputOptions::default()).await.unwrap();
+        report
+    });
+    let inventory = task::block_on(merge::list_sheets(Path::new(&data_dir), &SheetExclusions::default())).unwrap();
+    let monthly = read_output(&root.join("output-monthly.csv"));
+    let calendar_yearly = read_output(&root.join("output-calendar-yearly.csv"));
+    let fiscal_yearly = read_output(&root.join("output-fiscal-yearly.csv"));
+    let written_files = std::fs::read_dir(&root).unwrap().count();
+
+    assert_eq!(cells(&[
+        ("2022-07", "Demand deposits", "40"), ("2022-07", "Time deposits", "60"),
+        ("2022-08", "Demand deposits", "42.5"), ("2022-08", "Time deposits", "61"),
+        ("2022-09", "Demand deposits", "43"), ("2022-09", "Time deposits", "NA")
+    ]), monthly);
+    assert_eq!(cells(&[
+        ("2022", "Demand deposits", "500"), ("2022", "Time deposits", "700"),
+        ("2022", "Exports.Goods", "NA"), ("2022", "Exports.Services", "NA"), ("2022", "Imports.Goods", "NA"),
+        ("2022", "Reserves", "NA"), ("2022", "Remittances", "NA"),
+        ("2021", "Demand deposits", "NA"), ("2021", "Time deposits", "NA"),
+        ("2021", "Exports.Goods", "10"), ("2021", "Exports.Services", "5"), ("2021", "Imports.Goods", "12"),
+        ("2021", "Reserves", "NA"), ("2021", "Remittances", "NA"),
+        ("2020", "Demand deposits", "NA"), ("2020", "Time deposits", "NA"),
+        ("2020", "Exports.Goods", "NA"), ("2020", "Exports.Services", "NA"), ("2020", "Imports.Goods", "NA"),
+        ("2020", "Reserves", "32"), ("2020", "Remittances", "18.2")
+    ]), calendar_yearly);
+    assert_eq!(cells(&[
+        ("2019-20", "GDP growth", "3.45"), ("2020-21", "GDP growth", "6.94")
+    ]), fiscal_yearly);
+    // The data directory and three outputs
+    assert_eq!(4, written_files);
+
+    // The contents sheet is excluded by name
+    assert_eq!(vec![
+        (String::from("2023-1.xlsx"), vec![String::from("Deposits")]),
+        (String::from("2023-2.xlsx"), vec![String::from("GDP"), String::from("Trade"), String::from("Foreign exchange")]),
+        (String::from("2023-3.xlsx"), vec![String::from("Treasury")])
+    ], inventory);
+
+    // Files load concurrently, so their sheets are reported in no particular order
+    let mut merged_sheets = report.merged_sheets()
+        .map(|(_, sheet)| (sheet.sheet().to_string(), sheet.rows()))
+        .collect::<Vec<_>>();
+    merged_sheets.sort();
+    assert_eq!(vec![
+        (String::from("Deposits"), 4), (String::from("Foreign exchange"), 1), (String::from("GDP"), 2),
+        (String::from("Trade"), 1)
+    ], merged_sheets);
+    assert_eq!(0, report.empty_sheets().count());
+
+    let mut json = serde_json::to_value(&report).unwrap();
+    json.as_object_mut().unwrap().remove("merged_sheets");
+    assert_eq!(
+        serde_json::json!({
+            "loaded_files": 3,
+            "loaded_sheets": 4,
+            "xls_unsupported": [],
+            "password_protected": [],
+            "error_counts": {"unsupported": 1, "no_data": 0, "other_failure": 0},
+            "redundant_rows": {"monthly": 0, "calendar-yearly": 0, "fiscal-yearly": 0},
+            "cached_files": 0,
+            "errors": [{
+                "file": data_dir.join("2023-3.xlsx").to_string_lossy(),
+                "sheet": "Treasury",
+                "kind": "Unsupported",
+                "reason": "Government securities/bonds sheet unsupported"
+            }]
+        }),
+        json
+    );
+}
+
+#[test]
+fn reuse_cached_rows() {
+    let root = TempDir::new("cache");
+    let data_dir = root.join("data");
+    let cache_dir = root.join("cache");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    fixtures::write_all(&data_dir).unwrap();
+
+    let merge = |prefix: &str| task::block_on(async {
+        let options = AnalysisOptions::default();
+        let cache = MergeCache::open(Path::new(&cache_dir), &options).await.unwrap();
+        let merge_xl = MergeXL::new(options);
+        let report = merge_xl.load_all_cached(Path::new(&data_dir), &cache).await.unwrap();
+        cache.save().await.unwrap();
+        merge_xl.write_to(root.join(prefix).as_os_str(), &OutputOptions::default()).await.unwrap();
+        report
+    });
+    let first = merge("first");
+    let second = merge("second");
+    assert_eq!(0, first.cached_files());
+    assert_eq!(3, second.cached_files());
+    assert_eq!(first.loaded_sheets(), second.loaded_sheets());
+    assert_eq!(first.errors().count(), second.errors().count());
+    for granularity in ["monthly", "calendar-yearly", "fiscal-yearly"] {
+        assert_eq!(
+            read_output(&root.join(format!("first-{}.csv", granularity))),
+            read_output(&root.join(format!("second-{}.csv", granularity)))
+        );
+    }
+
+    // The deposits are replaced, so the file is parsed again and its old rows dropped
+    std::fs::copy(data_dir.join("2023-3.xlsx"), data_dir.join("2023-1.xlsx")).unwrap();
+    let third = merge("third");
+    let cached_rows = std::fs::read_dir(&cache_dir)
+        .unwrap()
+        .filter(|file| file.as_ref().unwrap().file_name().to_string_lossy().starts_with("rows-"))
+        .count();
+    let has_monthly = root.join("third-monthly.csv").exists();
+    assert_eq!(2, third.cached_files());
+    assert!(!has_monthly);
+    // The copy shares its rows with the original
+    assert_eq!(2, cached_rows);
+}